@@ -0,0 +1,369 @@
+use ffi::YamlErrorType;
+use error::{YamlError, YamlErrorCode};
+use event::YamlEvent;
+use event::YamlEvent::*;
+use emitter::YamlEmitter;
+use parser::YamlResourceLimits;
+
+fn expansion_error(code: YamlErrorCode, message: String) -> YamlError {
+    YamlError {
+        kind: YamlErrorType::YAML_COMPOSER_ERROR,
+        code: code,
+        problem: Some(message),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+pub struct YamlMapEvents<I, F> {
+    iter: I,
+    f: F
+}
+
+impl<I, F> Iterator for YamlMapEvents<I, F>
+    where I: Iterator<Item=Result<YamlEvent, YamlError>>, F: FnMut(YamlEvent) -> YamlEvent
+{
+    type Item = Result<YamlEvent, YamlError>;
+
+    fn next(&mut self) -> Option<Result<YamlEvent, YamlError>> {
+        match self.iter.next() {
+            Some(Ok(evt)) => Some(Ok((self.f)(evt))),
+            res => res
+        }
+    }
+}
+
+pub struct YamlFilterEvents<I, F> {
+    iter: I,
+    predicate: F
+}
+
+impl<I, F> Iterator for YamlFilterEvents<I, F>
+    where I: Iterator<Item=Result<YamlEvent, YamlError>>, F: FnMut(&YamlEvent) -> bool
+{
+    type Item = Result<YamlEvent, YamlError>;
+
+    fn next(&mut self) -> Option<Result<YamlEvent, YamlError>> {
+        loop {
+            match self.iter.next() {
+                Some(Ok(evt)) => if (self.predicate)(&evt) {
+                    return Some(Ok(evt));
+                },
+                res => return res
+            }
+        }
+    }
+}
+
+pub struct YamlTeeEvents<'v, I> {
+    iter: I,
+    record: &'v mut Vec<YamlEvent>
+}
+
+impl<'v, I> Iterator for YamlTeeEvents<'v, I>
+    where I: Iterator<Item=Result<YamlEvent, YamlError>>
+{
+    type Item = Result<YamlEvent, YamlError>;
+
+    fn next(&mut self) -> Option<Result<YamlEvent, YamlError>> {
+        let res = self.iter.next();
+        match res {
+            Some(Ok(ref evt)) => self.record.push(evt.clone()),
+            _ => ()
+        }
+        res
+    }
+}
+
+pub struct YamlAliasExpander<I> {
+    iter: I,
+    limit: Option<uint>,
+    expanded: uint,
+    anchors: Vec<(String, Vec<YamlEvent>)>,
+    recording: Vec<(String, Vec<YamlEvent>, uint)>,
+    replay: Vec<YamlEvent>,
+    finished: bool
+}
+
+fn event_anchor(event: &YamlEvent) -> Option<&str> {
+    match *event {
+        YamlScalarEvent(ref param) => param.anchor.as_ref().map(|a| a.as_slice()),
+        YamlSequenceStartEvent(ref param) | YamlMappingStartEvent(ref param) =>
+            param.anchor.as_ref().map(|a| a.as_slice()),
+        _ => None
+    }
+}
+
+impl<I> YamlAliasExpander<I> {
+    fn record(&mut self, event: &YamlEvent) {
+        let (opens, closes) = match *event {
+            YamlSequenceStartEvent(_) | YamlMappingStartEvent(_) => (true, false),
+            YamlSequenceEndEvent | YamlMappingEndEvent => (false, true),
+            YamlDocumentStartEvent(..) => {
+                self.anchors.clear();
+                (false, false)
+            },
+            _ => (false, false)
+        };
+
+        for &mut (_, ref mut events, ref mut depth) in self.recording.iter_mut() {
+            events.push(strip_anchor(event.clone()));
+            if opens {
+                *depth += 1;
+            } else if closes {
+                *depth -= 1;
+            }
+        }
+
+        match event_anchor(event) {
+            Some(anchor) => self.recording.push((anchor.to_string(), vec![strip_anchor(event.clone())],
+                                                 if opens { 1 } else { 0 })),
+            None => ()
+        }
+
+        while self.recording.last().map_or(false, |&(_, _, depth)| depth == 0) {
+            let (anchor, events, _) = self.recording.pop().unwrap();
+            self.anchors.push((anchor, events));
+        }
+    }
+
+    fn expand(&mut self, anchor: String) -> Result<(), YamlError> {
+        let events = match self.anchors.iter().rev().find(|&&(ref a, _)| *a == anchor) {
+            Some(&(_, ref events)) => events.clone(),
+            None => return Err(expansion_error(YamlErrorCode::ComposerError,
+                                               format!("found undefined alias {}", anchor)))
+        };
+
+        self.expanded += events.len();
+        match self.limit {
+            Some(limit) if self.expanded > limit => {
+                return Err(expansion_error(YamlErrorCode::AliasExpansionLimitExceeded,
+                                           format!("expanding alias {} exceeds the limit of {} events", anchor, limit)));
+            },
+            _ => ()
+        }
+        self.replay.extend(events.into_iter().rev());
+        Ok(())
+    }
+}
+
+impl<I> Iterator for YamlAliasExpander<I>
+    where I: Iterator<Item=Result<YamlEvent, YamlError>>
+{
+    type Item = Result<YamlEvent, YamlError>;
+
+    fn next(&mut self) -> Option<Result<YamlEvent, YamlError>> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            let event = match self.replay.pop() {
+                Some(evt) => evt,
+                None => match self.iter.next() {
+                    Some(Ok(YamlAliasEvent(anchor))) => match self.expand(anchor) {
+                        Ok(()) => continue,
+                        Err(e) => {
+                            self.finished = true;
+                            return Some(Err(e));
+                        }
+                    },
+                    Some(Ok(evt)) => evt,
+                    res => {
+                        self.finished = true;
+                        return res;
+                    }
+                }
+            };
+
+            self.record(&event);
+            return Some(Ok(event));
+        }
+    }
+}
+
+pub trait YamlEventAdapters: Iterator<Item=Result<YamlEvent, YamlError>> + Sized {
+    fn map_events<F: FnMut(YamlEvent) -> YamlEvent>(self, f: F) -> YamlMapEvents<Self, F> {
+        YamlMapEvents {
+            iter: self,
+            f: f
+        }
+    }
+
+    fn filter_events<F: FnMut(&YamlEvent) -> bool>(self, predicate: F) -> YamlFilterEvents<Self, F> {
+        YamlFilterEvents {
+            iter: self,
+            predicate: predicate
+        }
+    }
+
+    fn tee_events<'v>(self, record: &'v mut Vec<YamlEvent>) -> YamlTeeEvents<'v, Self> {
+        YamlTeeEvents {
+            iter: self,
+            record: record
+        }
+    }
+
+    // alias_expansions caps the replayed events; the other limits are the parser's
+    fn expand_aliases(self, limits: &YamlResourceLimits) -> YamlAliasExpander<Self> {
+        YamlAliasExpander {
+            iter: self,
+            limit: limits.alias_expansions,
+            expanded: 0,
+            anchors: Vec::new(),
+            recording: Vec::new(),
+            replay: Vec::new(),
+            finished: false
+        }
+    }
+
+    fn emit_to(self, emitter: &mut YamlEmitter) -> Result<(), YamlError> {
+        for res in self {
+            try!(emitter.emit_event(&try!(res)));
+        }
+        emitter.flush()
+    }
+}
+
+impl<I: Iterator<Item=Result<YamlEvent, YamlError>>> YamlEventAdapters for I {}
+
+pub fn strip_anchor(event: YamlEvent) -> YamlEvent {
+    match event {
+        YamlScalarEvent(mut param) => {
+            param.anchor = None;
+            YamlScalarEvent(param)
+        },
+        YamlSequenceStartEvent(mut param) => {
+            param.anchor = None;
+            YamlSequenceStartEvent(param)
+        },
+        YamlMappingStartEvent(mut param) => {
+            param.anchor = None;
+            YamlMappingStartEvent(param)
+        },
+        evt => evt
+    }
+}
+
+pub fn event_tag(event: &YamlEvent) -> Option<&str> {
+    match *event {
+        YamlScalarEvent(ref param) => param.tag.as_ref().map(|t| t.as_slice()),
+        YamlSequenceStartEvent(ref param) | YamlMappingStartEvent(ref param) =>
+            param.tag.as_ref().map(|t| t.as_slice()),
+        _ => None
+    }
+}
+
+pub fn set_event_tag(event: YamlEvent, tag: Option<String>) -> YamlEvent {
+    match event {
+        YamlScalarEvent(mut param) => {
+            param.plain_implicit = tag.is_none();
+            param.quoted_implicit = tag.is_none();
+            param.tag = tag;
+            YamlScalarEvent(param)
+        },
+        YamlSequenceStartEvent(mut param) => {
+            param.implicit = tag.is_none();
+            param.tag = tag;
+            YamlSequenceStartEvent(param)
+        },
+        YamlMappingStartEvent(mut param) => {
+            param.implicit = tag.is_none();
+            param.tag = tag;
+            YamlMappingStartEvent(param)
+        },
+        evt => evt
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use adapters::{YamlEventAdapters, strip_anchor, event_tag, set_event_tag};
+    use event::YamlEvent::*;
+    use error::YamlErrorCode;
+    use parser::{YamlParser, YamlByteParser, YamlResourceLimits};
+    use emitter::YamlEmitter;
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+    use std::io::MemWriter;
+    use std::str;
+
+    #[test]
+    fn test_map_and_tee() {
+        let data = "[&a x, !old y]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut record = Vec::new();
+        let events: Vec<_> = parser.parse()
+            .tee_events(&mut record)
+            .map_events(strip_anchor)
+            .map_events(|evt| match event_tag(&evt) {
+                Some("!old") => set_event_tag(evt, Some("!new".to_string())),
+                _ => evt
+            })
+            .map(|res| res.unwrap()).collect();
+
+        assert_eq!(record.len(), events.len());
+        match (&record[3], &events[3]) {
+            (&YamlScalarEvent(ref before), &YamlScalarEvent(ref after)) => {
+                assert_eq!(Some("a".to_string()), before.anchor);
+                assert_eq!(None, after.anchor);
+            },
+            evts => panic!("unexpected events: {}", evts)
+        }
+        match events[4] {
+            YamlScalarEvent(ref param) => assert_eq!(Some("!new".to_string()), param.tag),
+            ref evt => panic!("unexpected event: {}", evt)
+        }
+    }
+
+    #[test]
+    fn test_filter_and_emit() {
+        let data = "[a, b, c]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut writer = MemWriter::new();
+        {
+            let mut emitter = YamlEmitter::init(&mut writer);
+            let res = parser.parse()
+                .filter_events(|evt| match *evt {
+                    YamlScalarEvent(ref param) => param.value.as_slice() != "b",
+                    _ => true
+                })
+                .emit_to(&mut *emitter);
+            assert!(res.is_ok());
+        }
+
+        assert_eq!("[a, c]\n", str::from_utf8(writer.get_ref()).unwrap());
+    }
+
+    #[test]
+    fn test_expand_aliases() {
+        let data = "a: &x [1, &y {b: c}]\nd: *x\ne: *y\n";
+        let mut limits = YamlResourceLimits::new();
+        limits.alias_expansions = Some(100);
+        let expanded: Vec<_> = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+            .expand_aliases(&limits).map_events(strip_anchor).map(|res| res.unwrap()).collect();
+        let plain = "a: [1, {b: c}]\nd: [1, {b: c}]\ne: {b: c}\n";
+        let expected: Vec<_> = YamlByteParser::init(plain.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+            .map(|res| res.unwrap()).collect();
+
+        assert_eq!(expected, expanded);
+    }
+
+    #[test]
+    fn test_expand_aliases_limit() {
+        let data = "- &a [x, x, x]\n- &b [*a, *a, *a]\n- &c [*b, *b, *b]\n";
+        let mut limits = YamlResourceLimits::new();
+        limits.alias_expansions = Some(20);
+        let mut stream = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().expand_aliases(&limits);
+
+        match stream.find(|res| res.is_err()) {
+            Some(Err(e)) => assert_eq!(YamlErrorCode::AliasExpansionLimitExceeded, e.code),
+            _ => panic!("should return an error")
+        }
+        assert!(stream.next().is_none());
+
+        let mut stream = YamlByteParser::init("*a".as_bytes(), YamlUtf8Encoding).unwrap().parse()
+            .expand_aliases(&YamlResourceLimits::new());
+        assert!(stream.any(|res| res.is_err()));
+    }
+}
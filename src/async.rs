@@ -0,0 +1,253 @@
+use ffi;
+use ffi::YamlErrorType;
+use error::YamlError;
+use event::YamlEvent;
+use push::YamlPushParser;
+use parser::YamlResourceLimits;
+
+use std::io::{IoResult, EndOfFile, ResourceUnavailable};
+
+pub use push::YamlPoll;
+
+pub trait YamlAsyncSource {
+    fn poll_read(&mut self, buf: &mut [u8]) -> YamlPoll<IoResult<uint>>;
+}
+
+pub struct YamlNonBlockingReader<R> {
+    reader: R
+}
+
+impl<R: Reader> YamlNonBlockingReader<R> {
+    pub fn new(reader: R) -> YamlNonBlockingReader<R> {
+        YamlNonBlockingReader {
+            reader: reader
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Reader> YamlAsyncSource for YamlNonBlockingReader<R> {
+    fn poll_read(&mut self, buf: &mut [u8]) -> YamlPoll<IoResult<uint>> {
+        match self.reader.read(buf) {
+            Err(ref e) if e.kind == ResourceUnavailable => YamlPoll::Pending,
+            res => YamlPoll::Ready(res)
+        }
+    }
+}
+
+pub struct YamlAsyncParser<S> {
+    source: S,
+    push: YamlPushParser,
+    chunk: Vec<u8>,
+    finished: bool
+}
+
+impl<S: YamlAsyncSource> YamlAsyncParser<S> {
+    pub fn new(source: S, encoding: ffi::YamlEncoding) -> YamlAsyncParser<S> {
+        YamlAsyncParser {
+            source: source,
+            push: YamlPushParser::new(encoding),
+            chunk: Vec::from_elem(4096, 0u8),
+            finished: false
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: YamlResourceLimits) {
+        self.push.set_limits(limits);
+    }
+
+    pub fn buffered(&self) -> uint {
+        self.push.buffered()
+    }
+
+    pub fn next_event(&mut self) -> YamlPoll<Option<Result<YamlEvent, YamlError>>> {
+        if self.finished {
+            return YamlPoll::Ready(None);
+        }
+
+        loop {
+            match self.push.next_event() {
+                YamlPoll::Pending => (),
+                ready => return ready
+            }
+
+            match self.source.poll_read(self.chunk.as_mut_slice()) {
+                YamlPoll::Pending => return YamlPoll::Pending,
+                // old_io readers may return nothing without being at the end
+                YamlPoll::Ready(Ok(0)) => return YamlPoll::Pending,
+                YamlPoll::Ready(Ok(size)) => self.push.feed(self.chunk.slice_to(size)),
+                YamlPoll::Ready(Err(ref e)) if e.kind == EndOfFile => self.push.finish(),
+                YamlPoll::Ready(Err(e)) => {
+                    self.finished = true;
+                    return YamlPoll::Ready(Some(Err(YamlError::from_io_error(YamlErrorType::YAML_READER_ERROR, e))));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async::{YamlAsyncParser, YamlAsyncSource, YamlNonBlockingReader, YamlPoll};
+    use parser::{YamlParser, YamlByteParser};
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+    use std::io;
+    use std::io::IoResult;
+
+    struct TrickleSource {
+        data: Vec<u8>,
+        pos: uint,
+        ready: bool
+    }
+
+    impl YamlAsyncSource for TrickleSource {
+        fn poll_read(&mut self, buf: &mut [u8]) -> YamlPoll<IoResult<uint>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                return YamlPoll::Pending;
+            }
+            if self.pos == self.data.len() {
+                return YamlPoll::Ready(Err(io::standard_error(io::EndOfFile)));
+            }
+            buf[0] = self.data[self.pos];
+            self.pos += 1;
+            YamlPoll::Ready(Ok(1))
+        }
+    }
+
+    #[test]
+    fn test_async_events() {
+        let data = "a: [1, 'two']\n--- b\n";
+        let source = TrickleSource { data: data.as_bytes().to_vec(), pos: 0, ready: false };
+        let mut parser = YamlAsyncParser::new(source, YamlUtf8Encoding);
+        let mut events = Vec::new();
+        let mut pending = 0u;
+
+        loop {
+            match parser.next_event() {
+                YamlPoll::Pending => pending += 1,
+                YamlPoll::Ready(Some(evt)) => events.push(evt.unwrap()),
+                YamlPoll::Ready(None) => break
+            }
+        }
+
+        let expected: Vec<_> = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+                                    .map(|evt| evt.unwrap()).collect();
+        assert_eq!(expected, events);
+        assert!(pending > 0);
+    }
+
+    #[test]
+    fn test_async_buffer_stays_bounded() {
+        let doc = "--- [1, 'two']\n";
+        let mut data = String::new();
+        for _ in range(0u, 200) {
+            data.push_str(doc);
+        }
+        let source = TrickleSource { data: data.as_bytes().to_vec(), pos: 0, ready: false };
+        let mut parser = YamlAsyncParser::new(source, YamlUtf8Encoding);
+        let mut events = 0u;
+
+        loop {
+            match parser.next_event() {
+                YamlPoll::Pending => assert!(parser.buffered() <= 2 * doc.len()),
+                YamlPoll::Ready(Some(evt)) => { evt.unwrap(); events += 1; },
+                YamlPoll::Ready(None) => break
+            }
+        }
+        assert_eq!(2 + 200 * 6, events);
+    }
+
+    struct BlockingOnceReader {
+        reader: io::BufReader<'static>,
+        blocked: bool
+    }
+
+    impl Reader for BlockingOnceReader {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+            if !self.blocked {
+                self.blocked = true;
+                return Err(io::standard_error(io::ResourceUnavailable));
+            }
+            self.reader.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_non_blocking_reader() {
+        let data = "[a, b]";
+        let reader = BlockingOnceReader { reader: io::BufReader::new(data.as_bytes()), blocked: false };
+        let mut parser = YamlAsyncParser::new(YamlNonBlockingReader::new(reader), YamlUtf8Encoding);
+
+        assert_eq!(YamlPoll::Pending, parser.next_event());
+        let mut events = Vec::new();
+        loop {
+            match parser.next_event() {
+                YamlPoll::Pending => panic!("reader should not block again"),
+                YamlPoll::Ready(Some(evt)) => events.push(evt.unwrap()),
+                YamlPoll::Ready(None) => break
+            }
+        }
+
+        let expected: Vec<_> = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+                                    .map(|evt| evt.unwrap()).collect();
+        assert_eq!(expected, events);
+    }
+
+    struct EmptyReadReader {
+        reader: io::BufReader<'static>,
+        empty_reads: uint
+    }
+
+    impl Reader for EmptyReadReader {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+            if self.empty_reads > 0 {
+                self.empty_reads -= 1;
+                return Ok(0);
+            }
+            self.reader.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_empty_read_is_not_end_of_input() {
+        let data = "[a, b]";
+        let reader = EmptyReadReader { reader: io::BufReader::new(data.as_bytes()), empty_reads: 2 };
+        let mut parser = YamlAsyncParser::new(YamlNonBlockingReader::new(reader), YamlUtf8Encoding);
+        let mut events = Vec::new();
+        let mut pending = 0u;
+
+        loop {
+            match parser.next_event() {
+                YamlPoll::Pending => pending += 1,
+                YamlPoll::Ready(Some(evt)) => events.push(evt.unwrap()),
+                YamlPoll::Ready(None) => break
+            }
+        }
+
+        let expected: Vec<_> = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+                                    .map(|evt| evt.unwrap()).collect();
+        assert_eq!(expected, events);
+        assert_eq!(2, pending);
+    }
+
+    #[test]
+    fn test_async_error() {
+        let data = "[1, 2";
+        let source = TrickleSource { data: data.as_bytes().to_vec(), pos: 0, ready: false };
+        let mut parser = YamlAsyncParser::new(source, YamlUtf8Encoding);
+
+        loop {
+            match parser.next_event() {
+                YamlPoll::Pending => (),
+                YamlPoll::Ready(Some(Ok(_))) => (),
+                YamlPoll::Ready(Some(Err(_))) => break,
+                YamlPoll::Ready(None) => panic!("should return an error")
+            }
+        }
+        assert_eq!(YamlPoll::Ready(None), parser.next_event());
+    }
+}
@@ -27,3 +27,106 @@ pub fn decode_buf(buf: *const ffi::yaml_char_t, length: libc::size_t) -> Option<
     }
 }
 
+pub fn char_to_byte_offset(s: &str, char_index: uint) -> Option<uint> {
+    let mut count = 0;
+    for (offset, _) in s.char_indices() {
+        if count == char_index {
+            return Some(offset);
+        }
+        count += 1;
+    }
+    if count == char_index { Some(s.len()) } else { None }
+}
+
+// like char_to_byte_offset, for UTF-8 bytes that may end mid-character
+pub fn utf8_byte_offset(bytes: &[u8], char_index: uint) -> uint {
+    let mut count = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b & 0xC0 != 0x80 {
+            if count == char_index {
+                return i;
+            }
+            count += 1;
+        }
+    }
+    bytes.len()
+}
+
+static BASE64_CHARS: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0] as uint;
+        let b1 = if chunk.len() > 1 { chunk[1] as uint } else { 0 };
+        let b2 = if chunk.len() > 2 { chunk[2] as uint } else { 0 };
+        let group = (b0 << 16) | (b1 << 8) | b2;
+
+        out.push(BASE64_CHARS[(group >> 18) & 0x3f] as char);
+        out.push(BASE64_CHARS[(group >> 12) & 0x3f] as char);
+        out.push(if chunk.len() > 1 { BASE64_CHARS[(group >> 6) & 0x3f] as char } else { '=' });
+        out.push(if chunk.len() > 2 { BASE64_CHARS[group & 0x3f] as char } else { '=' });
+    }
+    out
+}
+
+// whitespace is skipped since binary scalars are usually folded over several lines
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut group = 0u;
+    let mut count = 0u;
+    let mut padding = 0u;
+
+    for c in text.chars() {
+        let value = match c {
+            'A'...'Z' => c as uint - 'A' as uint,
+            'a'...'z' => c as uint - 'a' as uint + 26,
+            '0'...'9' => c as uint - '0' as uint + 52,
+            '+' => 62,
+            '/' => 63,
+            '=' => {
+                padding += 1;
+                0
+            },
+            ' ' | '\t' | '\r' | '\n' => continue,
+            _ => return None
+        };
+        if padding > 0 && c != '=' {
+            return None;
+        }
+
+        group = (group << 6) | value;
+        count += 1;
+        if count == 4 {
+            out.push((group >> 16) as u8);
+            if padding < 2 {
+                out.push((group >> 8) as u8);
+            }
+            if padding < 1 {
+                out.push(group as u8);
+            }
+            group = 0;
+            count = 0;
+        }
+    }
+
+    if count == 0 && padding <= 2 { Some(out) } else { None }
+}
+
+#[cfg(test)]
+mod test {
+    use codecs::{base64_encode, base64_decode};
+
+    #[test]
+    fn test_base64() {
+        let cases = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")];
+        for &(plain, encoded) in cases.iter() {
+            assert_eq!(encoded, base64_encode(plain.as_bytes()).as_slice());
+            assert_eq!(Some(plain.as_bytes().to_vec()), base64_decode(encoded));
+        }
+        assert_eq!(Some(b"foobar".to_vec()), base64_decode("Zm9v\n  YmFy\n"));
+        assert_eq!(None, base64_decode("Zm9"));
+        assert_eq!(None, base64_decode("Zg==Zg=="));
+        assert_eq!(None, base64_decode("Zm9v!"));
+    }
+}
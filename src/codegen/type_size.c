@@ -53,6 +53,42 @@ int main()
     printf("    YAML_MAPPING_END_EVENT\n");
     printf("}\n\n");
 
+    yaml_token_t dummy_token;
+
+    printf("#[allow(non_camel_case_types)]\n");
+    printf("pub type yaml_token_data_t = [c_int; %lu];\n", sizeof(dummy_token.data) / sizeof(int));
+    printf("pub fn new_yaml_token_data_t() -> yaml_token_data_t {\n");
+    printf("    [0; %lu]\n", sizeof(dummy_token.data) / sizeof(int));
+    printf("}\n\n");
+
+    printf("#[allow(non_camel_case_types)]\n");
+    printf("#[repr(u%lu)]\n", ((size_t)(&dummy_token.data) - (size_t)(&dummy_token)) * 8);
+    printf("#[derive(Show, PartialEq, Copy)]\n");
+    printf("pub enum yaml_token_type_t {\n");
+    printf("    YAML_NO_TOKEN = 0,\n\n");
+    printf("    YAML_STREAM_START_TOKEN,\n");
+    printf("    YAML_STREAM_END_TOKEN,\n\n");
+    printf("    YAML_VERSION_DIRECTIVE_TOKEN,\n");
+    printf("    YAML_TAG_DIRECTIVE_TOKEN,\n");
+    printf("    YAML_DOCUMENT_START_TOKEN,\n");
+    printf("    YAML_DOCUMENT_END_TOKEN,\n\n");
+    printf("    YAML_BLOCK_SEQUENCE_START_TOKEN,\n");
+    printf("    YAML_BLOCK_MAPPING_START_TOKEN,\n");
+    printf("    YAML_BLOCK_END_TOKEN,\n\n");
+    printf("    YAML_FLOW_SEQUENCE_START_TOKEN,\n");
+    printf("    YAML_FLOW_SEQUENCE_END_TOKEN,\n");
+    printf("    YAML_FLOW_MAPPING_START_TOKEN,\n");
+    printf("    YAML_FLOW_MAPPING_END_TOKEN,\n\n");
+    printf("    YAML_BLOCK_ENTRY_TOKEN,\n");
+    printf("    YAML_FLOW_ENTRY_TOKEN,\n");
+    printf("    YAML_KEY_TOKEN,\n");
+    printf("    YAML_VALUE_TOKEN,\n\n");
+    printf("    YAML_ALIAS_TOKEN,\n");
+    printf("    YAML_ANCHOR_TOKEN,\n");
+    printf("    YAML_TAG_TOKEN,\n");
+    printf("    YAML_SCALAR_TOKEN\n");
+    printf("}\n\n");
+
     yaml_parser_t dummy_parser;
 
     printf("#[allow(non_camel_case_types)]\n");
@@ -87,6 +123,8 @@ int main()
     printf("pub static YAML_DOCUMENT_T_SIZE:uint = %lu;\n", sizeof(yaml_document_t));
     printf("#[cfg(test)]\n");
     printf("pub static YAML_NODE_T_SIZE:uint = %lu;\n", sizeof(yaml_node_t));
+    printf("#[cfg(test)]\n");
+    printf("pub static YAML_TOKEN_T_SIZE:uint = %lu;\n", sizeof(yaml_token_t));
 
     return 0;
 }
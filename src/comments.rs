@@ -0,0 +1,102 @@
+use ffi;
+use error::{YamlError, YamlMark};
+use parser::{YamlParser, YamlByteParser};
+use tokens::YamlMarkedToken;
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlCommentAttachment {
+    TrailingComment,
+    LeadingComment
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlComment {
+    pub text: String,
+    pub mark: YamlMark,
+    pub attachment: YamlCommentAttachment,
+    pub token_index: uint
+}
+
+// Comments only ever appear in the gaps that libyaml leaves between tokens, so
+// each gap is walked with the position of the token that precedes it.
+fn gap_comments(chars: &[char], prev: &YamlMarkedToken, prev_index: uint, next_start: uint,
+    comments: &mut Vec<YamlComment>)
+{
+    let mut index = prev.end_mark.index;
+    let mut line = prev.end_mark.line;
+    let mut column = prev.end_mark.column;
+
+    while index < next_start && index < chars.len() {
+        if chars[index] == '#' {
+            let mark = YamlMark { index: index, line: line, column: column };
+            let mut end = index + 1;
+            while end < next_start && end < chars.len() && chars[end] != '\n' && chars[end] != '\r' {
+                end += 1;
+            }
+            let trailing = line == prev.end_mark.line && prev.end_mark.index > prev.start_mark.index;
+            comments.push(YamlComment {
+                text: chars.slice(index + 1, end).iter().map(|&c| c).collect(),
+                mark: mark,
+                attachment: if trailing { YamlCommentAttachment::TrailingComment } else { YamlCommentAttachment::LeadingComment },
+                token_index: if trailing { prev_index } else { prev_index + 1 }
+            });
+            column += end - index;
+            index = end;
+            continue;
+        }
+
+        match chars[index] {
+            '\n' => { line += 1; column = 0; },
+            '\r' if index + 1 < chars.len() && chars[index + 1] == '\n' => (),
+            '\r' => { line += 1; column = 0; },
+            _ => column += 1
+        }
+        index += 1;
+    }
+}
+
+pub fn capture_comments(source: &str) -> Result<Vec<YamlComment>, YamlError> {
+    let parser = try!(YamlByteParser::init(source.as_bytes(), ffi::YamlEncoding::YamlUtf8Encoding));
+    let tokens: Vec<YamlMarkedToken> = try!(parser.tokens().collect());
+    let chars: Vec<char> = source.chars().collect();
+    let mut comments = Vec::new();
+
+    for (i, pair) in tokens.windows(2).enumerate() {
+        gap_comments(chars.as_slice(), &pair[0], i, pair[1].start_mark.index, &mut comments);
+    }
+    Ok(comments)
+}
+
+#[cfg(test)]
+mod test {
+    use comments::{capture_comments, YamlComment};
+    use comments::YamlCommentAttachment::*;
+    use error::YamlMark;
+
+    #[test]
+    fn test_capture_comments() {
+        let source = "# header\na: 1 # one\n# before b\nb: 'x # not a comment'\nc: [d, # inside\n  e]\n";
+        let comments = capture_comments(source).unwrap();
+
+        let texts: Vec<&str> = comments.iter().map(|c| c.text.as_slice()).collect();
+        assert_eq!(vec![" header", " one", " before b", " inside"], texts);
+
+        assert_eq!(YamlComment {
+            text: " header".to_string(),
+            mark: YamlMark { index: 0, line: 0, column: 0 },
+            attachment: LeadingComment,
+            token_index: 1
+        }, comments[0]);
+        assert_eq!(TrailingComment, comments[1].attachment);
+        assert_eq!(YamlMark { index: 14, line: 1, column: 5 }, comments[1].mark);
+        assert_eq!(LeadingComment, comments[2].attachment);
+        assert_eq!(2, comments[2].mark.line);
+        assert_eq!(TrailingComment, comments[3].attachment);
+    }
+
+    #[test]
+    fn test_no_comments() {
+        assert_eq!(0, capture_comments("a: '#b'\nc: d#e\n").unwrap().len());
+        assert!(capture_comments("a: [b").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use ffi;
+use error::{YamlError, YamlMark};
+use event::{YamlEvent, YamlSequenceParam, YamlScalarParam};
+use event::YamlEvent::*;
+use parser::{YamlParser, YamlIoParser};
+use schema::YamlResolver;
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+
+use std::num::Float;
+
+#[derive(Show, PartialEq)]
+pub struct YamlStreamDivergence {
+    pub left: YamlEvent,
+    pub left_mark: YamlMark,
+    pub right: YamlEvent,
+    pub right_mark: YamlMark
+}
+
+#[derive(Show, PartialEq)]
+enum NormalizedEvent {
+    StreamStart,
+    StreamEnd,
+    DocumentStart,
+    DocumentEnd,
+    Alias(Option<uint>),
+    Scalar(Option<String>, YamlStandardData),
+    NanScalar(Option<String>),
+    SequenceStart(Option<String>),
+    SequenceEnd,
+    MappingStart(Option<String>),
+    MappingEnd,
+}
+
+fn register_anchor(anchor: &Option<String>, anchors: &mut Vec<String>) {
+    match *anchor {
+        Some(ref name) => anchors.push(name.clone()),
+        None => ()
+    }
+}
+
+fn collection_tag(param: &YamlSequenceParam) -> Option<String> {
+    match param.tag {
+        Some(ref tag) if tag.as_slice() != "!" => Some(tag.clone()),
+        _ => None
+    }
+}
+
+fn scalar_key(param: &YamlScalarParam, resolver: &YamlResolver) -> NormalizedEvent {
+    let tag = match param.tag {
+        Some(ref tag) if tag.as_slice() != "!" => Some(tag.clone()),
+        _ => None
+    };
+    // scalars match on what they resolve to, so `0x10` and `16` are the same
+    // integer; NaN never equals itself, so it gets its own key
+    match param.to_scalar(None).typed_value_with(resolver) {
+        Ok(YamlFloat(f)) if f.is_nan() => NormalizedEvent::NanScalar(tag),
+        Ok(value) => NormalizedEvent::Scalar(tag, value),
+        Err(_) => NormalizedEvent::Scalar(tag, YamlString(param.value.clone()))
+    }
+}
+
+fn normalize(event: &YamlEvent, anchors: &mut Vec<String>, resolver: &YamlResolver) -> NormalizedEvent {
+    match *event {
+        YamlNoEvent | YamlStreamEndEvent => NormalizedEvent::StreamEnd,
+        YamlStreamStartEvent(_) => NormalizedEvent::StreamStart,
+        YamlDocumentStartEvent(..) => {
+            // anchors are scoped to their document
+            anchors.clear();
+            NormalizedEvent::DocumentStart
+        },
+        YamlDocumentEndEvent(_) => NormalizedEvent::DocumentEnd,
+        YamlAliasEvent(ref name) => NormalizedEvent::Alias(anchors.iter().rposition(|a| a == name)),
+        YamlScalarEvent(ref param) => {
+            register_anchor(&param.anchor, anchors);
+            scalar_key(param, resolver)
+        },
+        YamlSequenceStartEvent(ref param) => {
+            register_anchor(&param.anchor, anchors);
+            NormalizedEvent::SequenceStart(collection_tag(param))
+        },
+        YamlSequenceEndEvent => NormalizedEvent::SequenceEnd,
+        YamlMappingStartEvent(ref param) => {
+            register_anchor(&param.anchor, anchors);
+            NormalizedEvent::MappingStart(collection_tag(param))
+        },
+        YamlMappingEndEvent => NormalizedEvent::MappingEnd,
+    }
+}
+
+fn next_event<P: YamlParser>(parser: &mut P) -> Result<(YamlEvent, YamlMark), YamlError> {
+    unsafe {
+        match parser.parse_marked_event() {
+            Some((evt, start_mark, _)) => Ok((evt, start_mark)),
+            None => Err(parser.get_error())
+        }
+    }
+}
+
+pub fn stream_equal(left_reader: &mut Reader, right_reader: &mut Reader)
+    -> Result<Option<YamlStreamDivergence>, YamlError>
+{
+    let mut left = try!(YamlIoParser::init(left_reader, ffi::YamlEncoding::YamlAnyEncoding));
+    let mut right = try!(YamlIoParser::init(right_reader, ffi::YamlEncoding::YamlAnyEncoding));
+    let left_resolver = left.resolver();
+    let right_resolver = right.resolver();
+    let mut left_anchors = Vec::new();
+    let mut right_anchors = Vec::new();
+
+    loop {
+        let (left_event, left_mark) = try!(next_event(&mut *left));
+        let (right_event, right_mark) = try!(next_event(&mut *right));
+
+        let normalized = normalize(&left_event, &mut left_anchors, &left_resolver);
+        if normalized != normalize(&right_event, &mut right_anchors, &right_resolver) {
+            return Ok(Some(YamlStreamDivergence {
+                left: left_event,
+                left_mark: left_mark,
+                right: right_event,
+                right_mark: right_mark
+            }));
+        }
+
+        if normalized == NormalizedEvent::StreamEnd {
+            return Ok(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use compare::stream_equal;
+    use std::io::BufReader;
+
+    fn compare(left: &str, right: &str) -> Option<(uint, uint)> {
+        let mut left_reader = BufReader::new(left.as_bytes());
+        let mut right_reader = BufReader::new(right.as_bytes());
+        stream_equal(&mut left_reader, &mut right_reader).unwrap().map(|d| {
+            (d.left_mark.column, d.right_mark.column)
+        })
+    }
+
+    #[test]
+    fn test_equal_streams() {
+        assert_eq!(None, compare("[1, 2, {a: b}]", "- 1\n- 2\n- a: b\n"));
+        assert_eq!(None, compare("[&x 1, *x]", "[&y 1, *y]"));
+        assert_eq!(None, compare("[0x10, yes, .nan, 'a']", "[16, true, .NaN, \"a\"]"));
+        assert_eq!(None, compare("--- &a 1\n--- [&b 2, *b]\n", "--- 1\n--- [&b 2, *b]\n"));
+    }
+
+    #[test]
+    fn test_divergent_streams() {
+        assert_eq!(Some((3, 4)), compare("a: 1", "a:  2"));
+        assert_eq!(Some((4, 4)), compare("[1, 2]", "[1, '2']"));
+    }
+}
@@ -1,12 +1,15 @@
 use document;
 use document::{YamlNode, YamlNodeData};
 use ffi::{YamlErrorType, YamlScalarStyle};
-use error::{YamlMark, YamlError, YamlErrorContext};
+use error::{YamlMark, YamlError, YamlErrorCode, YamlErrorContext};
+use parser::YamlResourceLimits;
 
+use schema::{YamlResolver, YamlSchema};
+use scalar::YamlScalar;
+
+use std::cell::{Cell, RefCell};
 use std::num::FromStrRadix;
-use std::f64;
 use std::char;
-use regex::Regex;
 
 pub trait YamlConstructor<T, E> {
     fn construct_scalar(&self, scalar: document::YamlScalarData) -> Result<T, E>;
@@ -22,7 +25,7 @@ pub trait YamlConstructor<T, E> {
     }
 }
 
-#[derive(PartialEq, Show)]
+#[derive(PartialEq, Show, Clone)]
 pub enum YamlStandardData {
     YamlInteger(int),
     YamlFloat(f64),
@@ -31,12 +34,43 @@ pub enum YamlStandardData {
     YamlBool(bool),
     YamlSequence(Vec<YamlStandardData>),
     YamlMapping(Vec<(YamlStandardData, YamlStandardData)>),
+    YamlSet(Vec<YamlStandardData>),
+    YamlOrderedMap(Vec<(YamlStandardData, YamlStandardData)>),
+    YamlPairs(Vec<(YamlStandardData, YamlStandardData)>),
+}
+
+pub trait YamlTagHandler {
+    fn construct_tagged(&self, scalar: &YamlScalar) -> Result<YamlStandardData, YamlError>;
+}
+
+impl<F: Fn(&YamlScalar) -> Result<YamlStandardData, YamlError>> YamlTagHandler for F {
+    fn construct_tagged(&self, scalar: &YamlScalar) -> Result<YamlStandardData, YamlError> {
+        (*self)(scalar)
+    }
 }
 
-#[derive(Copy)]
-pub struct YamlStandardConstructor;
+// sequences and mappings are constructed as usual, then passed to the handler
+pub trait YamlCollectionTagHandler {
+    fn construct_tagged(&self, value: YamlStandardData) -> Result<YamlStandardData, YamlError>;
+}
 
-fn standard_error(message: String, mark: &YamlMark) -> YamlError {
+impl<F: Fn(YamlStandardData) -> Result<YamlStandardData, YamlError>> YamlCollectionTagHandler for F {
+    fn construct_tagged(&self, value: YamlStandardData) -> Result<YamlStandardData, YamlError> {
+        (*self)(value)
+    }
+}
+
+pub struct YamlStandardConstructor {
+    node_limit: Option<uint>,
+    constructed: Cell<uint>,
+    stack: RefCell<Vec<int>>,
+    resolver: Option<YamlResolver>,
+    merge_keys: bool,
+    tag_handlers: Vec<(String, Box<YamlTagHandler + 'static>)>,
+    collection_handlers: Vec<(String, Box<YamlCollectionTagHandler + 'static>)>
+}
+
+fn standard_error(kind: YamlErrorType, code: YamlErrorCode, message: String, mark: &YamlMark) -> YamlError {
     let context = YamlErrorContext {
         byte_offset: mark.index,
         problem_mark: *mark,
@@ -45,16 +79,165 @@ fn standard_error(message: String, mark: &YamlMark) -> YamlError {
     };
 
     YamlError {
-        kind: YamlErrorType::YAML_PARSER_ERROR,
+        kind: kind,
+        code: code,
         problem: Some(message),
         io_error: None,
-        context: Some(context)
+        context: Some(context),
+        filename: None
+    }
+}
+
+fn collection_error(message: &str, mark: &YamlMark) -> YamlError {
+    standard_error(YamlErrorType::YAML_COMPOSER_ERROR, YamlErrorCode::InvalidNode, message.to_string(), mark)
+}
+
+fn escape_error(message: String, mark: &YamlMark) -> YamlError {
+    standard_error(YamlErrorType::YAML_PARSER_ERROR, YamlErrorCode::InvalidEscape, message, mark)
+}
+
+// !!omap and !!pairs are sequences of single-pair mappings
+fn single_pairs(items: Vec<YamlStandardData>, unique: bool, mark: &YamlMark)
+    -> Result<Vec<(YamlStandardData, YamlStandardData)>, YamlError>
+{
+    let mut pairs: Vec<(YamlStandardData, YamlStandardData)> = Vec::new();
+    for item in items.into_iter() {
+        let pair = match item {
+            YamlStandardData::YamlMapping(mut entry) if entry.len() == 1 => entry.pop().unwrap(),
+            _ => return Err(collection_error("expected a single-pair mapping", mark))
+        };
+        if unique && pairs.iter().any(|&(ref key, _)| *key == pair.0) {
+            return Err(collection_error("found duplicate key in ordered map", mark));
+        }
+        pairs.push(pair);
+    }
+    Ok(pairs)
+}
+
+fn is_merge_key(node: &YamlNode) -> bool {
+    match *node {
+        YamlNode::YamlScalarNode(ref scalar) => match scalar.tag().as_ref().map(|t| t.as_slice()) {
+            Some("tag:yaml.org,2002:merge") => true,
+            _ => scalar.style() == YamlScalarStyle::YamlPlainScalarStyle && scalar.get_value().as_slice() == "<<"
+        },
+        _ => false
+    }
+}
+
+// earlier sources win over later ones, and keys already merged are skipped
+fn merge_into(merged: &mut Vec<(YamlStandardData, YamlStandardData)>, source: YamlStandardData,
+    mark: &YamlMark) -> Result<(), YamlError>
+{
+    let sources = match source {
+        mapping @ YamlStandardData::YamlMapping(_) => vec![mapping],
+        YamlStandardData::YamlSequence(items) => items,
+        _ => return Err(collection_error("expected a mapping or list of mappings for merging", mark))
+    };
+    for source in sources.into_iter() {
+        let pairs = match source {
+            YamlStandardData::YamlMapping(pairs) => pairs,
+            _ => return Err(collection_error("expected a mapping for merging", mark))
+        };
+        for (key, value) in pairs.into_iter() {
+            if !merged.iter().any(|&(ref k, _)| *k == key) {
+                merged.push((key, value));
+            }
+        }
     }
+    Ok(())
 }
 
 impl YamlStandardConstructor {
     pub fn new() -> YamlStandardConstructor {
-        YamlStandardConstructor
+        YamlStandardConstructor {
+            node_limit: None,
+            constructed: Cell::new(0),
+            stack: RefCell::new(Vec::new()),
+            resolver: None,
+            merge_keys: true,
+            tag_handlers: Vec::new(),
+            collection_handlers: Vec::new()
+        }
+    }
+
+    // aliases are expanded during construction, so the limit counts every
+    // node visit made while building one root rather than nodes in the document
+    pub fn with_node_limit(limit: uint) -> YamlStandardConstructor {
+        YamlStandardConstructor {
+            node_limit: Some(limit),
+            constructed: Cell::new(0),
+            stack: RefCell::new(Vec::new()),
+            resolver: None,
+            merge_keys: true,
+            tag_handlers: Vec::new(),
+            collection_handlers: Vec::new()
+        }
+    }
+
+    pub fn with_limits(limits: &YamlResourceLimits) -> YamlStandardConstructor {
+        YamlStandardConstructor {
+            node_limit: limits.alias_expansions,
+            constructed: Cell::new(0),
+            stack: RefCell::new(Vec::new()),
+            resolver: None,
+            merge_keys: true,
+            tag_handlers: Vec::new(),
+            collection_handlers: Vec::new()
+        }
+    }
+
+    pub fn with_resolver(mut self, resolver: YamlResolver) -> YamlStandardConstructor {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn with_merge_keys(mut self, enabled: bool) -> YamlStandardConstructor {
+        self.merge_keys = enabled;
+        self
+    }
+
+    pub fn register_tag<F>(&mut self, tag: &str, handler: F)
+        where F: Fn(&YamlScalar) -> Result<YamlStandardData, YamlError> + 'static
+    {
+        self.tag_handlers.retain(|&(ref t, _)| t.as_slice() != tag);
+        self.tag_handlers.push((tag.to_string(), box handler as Box<YamlTagHandler + 'static>));
+    }
+
+    pub fn register_collection_tag<F>(&mut self, tag: &str, handler: F)
+        where F: Fn(YamlStandardData) -> Result<YamlStandardData, YamlError> + 'static
+    {
+        self.collection_handlers.retain(|&(ref t, _)| t.as_slice() != tag);
+        self.collection_handlers.push((tag.to_string(), box handler as Box<YamlCollectionTagHandler + 'static>));
+    }
+
+    fn handle_collection(&self, tag: Option<String>, value: YamlStandardData) -> Result<YamlStandardData, YamlError> {
+        match tag.and_then(|tag| self.collection_handlers.iter().find(|&&(ref t, _)| *t == tag)) {
+            Some(&(_, ref handler)) => handler.construct_tagged(value),
+            None => Ok(value)
+        }
+    }
+
+    fn count_node(&self, mark: YamlMark) -> Result<(), YamlError> {
+        let constructed = self.constructed.get() + 1;
+        self.constructed.set(constructed);
+        match self.node_limit {
+            Some(limit) if constructed > limit => {
+                Err(standard_error(YamlErrorType::YAML_COMPOSER_ERROR, YamlErrorCode::NodeLimitExceeded,
+                                   format!("constructing more than {} nodes", limit), &mark))
+            },
+            _ => Ok(())
+        }
+    }
+
+    // collections being constructed, innermost last; an alias back to one of
+    // them would recurse forever
+    fn enter(&self, index: int, mark: YamlMark) -> Result<(), YamlError> {
+        if self.stack.borrow().contains(&index) {
+            return Err(standard_error(YamlErrorType::YAML_COMPOSER_ERROR, YamlErrorCode::RecursiveAlias,
+                                      "found a recursive alias".to_string(), &mark));
+        }
+        self.stack.borrow_mut().push(index);
+        Ok(())
     }
 
     fn parse_double_quoted(value: &str, mark: &YamlMark) -> Result<String, YamlError> {
@@ -67,7 +250,7 @@ impl YamlStandardConstructor {
                 Some('\\') => {
                     // escape sequences
                     match it.next() {
-                        None => return Err(standard_error(
+                        None => return Err(escape_error(
                                     "unexpected end of string after escape".to_string(),
                                     mark
                                 )),
@@ -88,7 +271,7 @@ impl YamlStandardConstructor {
                             let code:String = it.take(2).collect();
                             match parse_escape_sequence(code.as_slice(), 2) {
                                 Some(c) => buf.push(c),
-                                None => return Err(standard_error(
+                                None => return Err(escape_error(
                                             format!("invalid escape sequence {}", code),
                                             mark
                                         ))
@@ -98,7 +281,7 @@ impl YamlStandardConstructor {
                             let code:String = it.take(4).collect();
                             match parse_escape_sequence(code.as_slice(), 4) {
                                 Some(c) => buf.push(c),
-                                None => return Err(standard_error(
+                                None => return Err(escape_error(
                                             format!("invalid escape sequence {}", code),
                                             mark
                                         ))
@@ -108,7 +291,7 @@ impl YamlStandardConstructor {
                             let code:String = it.take(8).collect();
                             match parse_escape_sequence(code.as_slice(), 8) {
                                 Some(c) => buf.push(c),
-                                None => return Err(standard_error(
+                                None => return Err(escape_error(
                                             format!("invalid escape sequence {}", code),
                                             mark
                                         ))
@@ -130,114 +313,134 @@ fn parse_escape_sequence(rep: &str, expected_len: uint) -> Option<char> {
     }
 }
 
-fn parse_int(sign: &str, data: &str, radix: uint) -> int {
-    let sign_flag = if sign == "-" {
-            -1
-        } else {
-            1
-        };
-
-    let filtered:String = data.chars().filter(|&c| c != '_').collect();
-    let unsigned:int = FromStrRadix::from_str_radix(filtered.as_slice(), radix).unwrap();
-    return unsigned * sign_flag;
+pub fn resolve_scalar(value: String, style: YamlScalarStyle, mark: &YamlMark) -> Result<YamlStandardData, YamlError> {
+    resolve_scalar_with(&YamlResolver::new(YamlSchema::Yaml11), value, style, mark)
 }
 
-fn parse_float(sign: &str, data: &str) -> f64 {
-    let unsigned:f64 = data.parse().unwrap();
-    if sign == "-" {
-        return -unsigned;
-    } else {
-        return unsigned;
+pub fn resolve_scalar_with(resolver: &YamlResolver, value: String, style: YamlScalarStyle, mark: &YamlMark) -> Result<YamlStandardData, YamlError> {
+    match style {
+        YamlScalarStyle::YamlPlainScalarStyle => {
+            Ok(resolver.resolve_plain(value.as_slice()))
+        },
+        YamlScalarStyle::YamlDoubleQuotedScalarStyle => {
+            YamlStandardConstructor::parse_double_quoted(value.as_slice(), mark).map(YamlStandardData::YamlString)
+        },
+        _ => {
+            Ok(YamlStandardData::YamlString(value))
+        }
     }
 }
 
-static DEC_INT:Regex = regex!(r"^[-+]?(0|[1-9][0-9_]*)$");
-static OCT_INT:Regex = regex!(r"^([-+]?)0o?([0-7_]+)$");
-static HEX_INT:Regex = regex!(r"^([-+]?)0x([0-9a-fA-F_]+)$");
-static BIN_INT:Regex = regex!(r"^([-+]?)0b([0-1_]+)$");
-static FLOAT_PATTERN:Regex = regex!(r"^([-+]?)(\.[0-9]+|[0-9]+(\.[0-9]*)?([eE][-+]?[0-9]+)?)$");
-static POS_INF:Regex = regex!(r"^[+]?(\.inf|\.Inf|\.INF)$");
-static NEG_INF:Regex = regex!(r"^-(\.inf|\.Inf|\.INF)$");
-static NAN_PATTERN:Regex = regex!(r"^(\.nan|\.NaN|\.NAN)$");
-static NULL_PATTERN:Regex = regex!(r"^(null|Null|NULL|~)$");
-static TRUE_PATTERN:Regex = regex!(r"^(true|True|TRUE|yes|Yes|YES)$");
-static FALSE_PATTERN:Regex = regex!(r"^(false|False|FALSE|no|No|NO)$");
+pub fn resolve_tagged_scalar_with(resolver: &YamlResolver, tag: Option<&str>, value: String,
+                                  style: YamlScalarStyle, mark: &YamlMark) -> Result<YamlStandardData, YamlError> {
+    let core = match tag {
+        Some(t @ "tag:yaml.org,2002:str") | Some(t @ "tag:yaml.org,2002:int") |
+        Some(t @ "tag:yaml.org,2002:float") | Some(t @ "tag:yaml.org,2002:bool") |
+        Some(t @ "tag:yaml.org,2002:null") => t,
+        _ => return resolve_scalar_with(resolver, value, style, mark)
+    };
 
-impl YamlConstructor<YamlStandardData, YamlError> for YamlStandardConstructor {
-    fn construct_scalar(&self, scalar: document::YamlScalarData) -> Result<YamlStandardData, YamlError> {
-        let value = scalar.get_value();
-        let mark = scalar.start_mark();
-
-        match scalar.style() {
-            YamlScalarStyle::YamlPlainScalarStyle => {
-                match BIN_INT.captures(value.as_slice()) {
-                    Some(caps) => return Ok(YamlStandardData::YamlInteger(parse_int(
-                                caps.at(1).unwrap(), caps.at(2).unwrap(), 2))),
-                    None => ()
-                };
-                match OCT_INT.captures(value.as_slice()) {
-                    Some(caps) => return Ok(YamlStandardData::YamlInteger(parse_int(
-                                caps.at(1).unwrap(), caps.at(2).unwrap(), 8))),
-                    None => ()
-                };
-                match HEX_INT.captures(value.as_slice()) {
-                    Some(caps) => return Ok(YamlStandardData::YamlInteger(parse_int(
-                                caps.at(1).unwrap(), caps.at(2).unwrap(), 16))),
-                    None => ()
-                };
-
-                if DEC_INT.is_match(value.as_slice()) {
-                    return Ok(YamlStandardData::YamlInteger(parse_int("", value.as_slice(), 10)));
-                }
+    let text = match style {
+        YamlScalarStyle::YamlDoubleQuotedScalarStyle =>
+            try!(YamlStandardConstructor::parse_double_quoted(value.as_slice(), mark)),
+        _ => value
+    };
 
-                match FLOAT_PATTERN.captures(value.as_slice()) {
-                    Some(caps) => return Ok(YamlStandardData::YamlFloat(parse_float(
-                                caps.at(1).unwrap(), caps.at(2).unwrap()))),
-                    None => ()
-                };
-
-                if POS_INF.is_match(value.as_slice()) {
-                    Ok(YamlStandardData::YamlFloat(f64::INFINITY))
-                } else if NEG_INF.is_match(value.as_slice()) {
-                    Ok(YamlStandardData::YamlFloat(f64::NEG_INFINITY))
-                } else if NAN_PATTERN.is_match(value.as_slice()) {
-                    Ok(YamlStandardData::YamlFloat(f64::NAN))
-                } else if NULL_PATTERN.is_match(value.as_slice()) {
-                    Ok(YamlStandardData::YamlNull)
-                } else if TRUE_PATTERN.is_match(value.as_slice()) {
-                    Ok(YamlStandardData::YamlBool(true))
-                } else if FALSE_PATTERN.is_match(value.as_slice()) {
-                    Ok(YamlStandardData::YamlBool(false))
-                } else {
-                    Ok(YamlStandardData::YamlString(value))
-                }
+    // an explicit core tag decides the type; the value only has to fit it
+    match (core, resolver.resolve_plain(text.as_slice())) {
+        ("tag:yaml.org,2002:str", _) => Ok(YamlStandardData::YamlString(text)),
+        ("tag:yaml.org,2002:int", v @ YamlStandardData::YamlInteger(_)) => Ok(v),
+        ("tag:yaml.org,2002:float", YamlStandardData::YamlInteger(i)) => Ok(YamlStandardData::YamlFloat(i as f64)),
+        ("tag:yaml.org,2002:float", v @ YamlStandardData::YamlFloat(_)) => Ok(v),
+        ("tag:yaml.org,2002:bool", v @ YamlStandardData::YamlBool(_)) => Ok(v),
+        ("tag:yaml.org,2002:null", v @ YamlStandardData::YamlNull) => Ok(v),
+        _ => Err(standard_error(YamlErrorType::YAML_COMPOSER_ERROR, YamlErrorCode::InvalidNode,
+                                format!("{} is not a valid {}", text, core), mark))
+    }
+}
+
+impl YamlConstructor<YamlStandardData, YamlError> for YamlStandardConstructor {
+    fn construct<'r>(&self, node: document::YamlNode<'r>) -> Result<YamlStandardData, YamlError> {
+        // each document's root starts a fresh count
+        if self.stack.borrow().is_empty() {
+            self.constructed.set(0);
+        }
+        match node {
+            YamlNode::YamlScalarNode(scalar) => {
+                try!(self.count_node(scalar.start_mark()));
+                self.construct_scalar(scalar)
             },
-            YamlScalarStyle::YamlDoubleQuotedScalarStyle => {
-                YamlStandardConstructor::parse_double_quoted(value.as_slice(), &mark).map(YamlStandardData::YamlString)
+            YamlNode::YamlSequenceNode(sequence) => {
+                try!(self.count_node(sequence.start_mark()));
+                try!(self.enter(sequence.index(), sequence.start_mark()));
+                let res = self.construct_sequence(sequence);
+                self.stack.borrow_mut().pop();
+                res
             },
-            _ => {
-                Ok(YamlStandardData::YamlString(value))
+            YamlNode::YamlMappingNode(mapping) => {
+                try!(self.count_node(mapping.start_mark()));
+                try!(self.enter(mapping.index(), mapping.start_mark()));
+                let res = self.construct_mapping(mapping);
+                self.stack.borrow_mut().pop();
+                res
             }
         }
     }
 
+    fn construct_scalar(&self, scalar: document::YamlScalarData) -> Result<YamlStandardData, YamlError> {
+        match scalar.tag() {
+            Some(tag) => match self.tag_handlers.iter().find(|&&(ref t, _)| *t == tag) {
+                Some(&(_, ref handler)) => return handler.construct_tagged(&scalar.to_scalar()),
+                None => ()
+            },
+            None => ()
+        }
+
+        match self.resolver {
+            Some(ref resolver) => scalar.to_scalar().typed_value_with(resolver),
+            None => scalar.typed_value()
+        }
+    }
+
     fn construct_sequence(&self, sequence: document::YamlSequenceData) -> Result<YamlStandardData, YamlError> {
-        let res:Result<Vec<YamlStandardData>, YamlError> = sequence.values().map(|node| { self.construct(node) }).collect();
-        res.map(|list| YamlStandardData::YamlSequence(list))
+        let res:Result<Vec<YamlStandardData>, YamlError> = sequence.values().map(|node| { node.and_then(|node| self.construct(node)) }).collect();
+        let list = try!(res);
+        match sequence.tag().as_ref().map(|t| t.as_slice()) {
+            Some("tag:yaml.org,2002:omap") =>
+                single_pairs(list, true, &sequence.start_mark()).map(YamlStandardData::YamlOrderedMap),
+            Some("tag:yaml.org,2002:pairs") =>
+                single_pairs(list, false, &sequence.start_mark()).map(YamlStandardData::YamlPairs),
+            _ => self.handle_collection(sequence.tag(), YamlStandardData::YamlSequence(list))
+        }
     }
 
     fn construct_mapping(&self, mapping: document::YamlMappingData) -> Result<YamlStandardData, YamlError> {
-        let pairs = mapping.pairs().map(|(key_node, value_node)| {
-            match self.construct(key_node) {
-                Ok(key) => match self.construct(value_node) {
-                    Ok(value) => Ok((key, value)),
-                    Err(e) => Err(e)
-                },
-                Err(e) => Err(e)
+        let mut merged = Vec::new();
+        let mut explicit = Vec::new();
+        for pair in mapping.pairs() {
+            let (key_node, value_node) = try!(pair);
+            if self.merge_keys && is_merge_key(&key_node) {
+                let source = try!(self.construct(value_node));
+                try!(merge_into(&mut merged, source, &mapping.start_mark()));
+                continue;
             }
-        });
-        let res:Result<Vec<(YamlStandardData, YamlStandardData)>, YamlError> = pairs.collect();
-        res.map(YamlStandardData::YamlMapping)
+            let key = try!(self.construct(key_node));
+            let value = try!(self.construct(value_node));
+            explicit.push((key, value));
+        }
+
+        let mut pairs: Vec<(YamlStandardData, YamlStandardData)> = merged.into_iter()
+            .filter(|&(ref key, _)| !explicit.iter().any(|&(ref k, _)| k == key)).collect();
+        pairs.extend(explicit.into_iter());
+        match mapping.tag().as_ref().map(|t| t.as_slice()) {
+            Some("tag:yaml.org,2002:set") => {
+                if pairs.iter().any(|&(_, ref value)| *value != YamlStandardData::YamlNull) {
+                    return Err(collection_error("set members must have null values", &mapping.start_mark()));
+                }
+                Ok(YamlStandardData::YamlSet(pairs.into_iter().map(|(key, _)| key).collect()))
+            },
+            _ => self.handle_collection(mapping.tag(), YamlStandardData::YamlMapping(pairs))
+        }
     }
 }
 
@@ -248,17 +451,20 @@ mod test {
     use std::f64;
     use std::num::FloatMath;
     use ffi::YamlEncoding::YamlUtf8Encoding;
-    use constructor::{YamlConstructor, YamlStandardConstructor};
+    use constructor::{YamlConstructor, YamlStandardConstructor, YamlStandardData};
+    use scalar::YamlScalar;
+    use schema::{YamlResolver, YamlSchema};
+    use error::YamlErrorCode;
 
     #[test]
     fn test_standard_constructor() {
         let data = "[1, 2, 3]";
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlSequence(vec![YamlInteger(1), YamlInteger(2), YamlInteger(3)])), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlSequence(vec![YamlInteger(1), YamlInteger(2), YamlInteger(3)])), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("unexpected result")
         }
@@ -267,12 +473,12 @@ mod test {
     #[test]
     fn test_integer_parser() {
         let data = "[0o10, 0x21, -30]";
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlSequence(vec![YamlInteger(0o10), YamlInteger(0x21), YamlInteger(-30)])), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlSequence(vec![YamlInteger(0o10), YamlInteger(0x21), YamlInteger(-30)])), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("unexpected result")
         }
@@ -280,13 +486,13 @@ mod test {
 
     #[test]
     fn test_float_parser() {
-        let data = "[0.3, -.4, 1e+2, -1.2e-3]";
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let data = "[0.3, -.4, 1.0e+2, -1.2e-3]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                let value = ctor.construct(doc.root().unwrap());
+                let value = doc.root().and_then(|root| ctor.construct(root));
                 match value {
                     Ok(YamlSequence(seq)) => {
                         match seq.as_slice() {
@@ -309,12 +515,12 @@ mod test {
     #[test]
     fn test_inf_parser() {
         let data = "[.inf, -.INF]";
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlSequence(vec![YamlFloat(f64::INFINITY), YamlFloat(f64::NEG_INFINITY)])), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlSequence(vec![YamlFloat(f64::INFINITY), YamlFloat(f64::NEG_INFINITY)])), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("document parse failure")
         }
@@ -323,12 +529,15 @@ mod test {
     #[test]
     fn test_misc_parser() {
         let data = "[yes, False, ~]";
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlSequence(vec![YamlBool(true), YamlBool(false), YamlNull])), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlSequence(vec![YamlString("yes".to_string()), YamlBool(false), YamlNull])), doc.root().and_then(|root| ctor.construct(root)));
+                let ctor = YamlStandardConstructor::new()
+                    .with_resolver(YamlResolver::new(YamlSchema::Yaml11).with_word_booleans(true));
+                assert_eq!(Ok(YamlSequence(vec![YamlBool(true), YamlBool(false), YamlNull])), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("document parse failure")
         }
@@ -337,12 +546,12 @@ mod test {
     #[test]
     fn test_double_quoted_parser() {
         let data = r#""hello, \"world\"""#;
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlString("hello, \"world\"".to_string())), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlString("hello, \"world\"".to_string())), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("document parse failure")
         }
@@ -351,12 +560,12 @@ mod test {
     #[test]
     fn test_single_quoted_parser() {
         let data = r#"'here''s to "quotes"'"#;
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlString(r#"here's to "quotes""#.to_string())), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlString(r#"here's to "quotes""#.to_string())), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("document parse failure")
         }
@@ -365,12 +574,12 @@ mod test {
     #[test]
     fn test_underlined_integer() {
         let data = "[1_000, -2_000_000]";
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlSequence(vec![YamlInteger(1000), YamlInteger(-2000000)])), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlSequence(vec![YamlInteger(1000), YamlInteger(-2000000)])), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("document parse failure")
         }
@@ -379,14 +588,148 @@ mod test {
     #[test]
     fn test_negative_radix() {
         let data = "[-0x30, -0700, -0b110]";
-        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
 
         match parser.load().next() {
             Some(Ok(doc)) => {
                 let ctor = YamlStandardConstructor::new();
-                assert_eq!(Ok(YamlSequence(vec![YamlInteger(-48), YamlInteger(-448), YamlInteger(-6)])), ctor.construct(doc.root().unwrap()))
+                assert_eq!(Ok(YamlSequence(vec![YamlInteger(-48), YamlInteger(-448), YamlInteger(-6)])), doc.root().and_then(|root| ctor.construct(root)))
             },
             _ => panic!("document parse failure")
         }
     }
+
+    #[test]
+    fn test_standard_collections() {
+        let data = "a: !!set {x, y}\nb: !!omap [c: 1, d: 2]\ne: !!pairs [f: 1, f: 2]\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let ctor = YamlStandardConstructor::new();
+
+        assert_eq!(Ok(YamlMapping(vec![
+            (YamlString("a".to_string()), YamlSet(vec![YamlString("x".to_string()), YamlString("y".to_string())])),
+            (YamlString("b".to_string()), YamlOrderedMap(vec![(YamlString("c".to_string()), YamlInteger(1)),
+                                                              (YamlString("d".to_string()), YamlInteger(2))])),
+            (YamlString("e".to_string()), YamlPairs(vec![(YamlString("f".to_string()), YamlInteger(1)),
+                                                         (YamlString("f".to_string()), YamlInteger(2))]))
+        ])), ctor.construct(doc.root().unwrap()));
+
+        for data in ["!!set {x: 1}", "!!omap [a: 1, a: 2]", "!!pairs [a, b]", "!!omap [{a: 1, b: 2}]"].iter() {
+            let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+            let doc = parser.load().next().unwrap().unwrap();
+            match ctor.construct(doc.root().unwrap()) {
+                Err(e) => assert_eq!(YamlErrorCode::InvalidNode, e.code),
+                Ok(value) => panic!("unexpected value: {}", value)
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let data = "base: &base {a: 1, b: 2}\nextra: &extra {b: 3, c: 4}\nmerged: {<<: [*base, *extra], a: 5}\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        let merged = match YamlStandardConstructor::new().construct(doc.root().unwrap()) {
+            Ok(YamlMapping(mut pairs)) => pairs.pop().unwrap().1,
+            res => panic!("unexpected result: {}", res)
+        };
+        assert_eq!(YamlMapping(vec![(YamlString("b".to_string()), YamlInteger(2)),
+                                    (YamlString("c".to_string()), YamlInteger(4)),
+                                    (YamlString("a".to_string()), YamlInteger(5))]), merged);
+
+        let kept = match YamlStandardConstructor::new().with_merge_keys(false).construct(doc.root().unwrap()) {
+            Ok(YamlMapping(mut pairs)) => pairs.pop().unwrap().1,
+            res => panic!("unexpected result: {}", res)
+        };
+        match kept {
+            YamlMapping(ref pairs) => assert_eq!(YamlString("<<".to_string()), pairs[0].0),
+            ref value => panic!("unexpected value: {}", value)
+        }
+
+        let parser = YamlByteParser::init("{<<: 1}".as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        match YamlStandardConstructor::new().construct(doc.root().unwrap()) {
+            Err(e) => assert_eq!(YamlErrorCode::InvalidNode, e.code),
+            Ok(value) => panic!("unexpected value: {}", value)
+        }
+    }
+
+    #[test]
+    fn test_tag_handlers() {
+        let data = "[!Ref bucket, !Len name, !Other x, plain]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let mut ctor = YamlStandardConstructor::new();
+        ctor.register_tag("!Ref", |scalar: &YamlScalar| {
+            Ok(YamlMapping(vec![(YamlString("Ref".to_string()), YamlString(scalar.value.clone()))]))
+        });
+        ctor.register_tag("!Len", |scalar: &YamlScalar| Ok(YamlInteger(scalar.value.len() as int)));
+
+        assert_eq!(Ok(YamlSequence(vec![
+            YamlMapping(vec![(YamlString("Ref".to_string()), YamlString("bucket".to_string()))]),
+            YamlInteger(4),
+            YamlString("x".to_string()),
+            YamlString("plain".to_string())
+        ])), ctor.construct(doc.root().unwrap()));
+
+        let data = "{a: !Join ['-', [x, y]], b: !GetAtt {name: c}, c: [z]}";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        ctor.register_collection_tag("!Join", |value: YamlStandardData| Ok(YamlMapping(vec![(YamlString("Fn::Join".to_string()), value)])));
+        ctor.register_collection_tag("!GetAtt", |value: YamlStandardData| match value {
+            YamlMapping(mut pairs) => Ok(pairs.pop().unwrap().1),
+            _ => panic!("unexpected value")
+        });
+
+        let join = YamlSequence(vec![YamlString("-".to_string()),
+                                     YamlSequence(vec![YamlString("x".to_string()), YamlString("y".to_string())])]);
+        assert_eq!(Ok(YamlMapping(vec![
+            (YamlString("a".to_string()), YamlMapping(vec![(YamlString("Fn::Join".to_string()), join)])),
+            (YamlString("b".to_string()), YamlString("c".to_string())),
+            (YamlString("c".to_string()), YamlSequence(vec![YamlString("z".to_string())]))
+        ])), ctor.construct(doc.root().unwrap()));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let data = "- &a [x, x, x]\n- &b [*a, *a, *a]\n- &c [*b, *b, *b]\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        let ctor = YamlStandardConstructor::with_node_limit(20);
+        match doc.root().and_then(|root| ctor.construct(root)) {
+            Err(e) => assert_eq!(YamlErrorCode::NodeLimitExceeded, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+        assert!(YamlStandardConstructor::with_node_limit(100).construct(doc.root().unwrap()).is_ok());
+
+        // the limit applies to each document, not to everything one constructor builds
+        let ctor = YamlStandardConstructor::with_node_limit(20);
+        for _ in range(0u, 10) {
+            assert!(ctor.construct(doc.root().unwrap()).is_err());
+        }
+        let parser = YamlByteParser::init("[a, b, c]".as_bytes(), YamlUtf8Encoding).unwrap();
+        let small = parser.load().next().unwrap().unwrap();
+        for _ in range(0u, 10) {
+            assert!(ctor.construct(small.root().unwrap()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_recursive_alias() {
+        for data in ["&a [x, *a]", "&a {b: [*a]}"].iter() {
+            let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+            let doc = parser.load().next().unwrap().unwrap();
+            match doc.root().and_then(|root| YamlStandardConstructor::new().construct(root)) {
+                Err(e) => assert_eq!(YamlErrorCode::RecursiveAlias, e.code),
+                Ok(_) => panic!("should return an error")
+            }
+        }
+
+        let data = "- &a [x]\n- [*a, *a]\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        assert!(YamlStandardConstructor::new().construct(doc.root().unwrap()).is_ok());
+    }
 }
@@ -0,0 +1,185 @@
+use ffi;
+use ffi::YamlErrorType;
+use error::YamlError;
+use parser::{YamlParser, YamlIoParser, YamlResourceLimits};
+use document::YamlDocument;
+use emitter::YamlEmitter;
+
+use std::io::File;
+
+#[derive(Show, PartialEq, Copy)]
+pub enum Format {
+    Json,
+    Yaml
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub struct Options {
+    pub input_encoding: ffi::YamlEncoding,
+    pub output_encoding: ffi::YamlEncoding,
+    pub limits: YamlResourceLimits
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options {
+            input_encoding: ffi::YamlEncoding::YamlAnyEncoding,
+            output_encoding: ffi::YamlEncoding::YamlUtf8Encoding,
+            limits: YamlResourceLimits::new()
+        }
+    }
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlConvertError {
+    pub path: Path,
+    pub error: YamlError
+}
+
+impl YamlConvertError {
+    pub fn message(&self) -> String {
+        format!("{}: {}", self.path.display(), self.error.message())
+    }
+}
+
+fn load_file(path: &Path, options: &Options) -> Result<Vec<Box<YamlDocument>>, YamlError> {
+    let file = try!(File::open(path).map_err(|e| YamlError::from_io_error(YamlErrorType::YAML_READER_ERROR, e)));
+    let mut parser = try!(YamlIoParser::init(file, options.input_encoding));
+    parser.set_limits(options.limits);
+    parser.load_all()
+}
+
+fn write_json(docs: &[Box<YamlDocument>], file: &mut File, options: &Options) -> Result<(), YamlError> {
+    for doc in docs.iter() {
+        try!(doc.write_json_with(file, &options.limits));
+        try!(file.write_str("\n").map_err(|e| YamlError::from_io_error(YamlErrorType::YAML_WRITER_ERROR, e)));
+    }
+    Ok(())
+}
+
+fn write_yaml(docs: &[Box<YamlDocument>], file: &mut File, options: &Options) -> Result<(), YamlError> {
+    let mut emitter = YamlEmitter::init(file);
+    emitter.set_limits(options.limits);
+    emitter.emit_stream(options.output_encoding, |e| {
+        for doc in docs.iter() {
+            if doc.is_empty() {
+                continue;
+            }
+            let root = try!(doc.root());
+            try!(e.emit_document(doc.version_directive(), doc.tag_directives().as_slice(),
+                                 doc.start_implicit(), |e| e.emit_node(&root)));
+        }
+        Ok(())
+    })
+}
+
+pub fn convert_file(in_path: &Path, out_path: &Path, format: Format, options: &Options) -> Result<(), YamlConvertError> {
+    let docs = try!(load_file(in_path, options).map_err(|e| YamlConvertError {
+        path: in_path.clone(),
+        error: e
+    }));
+
+    let mut file = try!(File::create(out_path).map_err(|e| YamlConvertError {
+        path: out_path.clone(),
+        error: YamlError::from_io_error(YamlErrorType::YAML_WRITER_ERROR, e)
+    }));
+
+    let res = match format {
+        Format::Json => write_json(docs.as_slice(), &mut file, options),
+        Format::Yaml => write_yaml(docs.as_slice(), &mut file, options)
+    };
+
+    res.map_err(|e| YamlConvertError {
+        path: out_path.clone(),
+        error: e
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use convert::{convert_file, Format, Options};
+    use error::YamlErrorCode;
+    use std::io::{File, TempDir};
+
+    #[test]
+    fn test_convert_to_json() {
+        let dir = TempDir::new("yaml-convert").unwrap();
+        let input = dir.path().join("in.yaml");
+        let output = dir.path().join("out.json");
+        File::create(&input).write_str("a: [1, b]\n--- ~\n").unwrap();
+
+        assert!(convert_file(&input, &output, Format::Json, &Options::new()).is_ok());
+        assert_eq!("{\"a\":[1,\"b\"]}\nnull\n".to_string(), File::open(&output).read_to_string().unwrap());
+    }
+
+    #[test]
+    fn test_convert_json_schema() {
+        let dir = TempDir::new("yaml-convert").unwrap();
+        let input = dir.path().join("in.yaml");
+        let output = dir.path().join("out.json");
+        File::create(&input).write_str("[yes, 0o17, 1.5, null]").unwrap();
+
+        assert!(convert_file(&input, &output, Format::Json, &Options::new()).is_ok());
+        assert_eq!("[\"yes\",\"0o17\",1.5,null]\n".to_string(), File::open(&output).read_to_string().unwrap());
+
+        File::create(&input).write_str("{[a]: b}").unwrap();
+        match convert_file(&input, &output, Format::Json, &Options::new()) {
+            Err(e) => {
+                assert_eq!(output, e.path);
+                assert_eq!(YamlErrorCode::JsonUnrepresentable, e.error.code);
+            },
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_convert_to_yaml() {
+        let dir = TempDir::new("yaml-convert").unwrap();
+        let input = dir.path().join("in.yaml");
+        let output = dir.path().join("out.yaml");
+        File::create(&input).write_str("{a: [1, 'b']}").unwrap();
+
+        assert!(convert_file(&input, &output, Format::Yaml, &Options::new()).is_ok());
+        assert_eq!("{a: [1, 'b']}\n".to_string(), File::open(&output).read_to_string().unwrap());
+    }
+
+    #[test]
+    fn test_convert_limits() {
+        let dir = TempDir::new("yaml-convert").unwrap();
+        let input = dir.path().join("in.yaml");
+        let output = dir.path().join("out");
+        File::create(&input).write_str("- &a [x, x, x]\n- [*a, *a, *a]\n").unwrap();
+
+        let mut options = Options::new();
+        options.limits.output_bytes = Some(8);
+        match convert_file(&input, &output, Format::Yaml, &options) {
+            Err(e) => assert_eq!(YamlErrorCode::OutputLimitExceeded, e.error.code),
+            Ok(_) => panic!("should return an error")
+        }
+
+        let mut options = Options::new();
+        options.limits.alias_expansions = Some(4);
+        match convert_file(&input, &output, Format::Json, &options) {
+            Err(e) => assert_eq!(YamlErrorCode::AliasExpansionLimitExceeded, e.error.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_convert_error_context() {
+        let dir = TempDir::new("yaml-convert").unwrap();
+        let input = dir.path().join("bad.yaml");
+        let output = dir.path().join("out.json");
+        File::create(&input).write_str("a: 1\nb: [1\n").unwrap();
+
+        match convert_file(&input, &output, Format::Json, &Options::new()) {
+            Err(e) => {
+                assert_eq!(input, e.path);
+                assert!(e.message().as_slice().starts_with(input.display().to_string().as_slice()));
+                assert!(e.message().as_slice().contains("line"));
+                assert!(e.error.code != YamlErrorCode::NoError);
+            },
+            Ok(_) => panic!("should return an error")
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use error::{YamlError, YamlErrorCode, YamlMark};
+
+#[derive(Show, PartialEq, Eq, Copy, PartialOrd, Ord)]
+pub enum YamlSeverity {
+    Lint,
+    Warning,
+    Error,
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlDiagnostic {
+    pub severity: YamlSeverity,
+    pub code: YamlErrorCode,
+    pub message: String,
+    pub primary_mark: Option<YamlMark>,
+    pub related_marks: Vec<(String, YamlMark)>
+}
+
+impl YamlDiagnostic {
+    pub fn new(severity: YamlSeverity, code: YamlErrorCode, message: String,
+        primary_mark: Option<YamlMark>) -> YamlDiagnostic
+    {
+        YamlDiagnostic {
+            severity: severity,
+            code: code,
+            message: message,
+            primary_mark: primary_mark,
+            related_marks: Vec::new()
+        }
+    }
+
+    pub fn from_error(error: &YamlError) -> YamlDiagnostic {
+        let mut diagnostic = YamlDiagnostic::new(YamlSeverity::Error, error.code, error.message(),
+                                                 error.context.as_ref().map(|c| c.problem_mark));
+        match error.context {
+            Some(ref context) => match context.context {
+                Some(ref label) => diagnostic.related_marks.push((label.clone(), context.context_mark)),
+                None => ()
+            },
+            None => ()
+        }
+        diagnostic
+    }
+}
+
+#[derive(Show, PartialEq, Clone, Copy)]
+pub enum YamlWarningKind {
+    DuplicateKey,
+    DuplicateAnchor,
+    TabIndentation,
+    DeprecatedBoolean,
+    Yaml11Construct,
+}
+
+#[derive(Show, PartialEq, Clone)]
+pub struct YamlWarning {
+    pub kind: YamlWarningKind,
+    pub message: String,
+    pub mark: YamlMark,
+    pub related_mark: Option<YamlMark>
+}
+
+impl YamlWarning {
+    pub fn new(kind: YamlWarningKind, message: String, mark: YamlMark) -> YamlWarning {
+        YamlWarning {
+            kind: kind,
+            message: message,
+            mark: mark,
+            related_mark: None
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> YamlDiagnostic {
+        let code = match self.kind {
+            YamlWarningKind::DuplicateKey => YamlErrorCode::DuplicateKey,
+            YamlWarningKind::DuplicateAnchor => YamlErrorCode::DuplicateAnchor,
+            YamlWarningKind::TabIndentation => YamlErrorCode::TabIndentation,
+            YamlWarningKind::DeprecatedBoolean => YamlErrorCode::DeprecatedBoolean,
+            YamlWarningKind::Yaml11Construct => YamlErrorCode::Yaml11Construct
+        };
+        let mut diagnostic = YamlDiagnostic::new(YamlSeverity::Warning, code, self.message.clone(), Some(self.mark));
+        match self.related_mark {
+            Some(mark) => diagnostic.related_marks.push(("first occurrence".to_string(), mark)),
+            None => ()
+        }
+        diagnostic
+    }
+}
+
+pub trait YamlDiagnosticSink {
+    fn report(&mut self, diagnostic: YamlDiagnostic);
+}
+
+impl YamlDiagnosticSink for Vec<YamlDiagnostic> {
+    fn report(&mut self, diagnostic: YamlDiagnostic) {
+        self.push(diagnostic);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use diagnostic::{YamlDiagnostic, YamlDiagnosticSink, YamlSeverity, YamlWarningKind};
+    use parser::{YamlParser, YamlByteParser, YamlParserBuilder, YamlDuplicateAnchors};
+    use error::YamlErrorCode;
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+
+    #[test]
+    fn test_error_diagnostic() {
+        let data = "[&a 1, &a 2]";
+        let mut parser = YamlParserBuilder::new()
+            .duplicate_anchors(YamlDuplicateAnchors::RejectDuplicateAnchors)
+            .bytes(data.as_bytes()).unwrap();
+        let mut sink: Vec<YamlDiagnostic> = Vec::new();
+
+        match parser.documents().next() {
+            Some(Err(e)) => sink.report(YamlDiagnostic::from_error(&e)),
+            _ => panic!("unexpected result")
+        }
+
+        assert_eq!(1, sink.len());
+        assert_eq!(YamlSeverity::Error, sink[0].severity);
+        assert_eq!(YamlErrorCode::DuplicateAnchor, sink[0].code);
+        assert_eq!(7, sink[0].primary_mark.unwrap().column);
+        assert_eq!(1, sink[0].related_marks[0].1.column);
+    }
+
+    #[test]
+    fn test_warnings() {
+        let data = "a: yes\nb: [\n\tc]\na: 'no'\n";
+        let mut parser = YamlByteParser::new(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+
+        let warnings = parser.take_warnings();
+        let kinds: Vec<YamlWarningKind> = warnings.iter().map(|w| w.kind).collect();
+        assert_eq!(vec![YamlWarningKind::DeprecatedBoolean, YamlWarningKind::TabIndentation,
+                        YamlWarningKind::DuplicateKey], kinds);
+        assert_eq!(2, warnings[1].mark.line);
+        assert_eq!(0, warnings[2].related_mark.unwrap().line);
+        assert_eq!(YamlSeverity::Warning, warnings[2].to_diagnostic().severity);
+        let codes: Vec<YamlErrorCode> = warnings.iter().map(|w| w.to_diagnostic().code).collect();
+        assert_eq!(vec![YamlErrorCode::DeprecatedBoolean, YamlErrorCode::TabIndentation,
+                        YamlErrorCode::DuplicateKey], codes);
+        assert!(parser.take_warnings().is_empty());
+    }
+}
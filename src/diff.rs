@@ -0,0 +1,369 @@
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+use path::{child_path, key_segment};
+use schema;
+
+use std::cmp;
+use std::num::Float;
+
+#[derive(Show, PartialEq)]
+pub enum YamlChange {
+    Added(String, YamlStandardData),
+    Removed(String, YamlStandardData),
+    Modified(String, YamlStandardData, YamlStandardData),
+}
+
+fn find_value<'a>(pairs: &'a [(YamlStandardData, YamlStandardData)], key: &YamlStandardData)
+    -> Option<&'a YamlStandardData>
+{
+    pairs.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v)
+}
+
+fn diff_into(path: &str, left: &YamlStandardData, right: &YamlStandardData, out: &mut Vec<YamlChange>) {
+    match (left, right) {
+        (&YamlMapping(ref l), &YamlMapping(ref r)) => {
+            for (i, &(ref key, ref value)) in l.iter().enumerate() {
+                let segment = child_path(path, key_segment(key, i).as_slice());
+                match find_value(r.as_slice(), key) {
+                    Some(other) => diff_into(segment.as_slice(), value, other, out),
+                    None => out.push(YamlChange::Removed(segment, value.clone()))
+                }
+            }
+            for (i, &(ref key, ref value)) in r.iter().enumerate() {
+                if find_value(l.as_slice(), key).is_none() {
+                    out.push(YamlChange::Added(child_path(path, key_segment(key, i).as_slice()), value.clone()));
+                }
+            }
+        },
+        (&YamlSequence(ref l), &YamlSequence(ref r)) => {
+            for i in range(0, cmp::max(l.len(), r.len())) {
+                let segment = format!("{}[{}]", path, i);
+                if i >= r.len() {
+                    out.push(YamlChange::Removed(segment, l[i].clone()));
+                } else if i >= l.len() {
+                    out.push(YamlChange::Added(segment, r[i].clone()));
+                } else {
+                    diff_into(segment.as_slice(), &l[i], &r[i], out);
+                }
+            }
+        },
+        (&YamlFloat(a), &YamlFloat(b)) if a.is_nan() && b.is_nan() => (),
+        _ => if left != right {
+            out.push(YamlChange::Modified(path.to_string(), left.clone(), right.clone()));
+        }
+    }
+}
+
+pub fn diff(left: &YamlStandardData, right: &YamlStandardData) -> Vec<YamlChange> {
+    let mut out = Vec::new();
+    diff_into("", left, right, &mut out);
+    out
+}
+
+pub fn deep_merge(base: &YamlStandardData, overlay: &YamlStandardData) -> YamlStandardData {
+    match (base, overlay) {
+        (&YamlMapping(ref b), &YamlMapping(ref o)) => {
+            let mut merged = Vec::new();
+            for &(ref key, ref value) in b.iter() {
+                match find_value(o.as_slice(), key) {
+                    Some(other) => merged.push((key.clone(), deep_merge(value, other))),
+                    None => merged.push((key.clone(), value.clone()))
+                }
+            }
+            for &(ref key, ref value) in o.iter() {
+                if find_value(b.as_slice(), key).is_none() {
+                    merged.push((key.clone(), value.clone()));
+                }
+            }
+            YamlMapping(merged)
+        },
+        _ => overlay.clone()
+    }
+}
+
+// Removals are kept apart from the values, so that a null in the desired
+// document is an ordinary value rather than a deletion.
+#[derive(Show, PartialEq, Clone)]
+pub struct YamlOverlay {
+    pub value: YamlStandardData,
+    pub removed: Vec<Vec<YamlStandardData>>
+}
+
+fn overlay_into(base: &YamlStandardData, desired: &YamlStandardData, path: &mut Vec<YamlStandardData>,
+                removed: &mut Vec<Vec<YamlStandardData>>) -> YamlStandardData
+{
+    match (base, desired) {
+        (&YamlMapping(ref b), &YamlMapping(ref d)) => {
+            let mut overlay = Vec::new();
+            for &(ref key, ref value) in d.iter() {
+                match find_value(b.as_slice(), key) {
+                    Some(existing) if diff(existing, value).is_empty() => (),
+                    Some(existing) => {
+                        path.push(key.clone());
+                        let changed = overlay_into(existing, value, path, removed);
+                        path.pop();
+                        // a nested mapping that only lost keys needs no entry
+                        let unchanged = match (existing, &changed) {
+                            (&YamlMapping(_), &YamlMapping(ref pairs)) => pairs.is_empty(),
+                            _ => false
+                        };
+                        if !unchanged {
+                            overlay.push((key.clone(), changed));
+                        }
+                    },
+                    None => overlay.push((key.clone(), value.clone()))
+                }
+            }
+            for &(ref key, _) in b.iter() {
+                if find_value(d.as_slice(), key).is_none() {
+                    let mut key_path = path.clone();
+                    key_path.push(key.clone());
+                    removed.push(key_path);
+                }
+            }
+            YamlMapping(overlay)
+        },
+        _ => desired.clone()
+    }
+}
+
+pub fn make_overlay(base: &YamlStandardData, desired: &YamlStandardData) -> YamlOverlay {
+    let mut removed = Vec::new();
+    let value = overlay_into(base, desired, &mut Vec::new(), &mut removed);
+    YamlOverlay {
+        value: value,
+        removed: removed
+    }
+}
+
+fn remove_path(value: &mut YamlStandardData, path: &[YamlStandardData]) {
+    let pairs = match *value {
+        YamlMapping(ref mut pairs) => pairs,
+        _ => return
+    };
+    match path.len() {
+        0 => (),
+        1 => pairs.retain(|&(ref k, _)| *k != path[0]),
+        _ => for pair in pairs.iter_mut() {
+            if pair.0 == path[0] {
+                remove_path(&mut pair.1, path.slice_from(1));
+            }
+        }
+    }
+}
+
+pub fn apply_overlay(base: &YamlStandardData, overlay: &YamlOverlay) -> YamlStandardData {
+    let mut result = deep_merge(base, &overlay.value);
+    for path in overlay.removed.iter() {
+        remove_path(&mut result, path.as_slice());
+    }
+    result
+}
+
+pub trait YamlDiffColors {
+    fn added(&self, line: &str) -> String {
+        line.to_string()
+    }
+
+    fn removed(&self, line: &str) -> String {
+        line.to_string()
+    }
+
+    fn modified(&self, line: &str) -> String {
+        line.to_string()
+    }
+}
+
+#[derive(Copy)]
+pub struct YamlPlainColors;
+
+impl YamlDiffColors for YamlPlainColors {}
+
+#[derive(Copy)]
+pub struct YamlAnsiColors;
+
+impl YamlDiffColors for YamlAnsiColors {
+    fn added(&self, line: &str) -> String {
+        format!("\x1b[32m{}\x1b[0m", line)
+    }
+
+    fn removed(&self, line: &str) -> String {
+        format!("\x1b[31m{}\x1b[0m", line)
+    }
+
+    fn modified(&self, line: &str) -> String {
+        format!("\x1b[33m{}\x1b[0m", line)
+    }
+}
+
+fn render_string(s: &str) -> String {
+    // quote anything that is not plain syntax or would not load back as a string
+    let needs_quotes = s.is_empty()
+        || s.starts_with(" ") || s.ends_with(" ")
+        || s.contains(": ") || s.contains(" #") || s.contains("\n")
+        || s.chars().next().map_or(false, |c| "-?:,[]{}#&*!|>'\"%@`".contains_char(c))
+        || schema::needs_quotes(s);
+
+    if needs_quotes {
+        format!("'{}'", s.replace("'", "''"))
+    } else {
+        s.to_string()
+    }
+}
+
+fn render_pairs(pairs: &[(YamlStandardData, YamlStandardData)]) -> String {
+    let pairs: Vec<String> = pairs.iter().map(|&(ref k, ref v)| {
+        format!("{}: {}", render_value(k), render_value(v))
+    }).collect();
+    pairs.connect(", ")
+}
+
+pub fn render_value(value: &YamlStandardData) -> String {
+    match *value {
+        YamlInteger(i) => i.to_string(),
+        YamlFloat(f) => schema::float_text(f),
+        YamlString(ref s) => render_string(s.as_slice()),
+        YamlNull => "null".to_string(),
+        YamlBool(b) => b.to_string(),
+        YamlSequence(ref items) => {
+            let items: Vec<String> = items.iter().map(render_value).collect();
+            format!("[{}]", items.connect(", "))
+        },
+        YamlMapping(ref pairs) => format!("{{{}}}", render_pairs(pairs.as_slice())),
+        YamlSet(ref items) => {
+            let items: Vec<String> = items.iter().map(render_value).collect();
+            format!("!!set {{{}}}", items.connect(", "))
+        },
+        YamlOrderedMap(ref pairs) => format!("!!omap [{}]", render_pairs(pairs.as_slice())),
+        YamlPairs(ref pairs) => format!("!!pairs [{}]", render_pairs(pairs.as_slice()))
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() { "." } else { path }
+}
+
+pub fn render_changes<C: YamlDiffColors>(changes: &[YamlChange], colors: &C) -> String {
+    let mut out = String::new();
+    for change in changes.iter() {
+        let line = match *change {
+            YamlChange::Added(ref path, ref value) =>
+                colors.added(format!("+ {}: {}", display_path(path.as_slice()), render_value(value)).as_slice()),
+            YamlChange::Removed(ref path, ref value) =>
+                colors.removed(format!("- {}: {}", display_path(path.as_slice()), render_value(value)).as_slice()),
+            YamlChange::Modified(ref path, ref from, ref to) =>
+                colors.modified(format!("~ {}: {} -> {}", display_path(path.as_slice()),
+                                        render_value(from), render_value(to)).as_slice())
+        };
+        out.push_str(line.as_slice());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use diff::{diff, apply_overlay, deep_merge, make_overlay, render_changes, render_value, YamlChange, YamlPlainColors, YamlAnsiColors};
+    use constructor::YamlStandardData::*;
+
+    fn s(value: &str) -> ::constructor::YamlStandardData {
+        YamlString(value.to_string())
+    }
+
+    #[test]
+    fn test_diff() {
+        let base = YamlMapping(vec![
+            (s("spec"), YamlMapping(vec![(s("replicas"), YamlInteger(2))])),
+            (s("ports"), YamlSequence(vec![YamlInteger(80), YamlInteger(443)])),
+            (s("old"), YamlBool(true))
+        ]);
+        let desired = YamlMapping(vec![
+            (s("spec"), YamlMapping(vec![(s("replicas"), YamlInteger(3))])),
+            (s("ports"), YamlSequence(vec![YamlInteger(80)])),
+            (s("env"), s("prod"))
+        ]);
+
+        assert_eq!(vec![
+            YamlChange::Modified("spec.replicas".to_string(), YamlInteger(2), YamlInteger(3)),
+            YamlChange::Removed("ports[1]".to_string(), YamlInteger(443)),
+            YamlChange::Removed("old".to_string(), YamlBool(true)),
+            YamlChange::Added("env".to_string(), s("prod"))
+        ], diff(&base, &desired));
+        assert!(diff(&base, &base).is_empty());
+    }
+
+    #[test]
+    fn test_render_changes() {
+        let changes = vec![
+            YamlChange::Modified("spec.replicas".to_string(), YamlInteger(2), YamlInteger(3)),
+            YamlChange::Added("metadata.labels.env".to_string(), s("prod")),
+            YamlChange::Removed("debug".to_string(), s("yes"))
+        ];
+
+        assert_eq!("~ spec.replicas: 2 -> 3\n+ metadata.labels.env: prod\n- debug: 'yes'\n",
+                   render_changes(changes.as_slice(), &YamlPlainColors).as_slice());
+        assert_eq!("\x1b[32m+ metadata.labels.env: prod\x1b[0m\n",
+                   render_changes(changes.slice(1, 2), &YamlAnsiColors).as_slice());
+    }
+
+    #[test]
+    fn test_render_value() {
+        assert_eq!("{a: [1, null], b: ''}",
+                   render_value(&YamlMapping(vec![
+                       (s("a"), YamlSequence(vec![YamlInteger(1), YamlNull])),
+                       (s("b"), s(""))
+                   ])).as_slice());
+        assert_eq!("['yes', '1:30', '0o17', plain]",
+                   render_value(&YamlSequence(vec![s("yes"), s("1:30"), s("0o17"), s("plain")])).as_slice());
+    }
+
+    #[test]
+    fn test_make_overlay() {
+        let base = YamlMapping(vec![
+            (s("image"), s("app:1")),
+            (s("spec"), YamlMapping(vec![(s("replicas"), YamlInteger(2)), (s("port"), YamlInteger(80))])),
+            (s("debug"), YamlBool(true))
+        ]);
+        let desired = YamlMapping(vec![
+            (s("image"), s("app:1")),
+            (s("spec"), YamlMapping(vec![(s("replicas"), YamlInteger(3)), (s("port"), YamlInteger(80))])),
+            (s("env"), s("prod"))
+        ]);
+
+        let overlay = make_overlay(&base, &desired);
+        assert_eq!(YamlMapping(vec![
+            (s("spec"), YamlMapping(vec![(s("replicas"), YamlInteger(3))])),
+            (s("env"), s("prod"))
+        ]), overlay.value);
+        assert_eq!(vec![vec![s("debug")]], overlay.removed);
+        assert!(diff(&apply_overlay(&base, &overlay), &desired).is_empty());
+    }
+
+    #[test]
+    fn test_overlay_keeps_nulls() {
+        let base = YamlMapping(vec![
+            (s("spec"), YamlMapping(vec![(s("limit"), YamlInteger(2)), (s("old"), YamlBool(true))])),
+            (s("debug"), YamlBool(true))
+        ]);
+        let desired = YamlMapping(vec![
+            (s("spec"), YamlMapping(vec![(s("limit"), YamlInteger(2))])),
+            (s("debug"), YamlNull),
+            (s("extra"), YamlNull)
+        ]);
+
+        let overlay = make_overlay(&base, &desired);
+        assert_eq!(YamlMapping(vec![(s("debug"), YamlNull), (s("extra"), YamlNull)]), overlay.value);
+        assert_eq!(vec![vec![s("spec"), s("old")]], overlay.removed);
+        assert_eq!(desired, apply_overlay(&base, &overlay));
+        assert_eq!(YamlMapping(vec![(s("debug"), YamlNull)]),
+                   deep_merge(&YamlMapping(vec![(s("debug"), YamlBool(true))]), &YamlMapping(vec![(s("debug"), YamlNull)])));
+    }
+
+    #[test]
+    fn test_deep_merge_replaces_non_mappings() {
+        let base = YamlMapping(vec![(s("ports"), YamlSequence(vec![YamlInteger(80), YamlInteger(443)]))]);
+        let overlay = YamlMapping(vec![(s("ports"), YamlSequence(vec![YamlInteger(8080)]))]);
+
+        assert_eq!(overlay, deep_merge(&base, &overlay));
+    }
+}
@@ -3,30 +3,362 @@ use libc;
 use codecs;
 use ffi;
 use ffi::yaml_node_type_t::*;
-use error::YamlMark;
-use event::{YamlVersionDirective, YamlTagDirective};
+use ffi::YamlErrorType;
+use error::{YamlError, YamlErrorCode, YamlErrorContext, YamlMark};
+use event::{YamlEvent, YamlVersionDirective, YamlTagDirective, YamlScalarParam, YamlSequenceParam};
+use event::YamlEvent::*;
+use parser::{YamlParser, YamlResourceLimits};
+use parser::YamlDuplicateKeys::RejectDuplicateKeys;
+use parser::YamlDuplicateAnchors::RejectDuplicateAnchors;
+use diagnostic::{YamlWarning, YamlWarningKind};
+use emitter::{YamlEmitter, is_default_tag};
+use json;
+use scalar::YamlScalar;
+use schema::{YamlResolver, YamlSchema};
+use constructor::YamlStandardData;
 
 use std::ptr;
 use std::mem;
+use std::slice;
+use std::str;
+use std::fmt;
+use std::io::MemWriter;
+use std::collections::HashMap;
 use std::c_str::{CString, ToCStr};
 
 pub struct YamlDocument {
-    document_mem: ffi::yaml_document_t
+    document_mem: ffi::yaml_document_t,
+    anchors: Vec<(String, int, YamlMark)>,
+    composed: uint,
+    resolver: YamlResolver,
+    // libyaml stores the default tag for untagged nodes, so remember which were explicit
+    tagged: Vec<bool>
+}
+
+fn document_error(kind: YamlErrorType, code: YamlErrorCode, message: &str) -> YamlError {
+    YamlError {
+        kind: kind,
+        code: code,
+        problem: Some(message.to_string()),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+// keys are the same when they resolve to the same tag and text, so `a`
+// and 'a' collide while `1` and '1' do not
+fn key_tag(resolver: &YamlResolver, key: &YamlScalarParam) -> String {
+    match key.tag {
+        Some(ref tag) if tag.as_slice() != "!" => tag.clone(),
+        None if key.style == ffi::YamlScalarStyle::YamlPlainScalarStyle => resolver.implicit_tag(key.value.as_slice()).to_string(),
+        _ => "tag:yaml.org,2002:str".to_string()
+    }
+}
+
+fn composer_error(problem: &str, problem_mark: YamlMark,
+    context: Option<&str>, context_mark: YamlMark) -> YamlError
+{
+    YamlError {
+        kind: YamlErrorType::YAML_COMPOSER_ERROR,
+        code: YamlErrorCode::ComposerError,
+        problem: Some(problem.to_string()),
+        io_error: None,
+        context: Some(YamlErrorContext {
+            byte_offset: problem_mark.index,
+            problem_mark: problem_mark,
+            context: context.map(|s| s.to_string()),
+            context_mark: context_mark
+        }),
+        filename: None
+    }
+}
+
+fn ffi_mark(mark: &YamlMark) -> ffi::yaml_mark_t {
+    ffi::yaml_mark_t {
+        index: mark.index as libc::size_t,
+        line: mark.line as libc::size_t,
+        column: mark.column as libc::size_t
+    }
+}
+
+fn specific_tag(tag: &Option<String>) -> Option<&str> {
+    match *tag {
+        Some(ref t) if t.as_slice() != "!" => Some(t.as_slice()),
+        _ => None
+    }
+}
+
+fn next_event<P: YamlParser>(parser: &mut P) -> Result<(YamlEvent, YamlMark, YamlMark), YamlError> {
+    unsafe {
+        match parser.parse_marked_event() {
+            Some(evt) => Ok(evt),
+            None => Err(parser.get_error())
+        }
+    }
 }
 
 impl YamlDocument {
-    pub unsafe fn parser_load(parser: &mut ffi::yaml_parser_t) -> Option<Box<YamlDocument>> {
-        let mut document = box YamlDocument {
-            document_mem: mem::uninitialized()
-        };
+    pub fn compose<P: YamlParser>(parser: &mut P) -> Result<Option<Box<YamlDocument>>, YamlError> {
+        match YamlDocument::compose_document(parser) {
+            Err(mut e) => {
+                if e.filename.is_none() {
+                    e.filename = parser.filename();
+                }
+                Err(e)
+            },
+            res => res
+        }
+    }
 
-        if ffi::yaml_parser_load(parser, &mut document.document_mem) == 0 {
+    fn compose_document<P: YamlParser>(parser: &mut P) -> Result<Option<Box<YamlDocument>>, YamlError> {
+        loop {
+            let (event, start_mark, _) = try!(next_event(parser));
+            match event {
+                YamlStreamStartEvent(_) => continue,
+                YamlNoEvent | YamlStreamEndEvent => return Ok(None),
+                YamlDocumentStartEvent(vsn_dir, tag_dirs, implicit) => {
+                    let mut document = try!(YamlDocument::init(vsn_dir, tag_dirs.as_slice(), implicit, false));
+                    document.document_mem.start_mark = ffi_mark(&start_mark);
+                    document.resolver = parser.resolver();
+
+                    let (root_event, root_start, root_end) = try!(next_event(parser));
+                    try!(document.compose_node(parser, root_event, root_start, root_end));
+
+                    let (end_event, end_start, end_mark) = try!(next_event(parser));
+                    match end_event {
+                        YamlDocumentEndEvent(end_implicit) => {
+                            document.document_mem.end_implicit = if end_implicit { 1 } else { 0 };
+                            document.document_mem.end_mark = ffi_mark(&end_mark);
+                        },
+                        _ => return Err(composer_error("did not find expected <document end>", end_start, None, end_start))
+                    }
+
+                    return Ok(Some(document));
+                },
+                _ => return Err(composer_error("did not find expected <document start>", start_mark, None, start_mark))
+            }
+        }
+    }
+
+    pub fn compose_value<P: YamlParser>(parser: &mut P, event: YamlEvent,
+        start_mark: YamlMark, end_mark: YamlMark) -> Result<Box<YamlDocument>, YamlError>
+    {
+        let mut document = try!(YamlDocument::init(None, &[], true, true));
+        document.document_mem.start_mark = ffi_mark(&start_mark);
+        document.resolver = parser.resolver();
+        match document.compose_node(parser, event, start_mark, end_mark) {
+            Ok(_) => Ok(document),
+            Err(mut e) => {
+                if e.filename.is_none() {
+                    e.filename = parser.filename();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // YAML lets a later anchor rebind the name for the aliases after it; the
+    // parser already warns about it, so only the reject policy makes it fatal
+    fn register_anchor(&mut self, anchor: Option<String>, index: int, mark: YamlMark,
+                       reject: bool) -> Result<(), YamlError> {
+        match anchor {
+            None => Ok(()),
+            Some(name) => {
+                match self.anchors.iter().position(|&(ref a, _, _)| *a == name) {
+                    Some(pos) if reject => {
+                        let first_mark = match self.anchors[pos] { (_, _, mark) => mark };
+                        let mut error = composer_error("second occurrence", mark,
+                                                       Some("found duplicate anchor; first occurrence"), first_mark);
+                        error.code = YamlErrorCode::DuplicateAnchor;
+                        return Err(error);
+                    },
+                    Some(pos) => { self.anchors.remove(pos); },
+                    None => ()
+                }
+                self.anchors.push((name, index, mark));
+                Ok(())
+            }
+        }
+    }
+
+    fn set_node_marks(&mut self, index: int, start_mark: YamlMark, end_mark: YamlMark) {
+        unsafe {
+            let node_ptr = ffi::yaml_document_get_node(&self.document_mem, index as libc::c_int) as *mut ffi::yaml_node_t;
+            (*node_ptr).start_mark = ffi_mark(&start_mark);
+            (*node_ptr).end_mark = ffi_mark(&end_mark);
+        }
+    }
+
+    fn compose_node<P: YamlParser>(&mut self, parser: &mut P,
+        event: YamlEvent, start_mark: YamlMark, end_mark: YamlMark) -> Result<int, YamlError>
+    {
+        self.composed += 1;
+        match parser.node_limit() {
+            Some(limit) if self.composed > limit => {
+                let mut error = composer_error("document exceeds the node limit", start_mark, None, start_mark);
+                error.code = YamlErrorCode::NodeLimitExceeded;
+                return Err(error);
+            },
+            _ => ()
+        }
+        let reject_anchors = parser.duplicate_anchor_policy() == RejectDuplicateAnchors;
+
+        match event {
+            YamlAliasEvent(anchor) => {
+                match self.anchors.iter().find(|&&(ref a, _, _)| *a == anchor) {
+                    Some(&(_, index, _)) => Ok(index),
+                    None => Err(composer_error("found undefined alias", start_mark, None, start_mark))
+                }
+            },
+            YamlScalarEvent(param) => {
+                let index = try!(self.add_scalar(specific_tag(&param.tag), param.value.as_slice(), param.style));
+                self.set_node_marks(index, start_mark, end_mark);
+                try!(self.register_anchor(param.anchor, index, start_mark, reject_anchors));
+                Ok(index)
+            },
+            YamlSequenceStartEvent(param) => {
+                let index = try!(self.add_sequence(specific_tag(&param.tag), param.style));
+                try!(self.register_anchor(param.anchor, index, start_mark, reject_anchors));
+
+                loop {
+                    let (item_event, item_start, item_end) = try!(next_event(parser));
+                    match item_event {
+                        YamlSequenceEndEvent => {
+                            self.set_node_marks(index, start_mark, item_end);
+                            return Ok(index);
+                        },
+                        _ => {
+                            let item = try!(self.compose_node(parser, item_event, item_start, item_end));
+                            try!(self.append_sequence_item(index, item));
+                        }
+                    }
+                }
+            },
+            YamlMappingStartEvent(param) => {
+                let index = try!(self.add_mapping(specific_tag(&param.tag), param.style));
+                try!(self.register_anchor(param.anchor, index, start_mark, reject_anchors));
+                let reject_duplicates = parser.duplicate_key_policy() == RejectDuplicateKeys;
+                let resolver = parser.resolver();
+                let mut keys: HashMap<(String, String), YamlMark> = HashMap::new();
+
+                loop {
+                    let (key_event, key_start, key_end) = try!(next_event(parser));
+                    match key_event {
+                        YamlMappingEndEvent => {
+                            self.set_node_marks(index, start_mark, key_end);
+                            return Ok(index);
+                        },
+                        _ => {
+                            match key_event {
+                                YamlScalarEvent(ref key) => {
+                                    let entry = (key_tag(&resolver, key), key.value.clone());
+                                    let first = keys.get(&entry).map(|&mark| mark);
+                                    match first {
+                                        Some(first_mark) if reject_duplicates => {
+                                            let mut error = composer_error("found duplicate key", key_start,
+                                                Some("first occurrence"), first_mark);
+                                            error.code = YamlErrorCode::DuplicateKey;
+                                            return Err(error);
+                                        },
+                                        Some(first_mark) => {
+                                            let mut warning = YamlWarning::new(YamlWarningKind::DuplicateKey,
+                                                format!("found duplicate key {}", key.value), key_start);
+                                            warning.related_mark = Some(first_mark);
+                                            parser.report_warning(warning);
+                                        },
+                                        None => { keys.insert(entry, key_start); }
+                                    }
+                                },
+                                _ => ()
+                            }
+
+                            let key = try!(self.compose_node(parser, key_event, key_start, key_end));
+                            let (value_event, value_start, value_end) = try!(next_event(parser));
+                            let value = try!(self.compose_node(parser, value_event, value_start, value_end));
+                            try!(self.append_mapping_pair(index, key, value));
+                        }
+                    }
+                }
+            },
+            _ => Err(composer_error("did not find expected node content", start_mark, None, start_mark))
+        }
+    }
+
+    pub fn get_by_anchor<'r>(&'r self, anchor: &str) -> Option<YamlNode<'r>> {
+        self.anchors.iter().find(|&&(ref a, _, _)| a.as_slice() == anchor).and_then(|&(_, index, _)| {
+            self.get_node(index)
+        })
+    }
+
+    pub fn get_node<'r>(&'r self, index: int) -> Option<YamlNode<'r>> {
+        if index < 1 || index > self.node_count() {
             None
         } else {
-            Some(document)
+            unsafe {
+                self.load_node(index as libc::c_int).ok()
+            }
+        }
+    }
+
+    pub fn nodes<'r>(&'r self) -> YamlNodeIter<'r> {
+        YamlNodeIter {
+            doc: self,
+            index: 1,
+            count: self.node_count()
         }
     }
 
+    pub fn version_directive(&self) -> Option<YamlVersionDirective> {
+        if self.document_mem.version_directive == ptr::null() {
+            None
+        } else {
+            unsafe {
+                let c_vsn_dir = &*self.document_mem.version_directive;
+                Some(YamlVersionDirective { major: c_vsn_dir.major as int, minor: c_vsn_dir.minor as int })
+            }
+        }
+    }
+
+    pub fn tag_directives(&self) -> Vec<YamlTagDirective> {
+        let mut tag_dirs = Vec::new();
+        let mut tag_ptr = self.document_mem.tag_directives.start;
+        while tag_ptr != ptr::null() && tag_ptr != self.document_mem.tag_directives.end {
+            unsafe {
+                let tag_ref = &*tag_ptr;
+                let handle = codecs::decode_c_str(tag_ref.handle as *const ffi::yaml_char_t).unwrap();
+                let prefix = codecs::decode_c_str(tag_ref.prefix as *const ffi::yaml_char_t).unwrap();
+                tag_dirs.push(YamlTagDirective { handle: handle, prefix: prefix });
+                tag_ptr = tag_ptr.offset(1);
+            }
+        }
+        tag_dirs
+    }
+
+    pub fn start_implicit(&self) -> bool {
+        self.document_mem.start_implicit != 0
+    }
+
+    pub fn end_implicit(&self) -> bool {
+        self.document_mem.end_implicit != 0
+    }
+
+    pub fn schema(&self) -> YamlSchema {
+        self.resolver.schema()
+    }
+
+    pub fn set_schema(&mut self, schema: YamlSchema) {
+        self.resolver = YamlResolver::new(schema);
+    }
+
+    pub fn resolver(&self) -> YamlResolver {
+        self.resolver
+    }
+
+    pub fn set_resolver(&mut self, resolver: YamlResolver) {
+        self.resolver = resolver;
+    }
+
     pub fn is_empty(&self) -> bool {
         unsafe {
             ffi::yaml_document_get_root_node(&self.document_mem) == ptr::null()
@@ -35,7 +367,7 @@ impl YamlDocument {
 
     pub fn init(version_directive: Option<YamlVersionDirective>,
         tag_directives: &[YamlTagDirective],
-        start_implicit: bool, end_implicit: bool) -> Box<YamlDocument>
+        start_implicit: bool, end_implicit: bool) -> Result<Box<YamlDocument>, YamlError>
     {
         let mut vsn_dir = ffi::yaml_version_directive_t { major: 0, minor: 0 };
         let c_vsn_dir = match version_directive {
@@ -60,66 +392,493 @@ impl YamlDocument {
         let c_start_implicit = if start_implicit { 1 } else { 0 };
         let c_end_implicit = if end_implicit { 1 } else { 0 };
         unsafe {
+            // zeroed, so that dropping after a failed initialize frees nothing
             let mut document = box YamlDocument {
-                document_mem: mem::uninitialized()
+                document_mem: mem::zeroed(),
+                anchors: Vec::new(),
+                composed: 0,
+                resolver: YamlResolver::new(YamlSchema::Yaml11),
+                tagged: Vec::new()
             };
 
             let tag_dir_end = tag_dir_start.offset(c_tag_dirs.len() as int);
             if ffi::yaml_document_initialize(&mut document.document_mem, c_vsn_dir,
                 tag_dir_start, tag_dir_end, c_start_implicit, c_end_implicit) == 0
             {
-                panic!("yaml_document_initialize failed!")
+                return Err(YamlError {
+                    kind: YamlErrorType::YAML_MEMORY_ERROR,
+                    code: YamlErrorCode::MemoryError,
+                    problem: Some("yaml_document_initialize failed".to_string()),
+                    io_error: None,
+                    context: None,
+                    filename: None
+                });
+            }
+
+            Ok(document)
+        }
+    }
+
+    pub fn deep_clone(&self) -> Result<Box<YamlDocument>, YamlError> {
+        let mut doc = try!(YamlDocument::init(self.version_directive(), self.tag_directives().as_slice(),
+                                              self.start_implicit(), self.end_implicit()));
+
+        for res in self.nodes() {
+            let (index, node) = try!(res);
+            let tag = if self.has_explicit_tag(index) { node_ref(&node).tag() } else { None };
+            let new_index = try!(match node {
+                YamlNode::YamlScalarNode(ref scalar) =>
+                    doc.add_scalar(tag.as_ref().map(|t| t.as_slice()), scalar.get_value().as_slice(), scalar.style()),
+                YamlNode::YamlSequenceNode(ref sequence) =>
+                    doc.add_sequence(tag.as_ref().map(|t| t.as_slice()), sequence.style()),
+                YamlNode::YamlMappingNode(ref mapping) =>
+                    doc.add_mapping(tag.as_ref().map(|t| t.as_slice()), mapping.style())
+            });
+            if new_index != index {
+                return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                          YamlErrorCode::InvalidNodeIndex, "node index mismatch while cloning"));
+            }
+            doc.set_node_marks(index, node_ref(&node).start_mark(), node_ref(&node).end_mark());
+        }
+
+        for res in self.nodes() {
+            let (index, node) = try!(res);
+            match node {
+                YamlNode::YamlSequenceNode(ref sequence) => for item in sequence.values() {
+                    try!(doc.append_sequence_item(index, node_ref(&try!(item)).index()));
+                },
+                YamlNode::YamlMappingNode(ref mapping) => for pair in mapping.pairs() {
+                    let (key, value) = try!(pair);
+                    try!(doc.append_mapping_pair(index, node_ref(&key).index(), node_ref(&value).index()));
+                },
+                YamlNode::YamlScalarNode(_) => ()
+            }
+        }
+
+        doc.anchors = self.anchors.clone();
+        doc.resolver = self.resolver;
+        Ok(doc)
+    }
+
+    fn child_indices(&self, node: &YamlNode) -> Result<Vec<int>, YamlError> {
+        let mut children = Vec::new();
+        match *node {
+            YamlNode::YamlSequenceNode(ref sequence) => for item in sequence.values() {
+                children.push(node_ref(&try!(item)).index());
+            },
+            YamlNode::YamlMappingNode(ref mapping) => for pair in mapping.pairs() {
+                let (key, value) = try!(pair);
+                children.push(node_ref(&key).index());
+                children.push(node_ref(&value).index());
+            },
+            YamlNode::YamlScalarNode(_) => ()
+        }
+        Ok(children)
+    }
+
+    fn visit_acyclic(&self, index: int, state: &mut Vec<u8>) -> Result<(), YamlError> {
+        let node = match self.get_node(index) {
+            Some(node) => node,
+            None => return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                              YamlErrorCode::InvalidNodeIndex, "node does not exist"))
+        };
+        state[index as uint] = 1;
+        for child in try!(self.child_indices(&node)).into_iter() {
+            match state[child as uint] {
+                0 => try!(self.visit_acyclic(child, state)),
+                1 => {
+                    let mark = node_ref(&node).start_mark();
+                    let mut error = document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                                   YamlErrorCode::RecursiveAlias, "found a recursive alias");
+                    error.context = Some(YamlErrorContext {
+                        byte_offset: mark.index,
+                        problem_mark: mark,
+                        context: None,
+                        context_mark: mark
+                    });
+                    return Err(error);
+                },
+                _ => ()
+            }
+        }
+        state[index as uint] = 2;
+        Ok(())
+    }
+
+    pub fn check_acyclic(&self) -> Result<(), YamlError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let root = node_ref(&try!(self.root())).index();
+        let mut state = Vec::from_elem(self.node_count() as uint + 1, 0u8);
+        self.visit_acyclic(root, &mut state)
+    }
+
+    pub fn to_json_string(&self) -> Result<String, YamlError> {
+        json::document_to_string(self)
+    }
+
+    pub fn to_json_string_with(&self, limits: &YamlResourceLimits) -> Result<String, YamlError> {
+        json::document_to_string_with(self, limits)
+    }
+
+    pub fn write_json(&self, writer: &mut Writer) -> Result<(), YamlError> {
+        json::write_document(self, writer)
+    }
+
+    pub fn write_json_with(&self, writer: &mut Writer, limits: &YamlResourceLimits) -> Result<(), YamlError> {
+        json::write_document_with(self, writer, limits)
+    }
+
+    pub fn events(&self) -> Result<Vec<YamlEvent>, YamlError> {
+        let mut events = vec![
+            YamlStreamStartEvent(ffi::YamlEncoding::YamlUtf8Encoding),
+            YamlDocumentStartEvent(self.version_directive(), self.tag_directives(), self.start_implicit())
+        ];
+
+        if !self.is_empty() {
+            let root = try!(self.root());
+            let count = self.node_count() as uint;
+            let mut refs = Vec::from_elem(count + 1, 0u);
+            refs[node_ref(&root).index() as uint] += 1;
+            for res in self.nodes() {
+                match try!(res) {
+                    (_, YamlNode::YamlSequenceNode(ref sequence)) => for item in sequence.values() {
+                        refs[node_ref(&try!(item)).index() as uint] += 1;
+                    },
+                    (_, YamlNode::YamlMappingNode(ref mapping)) => for pair in mapping.pairs() {
+                        let (key, value) = try!(pair);
+                        refs[node_ref(&key).index() as uint] += 1;
+                        refs[node_ref(&value).index() as uint] += 1;
+                    },
+                    _ => ()
+                }
+            }
+
+            let mut anchors: Vec<Option<String>> = Vec::from_elem(count + 1, None);
+            for &(ref name, index, _) in self.anchors.iter() {
+                anchors[index as uint] = Some(name.clone());
+            }
+            let mut generated = 0u;
+            for index in range(1, count + 1) {
+                if refs[index] > 1 && anchors[index].is_none() {
+                    // skip names the document already uses for its own anchors
+                    let mut name;
+                    loop {
+                        generated += 1;
+                        name = format!("id{:03}", generated);
+                        if !anchors.iter().any(|a| a.as_ref() == Some(&name)) {
+                            break;
+                        }
+                    }
+                    anchors[index] = Some(name);
+                }
+            }
+
+            let mut emitted = Vec::from_elem(count + 1, false);
+            try!(self.node_events(&root, anchors.as_slice(), &mut emitted, &mut events));
+        }
+
+        events.push(YamlDocumentEndEvent(self.end_implicit()));
+        events.push(YamlStreamEndEvent);
+        Ok(events)
+    }
+
+    fn node_events(&self, node: &YamlNode, anchors: &[Option<String>],
+        emitted: &mut Vec<bool>, events: &mut Vec<YamlEvent>) -> Result<(), YamlError>
+    {
+        let index = node_ref(node).index() as uint;
+        if emitted[index] {
+            return match anchors[index] {
+                Some(ref anchor) => {
+                    events.push(YamlAliasEvent(anchor.clone()));
+                    Ok(())
+                },
+                None => Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                           YamlErrorCode::InvalidNode, "repeated node has no anchor"))
+            };
+        }
+        emitted[index] = true;
+        let anchor = anchors[index].clone();
+
+        match *node {
+            YamlNode::YamlScalarNode(ref scalar) => {
+                // an explicit `!!str` must survive a plain value that resolves otherwise
+                let tag = scalar.explicit_tag();
+                let implicit = is_default_tag(&tag, ffi::YAML_DEFAULT_SCALAR_TAG);
+                let plain_implicit = is_default_tag(&tag, "!");
+                let plain = scalar.style() == ffi::YamlScalarStyle::YamlPlainScalarStyle;
+                events.push(YamlScalarEvent(YamlScalarParam {
+                    anchor: anchor,
+                    tag: if plain_implicit { None } else { tag },
+                    value: scalar.get_value(),
+                    plain_implicit: plain_implicit && plain,
+                    quoted_implicit: implicit && !plain,
+                    style: scalar.style()
+                }));
+            },
+            YamlNode::YamlSequenceNode(ref sequence) => {
+                let tag = sequence.tag();
+                let implicit = is_default_tag(&tag, ffi::YAML_DEFAULT_SEQUENCE_TAG);
+                events.push(YamlSequenceStartEvent(YamlSequenceParam {
+                    anchor: anchor,
+                    tag: if implicit { None } else { tag },
+                    implicit: implicit,
+                    style: sequence.style()
+                }));
+                for item in sequence.values() {
+                    try!(self.node_events(&try!(item), anchors, emitted, events));
+                }
+                events.push(YamlSequenceEndEvent);
+            },
+            YamlNode::YamlMappingNode(ref mapping) => {
+                let tag = mapping.tag();
+                let implicit = is_default_tag(&tag, ffi::YAML_DEFAULT_MAPPING_TAG);
+                events.push(YamlMappingStartEvent(YamlSequenceParam {
+                    anchor: anchor,
+                    tag: if implicit { None } else { tag },
+                    implicit: implicit,
+                    style: mapping.style()
+                }));
+                for pair in mapping.pairs() {
+                    let (key, value) = try!(pair);
+                    try!(self.node_events(&key, anchors, emitted, events));
+                    try!(self.node_events(&value, anchors, emitted, events));
+                }
+                events.push(YamlMappingEndEvent);
+            }
+        }
+        Ok(())
+    }
+
+    fn node_count(&self) -> int {
+        let start = self.document_mem.nodes.start as uint;
+        let top = self.document_mem.nodes.top as uint;
+        ((top - start) / mem::size_of::<ffi::yaml_node_t>()) as int
+    }
+
+    fn node_type_of(&self, index: int) -> Option<ffi::yaml_node_type_t> {
+        if index < 1 || index > self.node_count() {
+            None
+        } else {
+            unsafe {
+                let node_ptr = ffi::yaml_document_get_node(&self.document_mem, index as libc::c_int);
+                Some((*node_ptr).node_type)
+            }
+        }
+    }
+
+    pub fn has_explicit_tag(&self, index: int) -> bool {
+        index >= 1 && self.tagged.get((index - 1) as uint).map_or(false, |&t| t)
+    }
+
+    pub fn add_scalar(&mut self, tag: Option<&str>, value: &str,
+        style: ffi::YamlScalarStyle) -> Result<int, YamlError>
+    {
+        self.add_scalar_bytes(tag, value.as_bytes(), style)
+    }
+
+    // libyaml copies the bytes as they are, so the value need not be UTF-8
+    pub fn add_scalar_bytes(&mut self, tag: Option<&str>, value: &[u8],
+        style: ffi::YamlScalarStyle) -> Result<int, YamlError>
+    {
+        let c_tag = tag.map(|s| { s.to_c_str() });
+        let tag_ptr = match c_tag {
+            Some(ref s) => s.as_ptr(),
+            None => ptr::null()
+        };
+
+        unsafe {
+            let index = ffi::yaml_document_add_scalar(&mut self.document_mem,
+                tag_ptr as *const ffi::yaml_char_t,
+                value.as_ptr(), value.len() as libc::c_int, style);
+
+            if index == 0 {
+                Err(document_error(YamlErrorType::YAML_MEMORY_ERROR,
+                                   YamlErrorCode::MemoryError, "failed to add a scalar node"))
+            } else {
+                self.tagged.push(tag.is_some());
+                Ok(index as int)
+            }
+        }
+    }
+
+    pub fn add_sequence(&mut self, tag: Option<&str>,
+        style: ffi::YamlSequenceStyle) -> Result<int, YamlError>
+    {
+        let c_tag = tag.map(|s| { s.to_c_str() });
+        let tag_ptr = match c_tag {
+            Some(ref s) => s.as_ptr(),
+            None => ptr::null()
+        };
+
+        unsafe {
+            let index = ffi::yaml_document_add_sequence(&mut self.document_mem,
+                tag_ptr as *const ffi::yaml_char_t, style);
+
+            if index == 0 {
+                Err(document_error(YamlErrorType::YAML_MEMORY_ERROR,
+                                   YamlErrorCode::MemoryError, "failed to add a sequence node"))
+            } else {
+                self.tagged.push(tag.is_some());
+                Ok(index as int)
+            }
+        }
+    }
+
+    pub fn add_mapping(&mut self, tag: Option<&str>,
+        style: ffi::YamlSequenceStyle) -> Result<int, YamlError>
+    {
+        let c_tag = tag.map(|s| { s.to_c_str() });
+        let tag_ptr = match c_tag {
+            Some(ref s) => s.as_ptr(),
+            None => ptr::null()
+        };
+
+        unsafe {
+            let index = ffi::yaml_document_add_mapping(&mut self.document_mem,
+                tag_ptr as *const ffi::yaml_char_t, style);
+
+            if index == 0 {
+                Err(document_error(YamlErrorType::YAML_MEMORY_ERROR,
+                                   YamlErrorCode::MemoryError, "failed to add a mapping node"))
+            } else {
+                self.tagged.push(tag.is_some());
+                Ok(index as int)
+            }
+        }
+    }
+
+    pub fn append_sequence_item(&mut self, sequence: int, item: int) -> Result<(), YamlError> {
+        match self.node_type_of(sequence) {
+            Some(YAML_SEQUENCE_NODE) => (),
+            _ => return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                           YamlErrorCode::InvalidNodeIndex, "the target node is not a sequence"))
+        }
+        if self.node_type_of(item).is_none() {
+            return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                      YamlErrorCode::InvalidNodeIndex, "invalid item node index"));
+        }
+
+        unsafe {
+            if ffi::yaml_document_append_sequence_item(&mut self.document_mem,
+                sequence as libc::c_int, item as libc::c_int) == 0
+            {
+                Err(document_error(YamlErrorType::YAML_MEMORY_ERROR,
+                                   YamlErrorCode::MemoryError, "failed to append a sequence item"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    pub fn append_mapping_pair(&mut self, mapping: int, key: int, value: int) -> Result<(), YamlError> {
+        match self.node_type_of(mapping) {
+            Some(YAML_MAPPING_NODE) => (),
+            _ => return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                           YamlErrorCode::InvalidNodeIndex, "the target node is not a mapping"))
+        }
+        if self.node_type_of(key).is_none() {
+            return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                      YamlErrorCode::InvalidNodeIndex, "invalid key node index"));
+        }
+        if self.node_type_of(value).is_none() {
+            return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                      YamlErrorCode::InvalidNodeIndex, "invalid value node index"));
+        }
+
+        unsafe {
+            if ffi::yaml_document_append_mapping_pair(&mut self.document_mem,
+                mapping as libc::c_int, key as libc::c_int, value as libc::c_int) == 0
+            {
+                Err(document_error(YamlErrorType::YAML_MEMORY_ERROR,
+                                   YamlErrorCode::MemoryError, "failed to append a mapping pair"))
+            } else {
+                Ok(())
             }
+        }
+    }
+
+    pub fn set_scalar_value(&mut self, index: int, value: &str) -> Result<(), YamlError> {
+        match self.node_type_of(index) {
+            Some(YAML_SCALAR_NODE) => (),
+            _ => return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                           YamlErrorCode::InvalidNodeIndex, "the target node is not a scalar"))
+        }
+
+        unsafe {
+            let buf = libc::malloc((value.len() + 1) as libc::size_t) as *mut u8;
+            if buf == ptr::null_mut() {
+                return Err(document_error(YamlErrorType::YAML_MEMORY_ERROR,
+                                          YamlErrorCode::MemoryError, "failed to allocate a scalar value"));
+            }
+            ptr::copy_nonoverlapping_memory(buf, value.as_ptr(), value.len());
+            *buf.offset(value.len() as int) = 0;
 
-            document
+            let node_ptr = ffi::yaml_document_get_node(&self.document_mem, index as libc::c_int) as *mut ffi::yaml_node_t;
+            let scalar_data: &mut ffi::yaml_scalar_node_t = mem::transmute(&mut (*node_ptr).data);
+            libc::free(scalar_data.value as *mut libc::c_void);
+            scalar_data.value = buf as *const ffi::yaml_char_t;
+            scalar_data.length = value.len() as libc::size_t;
         }
+
+        Ok(())
     }
 
-    unsafe fn load<'r>(&'r self, node_ptr: *const ffi::yaml_node_t) -> YamlNode<'r> {
+    unsafe fn load<'r>(&'r self, node_ptr: *const ffi::yaml_node_t) -> Result<YamlNode<'r>, YamlError> {
         if node_ptr == ptr::null() {
-            panic!("empty node")
+            return Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                      YamlErrorCode::InvalidNode, "node does not exist"));
         }
+        let index = ((node_ptr as uint - self.document_mem.nodes.start as uint)
+                     / mem::size_of::<ffi::yaml_node_t>()) as int + 1;
         let node = &*node_ptr;
         match node.node_type {
             YAML_SCALAR_NODE => {
                 let scalar_data: &ffi::yaml_scalar_node_t = mem::transmute(&node.data);
-                YamlNode::YamlScalarNode(YamlScalarData {
+                Ok(YamlNode::YamlScalarNode(YamlScalarData {
+                    index: index,
+                    doc: self,
                     node: node,
                     data: scalar_data
-                })
+                }))
             },
             YAML_SEQUENCE_NODE => {
                 let sequence_data: &ffi::yaml_sequence_node_t = mem::transmute(&node.data);
-                YamlNode::YamlSequenceNode(YamlSequenceData {
+                Ok(YamlNode::YamlSequenceNode(YamlSequenceData {
+                    index: index,
                     doc: self,
                     node: node,
                     data: sequence_data
-                })
+                }))
             },
             YAML_MAPPING_NODE => {
                 let mapping_data: &ffi::yaml_sequence_node_t = mem::transmute(&node.data);
-                YamlNode::YamlMappingNode(YamlMappingData {
+                Ok(YamlNode::YamlMappingNode(YamlMappingData {
+                    index: index,
                     doc: self,
                     node: node,
                     data: mapping_data
-                })
+                }))
             },
-            _ => panic!("invalid node")
+            _ => Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR, YamlErrorCode::InvalidNode,
+                format!("unknown node type at index {}", index).as_slice()))
         }
     }
 
-    unsafe fn get_node<'r>(&'r self, index: libc::c_int) -> YamlNode<'r> {
+    unsafe fn load_node<'r>(&'r self, index: libc::c_int) -> Result<YamlNode<'r>, YamlError> {
         let node_ptr = ffi::yaml_document_get_node(&self.document_mem, index);
         self.load(node_ptr)
     }
 
-    pub fn root<'r>(&'r self) -> Option<YamlNode<'r>> {
+    pub fn root<'r>(&'r self) -> Result<YamlNode<'r>, YamlError> {
         unsafe {
             let node_ptr = ffi::yaml_document_get_root_node(&self.document_mem);
             if node_ptr == ptr::null() {
-                None
+                Err(document_error(YamlErrorType::YAML_COMPOSER_ERROR,
+                                   YamlErrorCode::EmptyDocument, "document is empty"))
             } else {
-                Some(self.load(node_ptr))
+                self.load(node_ptr)
             }
         }
     }
@@ -139,9 +898,45 @@ pub enum YamlNode<'r> {
     YamlMappingNode(YamlMappingData<'r>),
 }
 
+fn node_ref<'a, 'r>(node: &'a YamlNode<'r>) -> &'a (YamlNodeData + 'a) {
+    match *node {
+        YamlNode::YamlScalarNode(ref scalar) => scalar as &YamlNodeData,
+        YamlNode::YamlSequenceNode(ref sequence) => sequence as &YamlNodeData,
+        YamlNode::YamlMappingNode(ref mapping) => mapping as &YamlNodeData
+    }
+}
+
+impl<'r> fmt::Show for YamlNode<'r> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut writer = MemWriter::new();
+        {
+            let mut emitter = YamlEmitter::init(&mut writer);
+            let res = emitter.emit_stream(ffi::YamlEncoding::YamlUtf8Encoding, |e| {
+                e.emit_document(None, &[], true, |e| e.emit_node(self))
+            });
+            if res.is_err() {
+                return Err(fmt::Error);
+            }
+        }
+
+        let text = match str::from_utf8(writer.get_ref()) {
+            Ok(text) => text.trim_right_chars('\n'),
+            Err(_) => return Err(fmt::Error)
+        };
+        let fragment = if text.ends_with("\n...") {
+            text.slice_to(text.len() - 4)
+        } else {
+            text
+        };
+        f.write_str(fragment)
+    }
+}
+
 pub trait YamlNodeData {
     unsafe fn internal_node<'r>(&'r self) -> &'r ffi::yaml_node_t;
 
+    fn index(&self) -> int;
+
     fn tag(&self) -> Option<String> {
         unsafe {
             codecs::decode_c_str(self.internal_node().tag)
@@ -159,9 +954,20 @@ pub trait YamlNodeData {
             YamlMark::conv(&self.internal_node().end_mark)
         }
     }
+
+    fn source_span<'a>(&self, source: &'a str) -> Option<&'a str> {
+        let start = codecs::char_to_byte_offset(source, self.start_mark().index);
+        let end = codecs::char_to_byte_offset(source, self.end_mark().index);
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end => Some(source.slice(start, end)),
+            _ => None
+        }
+    }
 }
 
 pub struct YamlScalarData<'r> {
+    index: int,
+    doc: &'r YamlDocument,
     node: &'r ffi::yaml_node_t,
     data: &'r ffi::yaml_scalar_node_t
 }
@@ -170,6 +976,10 @@ impl<'r> YamlNodeData for YamlScalarData<'r> {
     unsafe fn internal_node<'a>(&'a self) -> &'a ffi::yaml_node_t {
         self.node
     }
+
+    fn index(&self) -> int {
+        self.index
+    }
 }
 
 impl<'r> YamlScalarData<'r> {
@@ -177,12 +987,60 @@ impl<'r> YamlScalarData<'r> {
         codecs::decode_buf(self.data.value, self.data.length).unwrap()
     }
 
+    pub fn get_bytes(&self) -> &'r [u8] {
+        if self.data.value == ptr::null() {
+            &[]
+        } else {
+            unsafe {
+                slice::from_raw_buf(&self.data.value, self.data.length as uint)
+            }
+        }
+    }
+
     pub fn style(&self) -> ffi::YamlScalarStyle {
         self.data.style
     }
+
+    // the tag written in the source, without the default libyaml fills in
+    pub fn explicit_tag(&self) -> Option<String> {
+        if self.doc.has_explicit_tag(self.index) { self.tag() } else { None }
+    }
+
+    pub fn to_scalar(&self) -> YamlScalar {
+        YamlScalar::new(self.get_value(), self.explicit_tag(), self.style(), Some(self.start_mark()))
+    }
+
+    pub fn resolver(&self) -> YamlResolver {
+        self.doc.resolver()
+    }
+
+    pub fn typed_value(&self) -> Result<YamlStandardData, YamlError> {
+        self.to_scalar().typed_value_with(&self.resolver())
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.to_scalar().as_i64_with(&self.resolver())
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.to_scalar().as_f64_with(&self.resolver())
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.to_scalar().as_bool_with(&self.resolver())
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.to_scalar().is_null_with(&self.resolver())
+    }
+
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        self.to_scalar().as_bytes()
+    }
 }
 
 pub struct YamlSequenceData<'r> {
+    index: int,
     doc: &'r YamlDocument,
     node: &'r ffi::yaml_node_t,
     data: &'r ffi::yaml_sequence_node_t
@@ -192,9 +1050,17 @@ impl<'r> YamlNodeData for YamlSequenceData<'r> {
     unsafe fn internal_node<'a>(&'a self) -> &'a ffi::yaml_node_t {
         self.node
     }
+
+    fn index(&self) -> int {
+        self.index
+    }
 }
 
 impl<'r> YamlSequenceData<'r> {
+    pub fn style(&self) -> ffi::YamlSequenceStyle {
+        self.data.style
+    }
+
     pub fn values(&self) -> YamlSequenceIter<'r> {
         YamlSequenceIter {
             doc: self.doc,
@@ -204,6 +1070,29 @@ impl<'r> YamlSequenceData<'r> {
     }
 }
 
+pub struct YamlNodeIter<'r> {
+    doc: &'r YamlDocument,
+    index: int,
+    count: int
+}
+
+impl<'r> Iterator for YamlNodeIter<'r> {
+    type Item = Result<(int, YamlNode<'r>), YamlError>;
+
+    fn next(&mut self) -> Option<Result<(int, YamlNode<'r>), YamlError>> {
+        if self.index > self.count {
+            None
+        } else {
+            let index = self.index;
+            self.index += 1;
+
+            unsafe {
+                Some(self.doc.load_node(index as libc::c_int).map(|node| (index, node)))
+            }
+        }
+    }
+}
+
 pub struct YamlSequenceIter<'r> {
     doc: &'r YamlDocument,
     top: *const libc::c_int,
@@ -211,14 +1100,14 @@ pub struct YamlSequenceIter<'r> {
 }
 
 impl<'r> Iterator for YamlSequenceIter<'r> {
-    type Item = YamlNode<'r>;
+    type Item = Result<YamlNode<'r>, YamlError>;
 
-    fn next(&mut self) -> Option<YamlNode<'r>> {
+    fn next(&mut self) -> Option<Result<YamlNode<'r>, YamlError>> {
         if self.ptr == self.top {
             None
         } else {
             unsafe {
-                let next_node = self.doc.get_node(*self.ptr);
+                let next_node = self.doc.load_node(*self.ptr);
 
                 self.ptr = self.ptr.offset(1);
 
@@ -229,6 +1118,7 @@ impl<'r> Iterator for YamlSequenceIter<'r> {
 }
 
 pub struct YamlMappingData<'r> {
+    index: int,
     doc: &'r YamlDocument,
     node: &'r ffi::yaml_node_t,
     data: &'r ffi::yaml_sequence_node_t
@@ -238,9 +1128,17 @@ impl<'r> YamlNodeData for YamlMappingData<'r> {
     unsafe fn internal_node<'a>(&'a self) -> &'a ffi::yaml_node_t {
         self.node
     }
+
+    fn index(&self) -> int {
+        self.index
+    }
 }
 
 impl<'r> YamlMappingData<'r> {
+    pub fn style(&self) -> ffi::YamlSequenceStyle {
+        self.data.style
+    }
+
     pub fn pairs(&self) -> YamlMappingIter<'r> {
         YamlMappingIter {
             doc: self.doc,
@@ -257,21 +1155,339 @@ pub struct YamlMappingIter<'r> {
 }
 
 impl<'r> Iterator for YamlMappingIter<'r> {
-    type Item = (YamlNode<'r>, YamlNode<'r>);
+    type Item = Result<(YamlNode<'r>, YamlNode<'r>), YamlError>;
 
-    fn next(&mut self) -> Option<(YamlNode<'r>, YamlNode<'r>)> {
+    fn next(&mut self) -> Option<Result<(YamlNode<'r>, YamlNode<'r>), YamlError>> {
         if self.ptr == self.top {
             None
         } else {
             unsafe {
-                let next_key = self.doc.get_node((*self.ptr).key);
-                let next_value = self.doc.get_node((*self.ptr).value);
-
+                let doc = self.doc;
+                let pair = *self.ptr;
                 self.ptr = self.ptr.offset(1);
 
-                Some((next_key, next_value))
+                let next_pair = doc.load_node(pair.key).and_then(|next_key| {
+                    doc.load_node(pair.value).map(|next_value| (next_key, next_value))
+                });
+
+                Some(next_pair)
             }
         }
     }
 }
 
+
+#[cfg(test)]
+mod test {
+    use document::{YamlDocument, YamlNode, YamlNodeData};
+    use parser::{YamlParser, YamlByteParser};
+    use constructor::{YamlConstructor, YamlStandardConstructor};
+    use constructor::YamlStandardData::*;
+    use error::YamlErrorCode;
+    use schema::{YamlResolver, YamlSchema};
+    use event::{YamlVersionDirective, YamlTagDirective};
+    use event::YamlEvent::YamlAliasEvent;
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+    use ffi::YamlScalarStyle::*;
+    use ffi::YamlSequenceStyle::*;
+
+    #[test]
+    fn test_build_sequence() {
+        let mut doc = YamlDocument::init(None, &[], true, true).unwrap();
+        let seq = doc.add_sequence(None, YamlFlowSequenceStyle).unwrap();
+        let one = doc.add_scalar(None, "1", YamlPlainScalarStyle).unwrap();
+        let two = doc.add_scalar(None, "2", YamlPlainScalarStyle).unwrap();
+        doc.append_sequence_item(seq, one).unwrap();
+        doc.append_sequence_item(seq, two).unwrap();
+
+        match doc.root() {
+            Ok(YamlNode::YamlSequenceNode(seq)) => {
+                let values:Vec<String> = seq.values().map(|node| {
+                    match node.unwrap() {
+                        YamlNode::YamlScalarNode(scalar) => scalar.get_value(),
+                        _ => panic!("unexpected scalar")
+                    }
+                }).collect();
+                assert_eq!(vec!["1".to_string(), "2".to_string()], values)
+            },
+            _ => panic!("unexpected result")
+        }
+    }
+
+    #[test]
+    fn test_build_mapping() {
+        let mut doc = YamlDocument::init(None, &[], true, true).unwrap();
+        let map = doc.add_mapping(None, YamlBlockSequenceStyle).unwrap();
+        let key = doc.add_scalar(None, "a", YamlPlainScalarStyle).unwrap();
+        let value = doc.add_scalar(None, "1", YamlPlainScalarStyle).unwrap();
+        doc.append_mapping_pair(map, key, value).unwrap();
+
+        match doc.root() {
+            Ok(YamlNode::YamlMappingNode(map)) => assert_eq!(1, map.pairs().count()),
+            _ => panic!("unexpected result")
+        }
+    }
+
+    #[test]
+    fn test_edit_parsed_document() {
+        let data = "{a: 1, b: [x]}";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut doc = parser.load().next().unwrap().unwrap();
+
+        let (scalar, seq) = match doc.root() {
+            Ok(YamlNode::YamlMappingNode(map)) => {
+                let values: Vec<int> = map.pairs().map(|pair| pair.unwrap()).map(|(_, value)| match value {
+                    YamlNode::YamlScalarNode(scalar) => scalar.index(),
+                    YamlNode::YamlSequenceNode(seq) => seq.index(),
+                    YamlNode::YamlMappingNode(map) => map.index()
+                }).collect();
+                (values[0], values[1])
+            },
+            _ => panic!("unexpected result")
+        };
+
+        doc.set_scalar_value(scalar, "2").unwrap();
+        let item = doc.add_scalar(None, "y", YamlPlainScalarStyle).unwrap();
+        doc.append_sequence_item(seq, item).unwrap();
+        let map_index = match doc.root() {
+            Ok(YamlNode::YamlMappingNode(map)) => map.index(),
+            _ => panic!("unexpected result")
+        };
+        let key = doc.add_scalar(None, "c", YamlPlainScalarStyle).unwrap();
+        let value = doc.add_scalar(None, "3", YamlPlainScalarStyle).unwrap();
+        doc.append_mapping_pair(map_index, key, value).unwrap();
+
+        let ctor = YamlStandardConstructor::new();
+        assert_eq!(Ok(YamlMapping(vec![
+            (YamlString("a".to_string()), YamlInteger(2)),
+            (YamlString("b".to_string()), YamlSequence(vec![YamlString("x".to_string()), YamlString("y".to_string())])),
+            (YamlString("c".to_string()), YamlInteger(3))
+        ])), ctor.construct(doc.root().unwrap()))
+    }
+
+    #[test]
+    fn test_directives() {
+        let data = "%YAML 1.1\n%TAG !e! tag:example.com,2000:\n---\n!e!foo bar\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        assert_eq!(Some(YamlVersionDirective { major: 1, minor: 1 }), doc.version_directive());
+        assert_eq!(vec![YamlTagDirective { handle: "!e!".to_string(), prefix: "tag:example.com,2000:".to_string() }],
+                   doc.tag_directives());
+    }
+
+    #[test]
+    fn test_implicit_flags() {
+        let data = "a\n--- b\n...\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let docs: Vec<Box<YamlDocument>> = parser.load().map(|doc| doc.unwrap()).collect();
+
+        assert!(docs[0].start_implicit());
+        assert!(docs[0].end_implicit());
+        assert!(!docs[1].start_implicit());
+        assert!(!docs[1].end_implicit());
+    }
+
+    #[test]
+    fn test_no_directives() {
+        let doc = YamlDocument::init(None, &[], true, true).unwrap();
+
+        assert_eq!(None, doc.version_directive());
+        assert!(doc.tag_directives().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_append() {
+        let mut doc = YamlDocument::init(None, &[], true, true).unwrap();
+        let scalar = doc.add_scalar(None, "a", YamlPlainScalarStyle).unwrap();
+
+        assert!(doc.append_sequence_item(scalar, scalar).is_err());
+        assert!(doc.append_mapping_pair(scalar, scalar, scalar).is_err());
+        match doc.append_sequence_item(5, scalar) {
+            Err(e) => assert_eq!(YamlErrorCode::InvalidNodeIndex, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_empty_root() {
+        let doc = YamlDocument::init(None, &[], true, true).unwrap();
+
+        match doc.root() {
+            Err(e) => assert_eq!(YamlErrorCode::EmptyDocument, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_get_node() {
+        let data = "[a, b]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        match doc.get_node(2) {
+            Some(YamlNode::YamlScalarNode(scalar)) => {
+                assert_eq!("a".to_string(), scalar.get_value());
+                assert_eq!(2, scalar.index());
+            },
+            _ => panic!("unexpected result")
+        }
+        assert!(doc.get_node(0).is_none());
+        assert!(doc.get_node(4).is_none());
+    }
+
+    #[test]
+    fn test_nodes() {
+        let data = "{a: [1, 2]}";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        let scalars: Vec<(int, String)> = doc.nodes().filter_map(|res| match res.unwrap() {
+            (index, YamlNode::YamlScalarNode(scalar)) => Some((index, scalar.get_value())),
+            _ => None
+        }).collect();
+        assert_eq!(vec![(2, "a".to_string()), (4, "1".to_string()), (5, "2".to_string())], scalars);
+        assert_eq!(5, doc.nodes().count());
+    }
+
+    #[test]
+    fn test_get_bytes() {
+        let data = "\"\\xff\"";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        match doc.root() {
+            Ok(YamlNode::YamlScalarNode(scalar)) => assert_eq!([0xc3u8, 0xbf].as_slice(), scalar.get_bytes()),
+            _ => panic!("unexpected result")
+        }
+    }
+
+    #[test]
+    fn test_get_bytes_not_utf8() {
+        let mut doc = YamlDocument::init(None, &[], true, true).unwrap();
+        let index = doc.add_scalar_bytes(None, &[b'a', 0xff, 0xfe], YamlPlainScalarStyle).unwrap();
+
+        match doc.get_node(index) {
+            Some(YamlNode::YamlScalarNode(scalar)) => assert_eq!([b'a', 0xff, 0xfe].as_slice(), scalar.get_bytes()),
+            _ => panic!("unexpected result")
+        }
+    }
+
+    #[test]
+    fn test_show_node() {
+        let data = "{a: [1, 'b'], c: !foo d}";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        assert_eq!("{a: [1, 'b'], c: !foo d}", format!("{}", doc.root().unwrap()).as_slice());
+        assert_eq!("'b'", format!("{}", doc.get_node(5).unwrap()).as_slice());
+    }
+
+    #[test]
+    fn test_deep_clone() {
+        let data = "%YAML 1.1\n--- !root\na: &x [1, 2]\nb: *x\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut doc = parser.load().next().unwrap().unwrap();
+        let copy = doc.deep_clone().unwrap();
+
+        let ctor = YamlStandardConstructor::new();
+        let original = doc.root().and_then(|root| ctor.construct(root));
+        doc.set_scalar_value(5, "3").unwrap();
+
+        assert_eq!(original, copy.root().and_then(|root| ctor.construct(root)));
+        assert!(original != doc.root().and_then(|root| ctor.construct(root)));
+        assert_eq!(doc.version_directive(), copy.version_directive());
+        assert_eq!(Some("!root".to_string()), match copy.root() {
+            Ok(YamlNode::YamlMappingNode(map)) => map.tag(),
+            _ => None
+        });
+        assert!(copy.get_by_anchor("x").is_some());
+        assert_eq!(2, match copy.get_node(3) {
+            Some(YamlNode::YamlSequenceNode(seq)) => seq.start_mark().line,
+            _ => 0
+        });
+    }
+
+    #[test]
+    fn test_deep_clone_keeps_resolver() {
+        let parser = YamlByteParser::init("08".as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut doc = parser.load().next().unwrap().unwrap();
+        doc.set_resolver(YamlResolver::new(YamlSchema::Core));
+        let copy = doc.deep_clone().unwrap();
+
+        assert_eq!(doc.resolver(), copy.resolver());
+        assert_eq!(Some(8), match copy.root() {
+            Ok(YamlNode::YamlScalarNode(scalar)) => scalar.as_i64(),
+            _ => None
+        });
+    }
+
+    #[test]
+    fn test_events() {
+        let data = "&a [x, *a, {k: &b 'v', j: *b}, !t y]";
+        let expected: Vec<_> = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+                                    .map(|evt| evt.unwrap()).collect();
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        assert_eq!(Ok(expected), doc.events());
+    }
+
+    #[test]
+    fn test_events_generated_anchor() {
+        let mut doc = YamlDocument::init(None, &[], true, true).unwrap();
+        let seq = doc.add_sequence(None, YamlFlowSequenceStyle).unwrap();
+        let item = doc.add_scalar(None, "x", YamlPlainScalarStyle).unwrap();
+        doc.append_sequence_item(seq, item).unwrap();
+        doc.append_sequence_item(seq, item).unwrap();
+
+        let events = doc.events().unwrap();
+        assert_eq!(YamlAliasEvent("id001".to_string()), events[4]);
+    }
+
+    #[test]
+    fn test_events_generated_anchor_is_unique() {
+        let data = "[&id001 x, *id001]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut doc = parser.load().next().unwrap().unwrap();
+        let item = doc.add_scalar(None, "y", YamlPlainScalarStyle).unwrap();
+        doc.append_sequence_item(1, item).unwrap();
+        doc.append_sequence_item(1, item).unwrap();
+
+        let events = doc.events().unwrap();
+        assert_eq!(YamlAliasEvent("id001".to_string()), events[4]);
+        assert_eq!(YamlAliasEvent("id002".to_string()), events[6]);
+    }
+
+    #[test]
+    fn test_source_span() {
+        let data = "name: \"h\u00e9llo\"\nlist: [\u00fc, {k: v}]\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        let spans: Vec<Option<&str>> = doc.nodes().map(|res| match res.unwrap() {
+            (_, YamlNode::YamlScalarNode(scalar)) => scalar.source_span(data),
+            (_, YamlNode::YamlSequenceNode(seq)) => seq.source_span(data),
+            (_, YamlNode::YamlMappingNode(map)) => map.source_span(data)
+        }).collect();
+        assert_eq!(Some("\"h\u00e9llo\""), spans[2]);
+        assert_eq!(Some("[\u00fc, {k: v}]"), spans[4]);
+        assert_eq!(Some("{k: v}"), spans[6]);
+        match doc.root() {
+            Ok(YamlNode::YamlMappingNode(map)) => assert_eq!(None, map.source_span("x")),
+            _ => panic!("unexpected result")
+        }
+    }
+
+    #[test]
+    fn test_check_acyclic() {
+        let parser = YamlByteParser::init("a: &x [1, 2]\nb: *x\n".as_bytes(), YamlUtf8Encoding).unwrap();
+        assert!(parser.load().next().unwrap().unwrap().check_acyclic().is_ok());
+
+        let parser = YamlByteParser::init("a: &x [1, {b: *x}]".as_bytes(), YamlUtf8Encoding).unwrap();
+        match parser.load().next().unwrap().unwrap().check_acyclic() {
+            Err(e) => assert_eq!(YamlErrorCode::RecursiveAlias, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+}
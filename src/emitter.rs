@@ -1,7 +1,14 @@
 use ffi;
-use error::YamlError;
-use event::{YamlVersionDirective, YamlTagDirective};
-
+use error::{YamlError, YamlErrorCode};
+use event::{YamlEvent, YamlVersionDirective, YamlTagDirective};
+use document::{YamlNode, YamlNodeData};
+use parser::YamlResourceLimits;
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+use codecs;
+use schema;
+
+use std::str;
 use std::ptr;
 use std::mem;
 use std::c_vec::CVec;
@@ -9,6 +16,8 @@ use std::c_str::{CString, ToCStr};
 use std::io::IoError;
 use libc;
 
+static BINARY_LINE_WIDTH: uint = 76;
+
 pub struct YamlBaseEmitter {
     emitter_mem: ffi::yaml_emitter_t
 }
@@ -32,7 +41,11 @@ impl Drop for YamlBaseEmitter {
 pub struct YamlEmitter<'r> {
     base_emitter: YamlBaseEmitter,
     writer: &'r mut (Writer+'r),
+    tees: Vec<&'r mut (Writer+'r)>,
     io_error: Option<IoError>,
+    output_limit: Option<uint>,
+    bytes_written: uint,
+    limit_exceeded: bool,
 }
 
 impl<'r> YamlEmitter<'r> {
@@ -41,7 +54,11 @@ impl<'r> YamlEmitter<'r> {
             let mut emitter = box YamlEmitter {
                 base_emitter: YamlBaseEmitter::new(),
                 writer: writer,
-                io_error: None
+                tees: Vec::new(),
+                io_error: None,
+                output_limit: None,
+                bytes_written: 0,
+                limit_exceeded: false
             };
 
             if ffi::yaml_emitter_initialize(&mut emitter.base_emitter.emitter_mem) == 0 {
@@ -54,16 +71,36 @@ impl<'r> YamlEmitter<'r> {
         }
     }
 
+    // only output_bytes applies to an emitter
+    pub fn set_limits(&mut self, limits: YamlResourceLimits) {
+        self.output_limit = limits.output_bytes;
+    }
+
+    pub fn add_tee(&mut self, writer: &'r mut (Writer+'r)) {
+        self.tees.push(writer);
+    }
+
+    pub fn bytes_written(&self) -> uint {
+        self.bytes_written
+    }
+
     fn get_error(&mut self) -> YamlError {
         let emitter_mem = &self.base_emitter.emitter_mem;
         unsafe {
             let mut error = YamlError {
                 kind: emitter_mem.error,
+                code: YamlErrorCode::from_kind(emitter_mem.error),
                 problem: CString::new(emitter_mem.problem, false).as_str().map(|s| s.to_string()),
                 io_error: None,
-                context: None
+                context: None,
+                filename: None
             };
 
+            if self.limit_exceeded {
+                error.code = YamlErrorCode::OutputLimitExceeded;
+                error.problem = Some(format!("output exceeded the limit of {} bytes", self.output_limit.unwrap()));
+            }
+
             mem::swap(&mut self.io_error, &mut error.io_error);
 
             return error;
@@ -241,6 +278,84 @@ impl<'r> YamlEmitter<'r> {
         }
     }
 
+    pub fn emit_float(&mut self, anchor: Option<&str>, value: f64) -> Result<(), YamlError> {
+        self.emit_scalar_event(anchor, None, schema::float_text(value).as_slice(),
+                               true, false, ffi::YamlScalarStyle::YamlPlainScalarStyle)
+    }
+
+    pub fn emit_binary_scalar(&mut self, anchor: Option<&str>, data: &[u8]) -> Result<(), YamlError> {
+        let encoded = codecs::base64_encode(data);
+        if encoded.len() <= BINARY_LINE_WIDTH {
+            return self.emit_scalar_event(anchor, Some(ffi::YAML_BINARY_TAG), encoded.as_slice(),
+                                          false, false, ffi::YamlScalarStyle::YamlPlainScalarStyle);
+        }
+
+        let mut text = String::new();
+        for line in encoded.as_bytes().chunks(BINARY_LINE_WIDTH) {
+            text.push_str(str::from_utf8(line).unwrap());
+            text.push('\n');
+        }
+        self.emit_scalar_event(anchor, Some(ffi::YAML_BINARY_TAG), text.as_slice(),
+                               false, false, ffi::YamlScalarStyle::YamlLiteralScalarStyle)
+    }
+
+    pub fn emit_string(&mut self, anchor: Option<&str>, value: &str) -> Result<(), YamlError> {
+        let style = if schema::needs_quotes(value) {
+            ffi::YamlScalarStyle::YamlSingleQuotedScalarStyle
+        } else {
+            ffi::YamlScalarStyle::YamlAnyScalarStyle
+        };
+        self.emit_scalar_event(anchor, None, value, true, true, style)
+    }
+
+    fn emit_pair_list(&mut self, tag: &str, pairs: &[(YamlStandardData, YamlStandardData)]) -> Result<(), YamlError> {
+        try!(self.emit_sequence_start_event(None, Some(tag), false, ffi::YamlSequenceStyle::YamlAnySequenceStyle));
+        for &(ref key, ref value) in pairs.iter() {
+            try!(self.emit_mapping_start_event(None, None, true, ffi::YamlSequenceStyle::YamlAnySequenceStyle));
+            try!(self.emit_value(key));
+            try!(self.emit_value(value));
+            try!(self.emit_mapping_end_event());
+        }
+        self.emit_sequence_end_event()
+    }
+
+    pub fn emit_value(&mut self, value: &YamlStandardData) -> Result<(), YamlError> {
+        let plain = ffi::YamlScalarStyle::YamlPlainScalarStyle;
+        match *value {
+            YamlNull => self.emit_scalar_event(None, None, "null", true, false, plain),
+            YamlBool(b) => self.emit_scalar_event(None, None, if b { "true" } else { "false" }, true, false, plain),
+            YamlInteger(i) => self.emit_scalar_event(None, None, i.to_string().as_slice(), true, false, plain),
+            YamlFloat(f) => self.emit_float(None, f),
+            YamlString(ref s) => self.emit_string(None, s.as_slice()),
+            YamlSequence(ref items) => {
+                try!(self.emit_sequence_start_event(None, None, true, ffi::YamlSequenceStyle::YamlAnySequenceStyle));
+                for item in items.iter() {
+                    try!(self.emit_value(item));
+                }
+                self.emit_sequence_end_event()
+            },
+            YamlSet(ref items) => {
+                try!(self.emit_mapping_start_event(None, Some("tag:yaml.org,2002:set"), false,
+                                                   ffi::YamlSequenceStyle::YamlAnySequenceStyle));
+                for item in items.iter() {
+                    try!(self.emit_value(item));
+                    try!(self.emit_value(&YamlNull));
+                }
+                self.emit_mapping_end_event()
+            },
+            YamlMapping(ref pairs) => {
+                try!(self.emit_mapping_start_event(None, None, true, ffi::YamlSequenceStyle::YamlAnySequenceStyle));
+                for &(ref key, ref value) in pairs.iter() {
+                    try!(self.emit_value(key));
+                    try!(self.emit_value(value));
+                }
+                self.emit_mapping_end_event()
+            },
+            YamlOrderedMap(ref pairs) => self.emit_pair_list("tag:yaml.org,2002:omap", pairs.as_slice()),
+            YamlPairs(ref pairs) => self.emit_pair_list("tag:yaml.org,2002:pairs", pairs.as_slice())
+        }
+    }
+
     pub fn emit_sequence(&mut self, anchor: Option<&str>, tag: Option<&str>, implicit: bool,
             style: ffi::YamlSequenceStyle,
             f: |&mut YamlEmitter| -> Result<(), YamlError>)
@@ -251,7 +366,7 @@ impl<'r> YamlEmitter<'r> {
         self.emit_sequence_end_event()
     }
 
-    fn emit_sequence_start_event(&mut self, anchor: Option<&str>, tag: Option<&str>, implicit: bool,
+    pub fn emit_sequence_start_event(&mut self, anchor: Option<&str>, tag: Option<&str>, implicit: bool,
         style: ffi::YamlSequenceStyle) -> Result<(), YamlError>
     {
         let c_anchor = anchor.map(|s| { s.to_c_str() });
@@ -284,7 +399,7 @@ impl<'r> YamlEmitter<'r> {
         }
     }
 
-    fn emit_sequence_end_event(&mut self) -> Result<(), YamlError> {
+    pub fn emit_sequence_end_event(&mut self) -> Result<(), YamlError> {
         unsafe {
             let mut event = mem::uninitialized();
 
@@ -310,7 +425,7 @@ impl<'r> YamlEmitter<'r> {
         self.emit_mapping_end_event()
     }
 
-    fn emit_mapping_start_event(&mut self, anchor: Option<&str>, tag: Option<&str>, implicit: bool,
+    pub fn emit_mapping_start_event(&mut self, anchor: Option<&str>, tag: Option<&str>, implicit: bool,
         style: ffi::YamlSequenceStyle) -> Result<(), YamlError>
     {
         let c_anchor = anchor.map(|s| { s.to_c_str() });
@@ -343,7 +458,7 @@ impl<'r> YamlEmitter<'r> {
         }
     }
 
-    fn emit_mapping_end_event(&mut self) -> Result<(), YamlError> {
+    pub fn emit_mapping_end_event(&mut self) -> Result<(), YamlError> {
         unsafe {
             let mut event = mem::uninitialized();
 
@@ -359,6 +474,97 @@ impl<'r> YamlEmitter<'r> {
         }
     }
 
+    pub fn emit_event(&mut self, event: &YamlEvent) -> Result<(), YamlError> {
+        match *event {
+            YamlEvent::YamlNoEvent => Ok(()),
+            YamlEvent::YamlStreamStartEvent(encoding) => self.emit_stream_start_event(encoding),
+            YamlEvent::YamlStreamEndEvent => self.emit_stream_end_event(),
+            YamlEvent::YamlDocumentStartEvent(version_directive, ref tag_directives, implicit) =>
+                self.emit_document_start_event(version_directive, tag_directives.as_slice(), implicit),
+            YamlEvent::YamlDocumentEndEvent(implicit) => self.emit_document_end_event(implicit),
+            YamlEvent::YamlAliasEvent(ref anchor) => self.emit_alias_event(anchor.as_slice()),
+            YamlEvent::YamlScalarEvent(ref param) =>
+                self.emit_scalar_event(param.anchor.as_ref().map(|s| s.as_slice()),
+                                       param.tag.as_ref().map(|s| s.as_slice()),
+                                       param.value.as_slice(), param.plain_implicit,
+                                       param.quoted_implicit, param.style),
+            YamlEvent::YamlSequenceStartEvent(ref param) =>
+                self.emit_sequence_start_event(param.anchor.as_ref().map(|s| s.as_slice()),
+                                               param.tag.as_ref().map(|s| s.as_slice()),
+                                               param.implicit, param.style),
+            YamlEvent::YamlSequenceEndEvent => self.emit_sequence_end_event(),
+            YamlEvent::YamlMappingStartEvent(ref param) =>
+                self.emit_mapping_start_event(param.anchor.as_ref().map(|s| s.as_slice()),
+                                              param.tag.as_ref().map(|s| s.as_slice()),
+                                              param.implicit, param.style),
+            YamlEvent::YamlMappingEndEvent => self.emit_mapping_end_event()
+        }
+    }
+
+    pub fn chunked<'e, I>(&'e mut self, events: I) -> YamlChunkedEmitter<'e, 'r, I>
+        where I: Iterator<Item=Result<YamlEvent, YamlError>>
+    {
+        YamlChunkedEmitter {
+            emitter: self,
+            events: events,
+            done: false
+        }
+    }
+
+    pub fn emit_node(&mut self, node: &YamlNode) -> Result<(), YamlError> {
+        self.emit_node_inner(node, &mut Vec::new())
+    }
+
+    fn emit_node_inner(&mut self, node: &YamlNode, stack: &mut Vec<int>) -> Result<(), YamlError> {
+        match *node {
+            YamlNode::YamlScalarNode(ref scalar) => {
+                // an explicit `!!str` keeps its tag on plain values, which would
+                // otherwise resolve by their text
+                let tag = scalar.explicit_tag();
+                let plain_implicit = is_default_tag(&tag, "!");
+                let quoted_implicit = is_default_tag(&tag, ffi::YAML_DEFAULT_SCALAR_TAG);
+                let explicit_tag = if plain_implicit { None } else { tag.as_ref().map(|t| t.as_slice()) };
+                let value = scalar.get_value();
+
+                self.emit_scalar_event(None, explicit_tag, value.as_slice(), plain_implicit, quoted_implicit, scalar.style())
+            },
+            YamlNode::YamlSequenceNode(ref sequence) => {
+                let tag = sequence.tag();
+                let implicit = is_default_tag(&tag, ffi::YAML_DEFAULT_SEQUENCE_TAG);
+                let explicit_tag = if implicit { None } else { tag.as_ref().map(|t| t.as_slice()) };
+
+                if stack.contains(&sequence.index()) {
+                    return Err(recursive_alias_error());
+                }
+                stack.push(sequence.index());
+                try!(self.emit_sequence_start_event(None, explicit_tag, implicit, sequence.style()));
+                for item in sequence.values() {
+                    try!(self.emit_node_inner(&try!(item), stack));
+                }
+                stack.pop();
+                self.emit_sequence_end_event()
+            },
+            YamlNode::YamlMappingNode(ref mapping) => {
+                let tag = mapping.tag();
+                let implicit = is_default_tag(&tag, ffi::YAML_DEFAULT_MAPPING_TAG);
+                let explicit_tag = if implicit { None } else { tag.as_ref().map(|t| t.as_slice()) };
+
+                if stack.contains(&mapping.index()) {
+                    return Err(recursive_alias_error());
+                }
+                stack.push(mapping.index());
+                try!(self.emit_mapping_start_event(None, explicit_tag, implicit, mapping.style()));
+                for pair in mapping.pairs() {
+                    let (key, value) = try!(pair);
+                    try!(self.emit_node_inner(&key, stack));
+                    try!(self.emit_node_inner(&value, stack));
+                }
+                stack.pop();
+                self.emit_mapping_end_event()
+            }
+        }
+    }
+
     pub fn flush(&mut self) -> Result<(), YamlError> {
         unsafe {
             if ffi::yaml_emitter_flush(&mut self.base_emitter.emitter_mem) != 0 {
@@ -370,17 +576,87 @@ impl<'r> YamlEmitter<'r> {
     }
 }
 
+fn recursive_alias_error() -> YamlError {
+    YamlError {
+        kind: ffi::YamlErrorType::YAML_EMITTER_ERROR,
+        code: YamlErrorCode::RecursiveAlias,
+        problem: Some("cannot emit a recursive alias as a node tree".to_string()),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+pub fn is_default_tag(tag: &Option<String>, default_tag: &str) -> bool {
+    match *tag {
+        None => true,
+        Some(ref t) => t.as_slice() == default_tag || t.as_slice() == "!"
+    }
+}
+
+pub struct YamlChunkedEmitter<'e, 'r: 'e, I> {
+    emitter: &'e mut YamlEmitter<'r>,
+    events: I,
+    done: bool
+}
+
+impl<'e, 'r: 'e, I> YamlChunkedEmitter<'e, 'r, I>
+    where I: Iterator<Item=Result<YamlEvent, YamlError>>
+{
+    // emits events until at least `max_bytes` reach the writer; each event is
+    // flushed so the count is what the writer has actually taken
+    pub fn step(&mut self, max_bytes: uint) -> Result<bool, YamlError> {
+        let start = self.emitter.bytes_written();
+        while !self.done && self.emitter.bytes_written() - start < max_bytes {
+            match self.events.next() {
+                Some(event) => {
+                    try!(self.emitter.emit_event(&try!(event)));
+                    try!(self.emitter.flush());
+                },
+                None => self.done = true
+            }
+        }
+        Ok(self.done)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn bytes_written(&self) -> uint {
+        self.emitter.bytes_written()
+    }
+}
+
 extern fn handle_writer_cb(data: *mut YamlEmitter, buffer: *const u8, size: libc::size_t) -> libc::c_int {
     unsafe {
         let buf = CVec::new(buffer as *mut u8, size as uint);
         let emitter = &mut *data;
+        match emitter.output_limit {
+            Some(limit) if emitter.bytes_written + (size as uint) > limit => {
+                emitter.limit_exceeded = true;
+                return 0;
+            },
+            _ => ()
+        }
         match emitter.writer.write(buf.as_slice()) {
-            Ok(()) => 1,
+            Ok(()) => (),
             Err(err) => {
                 emitter.io_error = Some(err);
-                0
+                return 0;
+            }
+        }
+        for tee in emitter.tees.iter_mut() {
+            match tee.write(buf.as_slice()) {
+                Ok(()) => (),
+                Err(err) => {
+                    emitter.io_error = Some(err);
+                    return 0;
+                }
             }
         }
+        emitter.bytes_written += size as uint;
+        1
     }
 }
 
@@ -388,7 +664,11 @@ extern fn handle_writer_cb(data: *mut YamlEmitter, buffer: *const u8, size: libc
 mod test {
     use std::io::MemWriter;
     use emitter::YamlEmitter;
+    use error::YamlErrorCode;
+    use parser::{YamlParser, YamlByteParser, YamlResourceLimits};
+    use document::YamlNode;
     use ffi::YamlEncoding::YamlUtf8Encoding;
+    use std::f64;
     use ffi::YamlScalarStyle::*;
     use ffi::YamlSequenceStyle::*;
 
@@ -431,4 +711,133 @@ mod test {
         }
         assert_eq!(writer.get_ref(), "{a: 1, b: 2}\n".as_bytes());
     }
+
+    #[test]
+    fn float_scalar_test() {
+        let mut writer = MemWriter::new();
+        {
+            let mut emitter = YamlEmitter::init(&mut writer);
+            let res = emitter.emit_stream(YamlUtf8Encoding, |e| {
+                e.emit_document(None, &[], true, |e| {
+                    e.emit_sequence(None, None, true, YamlFlowSequenceStyle, |e| {
+                        for &value in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN, 1.0, 2.5].iter() {
+                            try!(e.emit_float(None, value));
+                        }
+                        Ok(())
+                    })
+                })
+            });
+            assert!(res.is_ok());
+        }
+        assert_eq!(writer.get_ref(), "[.inf, -.inf, .nan, 1.0, 2.5]\n".as_bytes());
+    }
+
+    #[test]
+    fn binary_scalar_test() {
+        let short = b"hello";
+        let long = Vec::from_fn(100, |i| i as u8);
+        let mut writer = MemWriter::new();
+        {
+            let mut emitter = YamlEmitter::init(&mut writer);
+            let res = emitter.emit_stream(YamlUtf8Encoding, |e| {
+                e.emit_document(None, &[], true, |e| {
+                    e.emit_sequence(None, None, true, YamlBlockSequenceStyle, |e| {
+                        try!(e.emit_binary_scalar(None, short));
+                        e.emit_binary_scalar(None, long.as_slice())
+                    })
+                })
+            });
+            assert!(res.is_ok());
+        }
+
+        let output = writer.get_ref();
+        assert!(output.starts_with(b"- !!binary aGVsbG8=\n- !!binary |\n"));
+        let parser = YamlByteParser::init(output, YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        match doc.root() {
+            Ok(YamlNode::YamlSequenceNode(seq)) => {
+                let values: Vec<Option<Vec<u8>>> = seq.values().map(|node| match node {
+                    Ok(YamlNode::YamlScalarNode(scalar)) => scalar.as_bytes(),
+                    _ => panic!("unexpected node")
+                }).collect();
+                assert_eq!(vec![Some(short.to_vec()), Some(long)], values);
+            },
+            _ => panic!("unexpected root")
+        }
+    }
+
+    #[test]
+    fn output_limit_test() {
+        let mut writer = MemWriter::new();
+        let mut emitter = YamlEmitter::init(&mut writer);
+        let mut limits = YamlResourceLimits::new();
+        limits.output_bytes = Some(8);
+        emitter.set_limits(limits);
+        let result = emitter.emit_stream(YamlUtf8Encoding, |e| {
+            e.emit_document(None, &[], true, |e| {
+                e.emit_scalar_event(None, None, "a long scalar value", true, false, YamlPlainScalarStyle)
+            })
+        });
+
+        match result {
+            Err(e) => assert_eq!(YamlErrorCode::OutputLimitExceeded, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+        assert_eq!(0, emitter.bytes_written());
+    }
+
+    #[test]
+    fn chunked_emitter_test() {
+        let data = "[1, 2, {a: b}, [c]]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut writer = MemWriter::new();
+        {
+            let mut emitter = YamlEmitter::init(&mut writer);
+            let mut chunked = emitter.chunked(parser.parse());
+            let mut progress = vec![0u];
+            while !chunked.step(4).unwrap() {
+                progress.push(chunked.bytes_written());
+            }
+            assert!(progress.len() > 2);
+            assert!(progress.windows(2).all(|w| w[1] >= w[0] + 4));
+            assert!(chunked.is_done());
+        }
+        assert_eq!(writer.get_ref(), "[1, 2, {a: b}, [c]]\n".as_bytes());
+    }
+
+    #[test]
+    fn tee_test() {
+        let mut writer = MemWriter::new();
+        let mut tee = MemWriter::new();
+        {
+            let mut emitter = YamlEmitter::init(&mut writer);
+            emitter.add_tee(&mut tee);
+            let result = emitter.emit_stream(YamlUtf8Encoding, |e| {
+                e.emit_document(None, &[], true, |e| {
+                    e.emit_scalar_event(None, None, "a", true, false, YamlPlainScalarStyle)
+                })
+            });
+            assert!(result.is_ok());
+        }
+        assert!(!writer.get_ref().is_empty());
+        assert_eq!(writer.get_ref(), tee.get_ref());
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn node_emitter_test() {
+        let data = "{a: [1, 'b'], c: !foo d}";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let mut writer = MemWriter::new();
+        {
+            let mut emitter = YamlEmitter::init(&mut writer);
+            emitter.emit_stream(YamlUtf8Encoding, |e| {
+                e.emit_document(None, &[], true, |e| {
+                    e.emit_node(&try!(doc.root()))
+                })
+            });
+        }
+        assert_eq!(writer.get_ref(), "{a: [1, 'b'], c: !foo d}\n".as_bytes());
+    }
 }
@@ -1,10 +1,12 @@
-use std::error::Error;
-use std::io::IoError;
+use std::cmp;
+use std::error::{Error, FromError};
+use std::fmt;
+use std::str::Utf8Error;
+use std::io::{IoError, IoErrorKind};
 use ffi;
-use ffi::YamlErrorType;
 use ffi::YamlErrorType::*;
 
-#[derive(Show, PartialEq, Copy)]
+#[derive(PartialEq, Copy)]
 pub struct YamlMark {
     pub index: uint,
     pub line: uint,
@@ -19,6 +21,20 @@ impl YamlMark {
             column: mark.column as uint
         }
     }
+
+    pub fn line1(&self) -> uint {
+        self.line + 1
+    }
+
+    pub fn column1(&self) -> uint {
+        self.column + 1
+    }
+}
+
+impl fmt::Show for YamlMark {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line1(), self.column1())
+    }
 }
 
 #[derive(Show, PartialEq)]
@@ -29,12 +45,389 @@ pub struct YamlErrorContext {
     pub context_mark: YamlMark
 }
 
+#[derive(Show, PartialEq, Eq, Copy)]
+pub enum YamlErrorCode {
+    NoError = 0,
+    MemoryError = 1,
+    ReaderError = 2,
+    WriterError = 3,
+    EmitterError = 4,
+
+    ScannerError = 101,
+    ParserError = 102,
+    ComposerError = 103,
+
+    InvalidEscape = 201,
+    InvalidNodeIndex = 202,
+    DuplicateKey = 203,
+    InvalidNode = 204,
+    EmptyDocument = 205,
+    RecursiveAlias = 206,
+    AnchorRejected = 207,
+    TagRejected = 208,
+    DuplicateAnchor = 209,
+    Yaml11Rejected = 210,
+    DecodeError = 211,
+
+    OutputLimitExceeded = 301,
+    JsonUnrepresentable = 302,
+    AliasExpansionLimitExceeded = 303,
+    Cancelled = 304,
+    NodeLimitExceeded = 305,
+    InputLimitExceeded = 306,
+    EventLimitExceeded = 307,
+    ScalarLimitExceeded = 308,
+    DepthLimitExceeded = 309,
+
+    TabIndentation = 401,
+    DeprecatedBoolean = 402,
+    Yaml11Construct = 403,
+}
+
+impl YamlErrorCode {
+    pub fn from_kind(kind: ffi::YamlErrorType) -> YamlErrorCode {
+        match kind {
+            YAML_NO_ERROR => YamlErrorCode::NoError,
+            YAML_MEMORY_ERROR => YamlErrorCode::MemoryError,
+            YAML_READER_ERROR => YamlErrorCode::ReaderError,
+            YAML_SCANNER_ERROR => YamlErrorCode::ScannerError,
+            YAML_PARSER_ERROR => YamlErrorCode::ParserError,
+            YAML_COMPOSER_ERROR => YamlErrorCode::ComposerError,
+            YAML_WRITER_ERROR => YamlErrorCode::WriterError,
+            YAML_EMITTER_ERROR => YamlErrorCode::EmitterError,
+        }
+    }
+
+    pub fn number(&self) -> uint {
+        *self as uint
+    }
+
+    pub fn to_code_string(&self) -> String {
+        format!("E{:04}", self.number())
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            YamlErrorCode::NoError => "no error",
+            YamlErrorCode::MemoryError => "cannot allocate memory",
+            YamlErrorCode::ReaderError => "cannot read or decode the input stream",
+            YamlErrorCode::WriterError => "cannot write to the output stream",
+            YamlErrorCode::EmitterError => "cannot emit a YAML stream",
+            YamlErrorCode::ScannerError => "scanner error",
+            YamlErrorCode::ParserError => "parser error",
+            YamlErrorCode::ComposerError => "composer error",
+            YamlErrorCode::InvalidEscape => "invalid escape sequence",
+            YamlErrorCode::InvalidNodeIndex => "invalid node index",
+            YamlErrorCode::DuplicateKey => "duplicate mapping key",
+            YamlErrorCode::InvalidNode => "invalid document node",
+            YamlErrorCode::EmptyDocument => "document has no root node",
+            YamlErrorCode::RecursiveAlias => "alias refers to one of its own ancestors",
+            YamlErrorCode::AnchorRejected => "anchors and aliases are not allowed",
+            YamlErrorCode::TagRejected => "tag is not in the YAML core schema",
+            YamlErrorCode::DuplicateAnchor => "anchor is defined more than once",
+            YamlErrorCode::Yaml11Rejected => "construct is only valid in YAML 1.1",
+            YamlErrorCode::DecodeError => "value does not match the decoded type",
+            YamlErrorCode::OutputLimitExceeded => "output size limit exceeded",
+            YamlErrorCode::JsonUnrepresentable => "value cannot be represented as JSON",
+            YamlErrorCode::AliasExpansionLimitExceeded => "alias expansion limit exceeded",
+            YamlErrorCode::Cancelled => "parsing was cancelled",
+            YamlErrorCode::NodeLimitExceeded => "document node limit exceeded",
+            YamlErrorCode::InputLimitExceeded => "input size limit exceeded",
+            YamlErrorCode::EventLimitExceeded => "document event limit exceeded",
+            YamlErrorCode::ScalarLimitExceeded => "scalar length limit exceeded",
+            YamlErrorCode::DepthLimitExceeded => "nesting depth limit exceeded",
+            YamlErrorCode::TabIndentation => "tab used for indentation",
+            YamlErrorCode::DeprecatedBoolean => "YAML 1.1 word boolean",
+            YamlErrorCode::Yaml11Construct => "value resolves differently in YAML 1.1",
+        }
+    }
+}
+
+pub trait YamlMessageRenderer {
+    fn render(&self, code: YamlErrorCode, params: &[(&'static str, String)]) -> String;
+}
+
+#[derive(Copy)]
+pub struct YamlEnglishRenderer;
+
+fn find_param<'a>(params: &'a [(&'static str, String)], name: &str) -> Option<&'a str> {
+    params.iter().find(|&&(key, _)| key == name).map(|&(_, ref value)| value.as_slice())
+}
+
+impl YamlMessageRenderer for YamlEnglishRenderer {
+    fn render(&self, code: YamlErrorCode, params: &[(&'static str, String)]) -> String {
+        let mut msg = match find_param(params, "filename") {
+            Some(filename) => format!("{}: ", filename),
+            None => String::new()
+        };
+        msg.push_str(code.description());
+
+        match find_param(params, "problem") {
+            Some(problem) => { msg.push_str(": "); msg.push_str(problem); },
+            None => ()
+        }
+        match find_param(params, "context") {
+            Some(context) => { msg.push_str(" "); msg.push_str(context); },
+            None => ()
+        }
+        match (find_param(params, "line"), find_param(params, "column")) {
+            (Some(line), Some(column)) => msg.push_str(format!(" at line {}, column {}", line, column).as_slice()),
+            _ => ()
+        }
+
+        msg
+    }
+}
+
 #[derive(Show, PartialEq)]
+pub struct YamlProblem<'e> {
+    pub problem: Option<&'e str>,
+    pub problem_mark: Option<YamlMark>,
+    pub context: Option<&'e str>,
+    pub context_mark: Option<YamlMark>
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlReaderProblem<'e> {
+    pub cause: Option<&'e IoError>,
+    pub problem: Option<&'e str>,
+    pub byte_offset: Option<uint>
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlScannerProblem<'e> {
+    pub expected: Option<&'e str>,
+    pub found: Option<&'e str>,
+    pub problem: YamlProblem<'e>
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlComposerProblem<'e> {
+    pub problem: Option<&'e str>,
+    pub problem_mark: Option<YamlMark>,
+    pub context_mark: Option<YamlMark>
+}
+
+#[derive(Show, PartialEq)]
+pub enum YamlErrorStage<'e> {
+    NoError,
+    MemoryError,
+    ReaderError(YamlReaderProblem<'e>),
+    ScannerError(YamlScannerProblem<'e>),
+    ParserError(YamlProblem<'e>),
+    ComposerError(YamlComposerProblem<'e>),
+    WriterError(Option<&'e IoError>),
+    EmitterError(YamlProblem<'e>)
+}
+
+// libyaml phrases scanner problems as "did not find expected X" or "found X"
+fn split_expectation<'e>(problem: Option<&'e str>) -> (Option<&'e str>, Option<&'e str>) {
+    match problem {
+        Some(p) if p.starts_with("did not find expected ") => (Some(p.slice_from("did not find expected ".len())), None),
+        Some(p) if p.starts_with("found ") => (None, Some(p.slice_from("found ".len()))),
+        _ => (None, None)
+    }
+}
+
+fn push_snippet(out: &mut String, source: &str, mark: YamlMark, width: uint, marker: char, label: &str) {
+    let text = source.lines_any().nth(mark.line).unwrap_or("");
+    let number = mark.line1().to_string();
+
+    out.push('\n');
+    for _ in range(number.len(), width) {
+        out.push(' ');
+    }
+    out.push_str(number.as_slice());
+    out.push_str(" | ");
+    out.push_str(text);
+    out.push('\n');
+    for _ in range(0, width) {
+        out.push(' ');
+    }
+    out.push_str(" | ");
+    for c in text.chars().take(mark.column) {
+        out.push(if c == '\t' { '\t' } else { ' ' });
+    }
+    out.push(marker);
+    if !label.is_empty() {
+        out.push(' ');
+        out.push_str(label);
+    }
+}
+
+#[derive(PartialEq)]
 pub struct YamlError {
     pub kind: ffi::YamlErrorType,
+    pub code: YamlErrorCode,
     pub problem: Option<String>,
     pub io_error: Option<IoError>,
-    pub context: Option<YamlErrorContext>
+    pub context: Option<YamlErrorContext>,
+    pub filename: Option<String>
+}
+
+impl YamlError {
+    pub fn from_io_error(kind: ffi::YamlErrorType, err: IoError) -> YamlError {
+        YamlError {
+            kind: kind,
+            code: YamlErrorCode::from_kind(kind),
+            problem: Some(err.desc.to_string()),
+            io_error: Some(err),
+            context: None,
+            filename: None
+        }
+    }
+
+    pub fn io_cause(&self) -> Option<&IoError> {
+        self.io_error.as_ref()
+    }
+
+    pub fn io_error_kind(&self) -> Option<IoErrorKind> {
+        self.io_error.as_ref().map(|e| e.kind)
+    }
+
+    pub fn stage<'e>(&'e self) -> YamlErrorStage<'e> {
+        let problem = YamlProblem {
+            problem: self.problem.as_ref().map(|p| p.as_slice()),
+            problem_mark: self.context.as_ref().map(|c| c.problem_mark),
+            context: self.context.as_ref().and_then(|c| c.context.as_ref().map(|ctx| ctx.as_slice())),
+            context_mark: self.context.as_ref().and_then(|c| c.context.as_ref().map(|_| c.context_mark))
+        };
+
+        match self.kind {
+            YAML_NO_ERROR => YamlErrorStage::NoError,
+            YAML_MEMORY_ERROR => YamlErrorStage::MemoryError,
+            YAML_READER_ERROR => YamlErrorStage::ReaderError(YamlReaderProblem {
+                cause: self.io_error.as_ref(),
+                problem: problem.problem,
+                byte_offset: self.context.as_ref().map(|c| c.byte_offset)
+            }),
+            YAML_SCANNER_ERROR => {
+                let (expected, found) = split_expectation(problem.problem);
+                YamlErrorStage::ScannerError(YamlScannerProblem {
+                    expected: expected,
+                    found: found,
+                    problem: problem
+                })
+            },
+            YAML_PARSER_ERROR => YamlErrorStage::ParserError(problem),
+            YAML_COMPOSER_ERROR => YamlErrorStage::ComposerError(YamlComposerProblem {
+                problem: problem.problem,
+                problem_mark: problem.problem_mark,
+                context_mark: self.context.as_ref().map(|c| c.context_mark)
+            }),
+            YAML_WRITER_ERROR => YamlErrorStage::WriterError(self.io_error.as_ref()),
+            YAML_EMITTER_ERROR => YamlErrorStage::EmitterError(problem)
+        }
+    }
+
+    pub fn is_syntax_error(&self) -> bool {
+        match self.code {
+            YamlErrorCode::ScannerError | YamlErrorCode::ParserError | YamlErrorCode::InvalidEscape => true,
+            _ => false
+        }
+    }
+
+    pub fn is_io_error(&self) -> bool {
+        self.io_error.is_some() || match self.code {
+            YamlErrorCode::ReaderError | YamlErrorCode::WriterError => true,
+            _ => false
+        }
+    }
+
+    pub fn is_limit_exceeded(&self) -> bool {
+        match self.code {
+            YamlErrorCode::OutputLimitExceeded | YamlErrorCode::AliasExpansionLimitExceeded |
+            YamlErrorCode::NodeLimitExceeded | YamlErrorCode::InputLimitExceeded |
+            YamlErrorCode::EventLimitExceeded | YamlErrorCode::ScalarLimitExceeded |
+            YamlErrorCode::DepthLimitExceeded => true,
+            _ => false
+        }
+    }
+
+    pub fn with_filename(mut self, filename: &str) -> YamlError {
+        self.filename = Some(filename.to_string());
+        self
+    }
+
+    pub fn message_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+
+        match self.filename {
+            Some(ref filename) => params.push(("filename", filename.clone())),
+            None => ()
+        }
+
+        match self.problem {
+            Some(ref problem) => params.push(("problem", problem.clone())),
+            None => ()
+        }
+        match self.context {
+            Some(ref context) => {
+                match context.context {
+                    Some(ref ctx) => params.push(("context", ctx.clone())),
+                    None => ()
+                }
+                params.push(("line", context.problem_mark.line1().to_string()));
+                params.push(("column", context.problem_mark.column1().to_string()));
+                params.push(("offset", context.byte_offset.to_string()));
+            },
+            None => ()
+        }
+
+        params
+    }
+
+    pub fn render_with<R: YamlMessageRenderer>(&self, renderer: &R) -> String {
+        renderer.render(self.code, self.message_params().as_slice())
+    }
+
+    pub fn message(&self) -> String {
+        self.render_with(&YamlEnglishRenderer)
+    }
+
+    pub fn render(&self, source: &str) -> String {
+        let mut out = self.message();
+        match self.context {
+            Some(ref context) => {
+                let last_line = cmp::max(context.problem_mark.line, context.context_mark.line);
+                let width = (last_line + 1).to_string().len();
+                match context.context {
+                    Some(ref label) if context.context_mark != context.problem_mark =>
+                        push_snippet(&mut out, source, context.context_mark, width, '-', label.as_slice()),
+                    _ => ()
+                }
+                let problem = self.problem.as_ref().map_or("", |p| p.as_slice());
+                push_snippet(&mut out, source, context.problem_mark, width, '^', problem);
+            },
+            None => ()
+        }
+        out
+    }
+}
+
+impl FromError<IoError> for YamlError {
+    fn from_error(err: IoError) -> YamlError {
+        YamlError::from_io_error(YAML_READER_ERROR, err)
+    }
+}
+
+impl FromError<Utf8Error> for YamlError {
+    fn from_error(err: Utf8Error) -> YamlError {
+        YamlError {
+            kind: YAML_READER_ERROR,
+            code: YamlErrorCode::ReaderError,
+            problem: Some(format!("invalid UTF-8: {}", err)),
+            io_error: None,
+            context: None,
+            filename: None
+        }
+    }
+}
+
+impl fmt::Show for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
 }
 
 impl Error for YamlError {
@@ -62,3 +455,164 @@ impl Error for YamlError {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use error::{YamlError, YamlErrorCode, YamlErrorContext, YamlMark, YamlMessageRenderer};
+    use error::{YamlErrorStage, YamlProblem, YamlScannerProblem, YamlComposerProblem, YamlReaderProblem};
+    use std::error::FromError;
+    use std::str;
+    use ffi::YamlErrorType;
+    use std::io;
+
+    struct KoreanRenderer;
+
+    impl YamlMessageRenderer for KoreanRenderer {
+        fn render(&self, code: YamlErrorCode, params: &[(&'static str, String)]) -> String {
+            let line = params.iter().find(|&&(key, _)| key == "line").map(|&(_, ref v)| v.clone());
+            match code {
+                YamlErrorCode::ScannerError => format!("{}번째 줄에서 스캐너 오류", line.unwrap()),
+                _ => "오류".to_string()
+            }
+        }
+    }
+
+    fn scanner_error() -> YamlError {
+        let mark = YamlMark { index: 10, line: 2, column: 4 };
+        YamlError {
+            kind: YamlErrorType::YAML_SCANNER_ERROR,
+            code: YamlErrorCode::ScannerError,
+            problem: Some("found unexpected end of stream".to_string()),
+            io_error: None,
+            context: Some(YamlErrorContext {
+                byte_offset: 10,
+                problem_mark: mark,
+                context: Some("while scanning a quoted scalar".to_string()),
+                context_mark: mark
+            }),
+            filename: None
+        }
+    }
+
+    #[test]
+    fn test_default_message() {
+        assert_eq!("scanner error: found unexpected end of stream while scanning a quoted scalar at line 3, column 5".to_string(),
+                   scanner_error().message())
+    }
+
+    #[test]
+    fn test_show() {
+        assert_eq!(scanner_error().message(), format!("{}", scanner_error()))
+    }
+
+    #[test]
+    fn test_filename_message() {
+        assert_eq!("in.yaml: scanner error: found unexpected end of stream while scanning a quoted scalar at line 3, column 5".to_string(),
+                   scanner_error().with_filename("in.yaml").message())
+    }
+
+    #[test]
+    fn test_render_snippet() {
+        let source = "key: \"value\nnext: 1\n\tlast";
+        let mut error = scanner_error();
+        error.context = Some(YamlErrorContext {
+            byte_offset: 23,
+            problem_mark: YamlMark { index: 23, line: 2, column: 3 },
+            context: Some("while scanning a quoted scalar".to_string()),
+            context_mark: YamlMark { index: 5, line: 0, column: 5 }
+        });
+
+        let expected = format!("{}\n1 | key: \"value\n  |      - while scanning a quoted scalar\n3 | \tlast\n  | \t  ^ found unexpected end of stream",
+                               error.message());
+        assert_eq!(expected, error.render(source));
+    }
+
+    #[test]
+    fn test_error_stage() {
+        let mark = YamlMark { index: 10, line: 2, column: 4 };
+        assert_eq!(YamlErrorStage::ScannerError(YamlScannerProblem {
+            expected: None,
+            found: Some("unexpected end of stream"),
+            problem: YamlProblem {
+                problem: Some("found unexpected end of stream"),
+                problem_mark: Some(mark),
+                context: Some("while scanning a quoted scalar"),
+                context_mark: Some(mark)
+            }
+        }), scanner_error().stage());
+
+        let mut error = scanner_error();
+        error.problem = Some("did not find expected ':'".to_string());
+        match error.stage() {
+            YamlErrorStage::ScannerError(YamlScannerProblem { expected, found, .. }) => {
+                assert_eq!(Some("':'"), expected);
+                assert_eq!(None, found);
+            },
+            stage => panic!("unexpected stage: {}", stage)
+        }
+
+        let mut error = scanner_error();
+        error.kind = YamlErrorType::YAML_COMPOSER_ERROR;
+        error.context.as_mut().unwrap().context_mark = YamlMark { index: 0, line: 0, column: 0 };
+        error.context.as_mut().unwrap().context = None;
+        assert_eq!(YamlErrorStage::ComposerError(YamlComposerProblem {
+            problem: Some("found unexpected end of stream"),
+            problem_mark: Some(mark),
+            context_mark: Some(YamlMark { index: 0, line: 0, column: 0 })
+        }), error.stage());
+
+        let error = YamlError::from_io_error(YamlErrorType::YAML_READER_ERROR, io::standard_error(io::EndOfFile));
+        match error.stage() {
+            YamlErrorStage::ReaderError(YamlReaderProblem { cause: Some(cause), byte_offset: None, .. }) =>
+                assert_eq!(io::EndOfFile, cause.kind),
+            stage => panic!("unexpected stage: {}", stage)
+        }
+
+        let error = YamlError::from_io_error(YamlErrorType::YAML_WRITER_ERROR, io::standard_error(io::BrokenPipe));
+        match error.stage() {
+            YamlErrorStage::WriterError(Some(cause)) => assert_eq!(io::BrokenPipe, cause.kind),
+            stage => panic!("unexpected stage: {}", stage)
+        }
+    }
+
+    fn read_utf8(bytes: &[u8]) -> ::Result<String> {
+        Ok(try!(str::from_utf8(bytes)).to_string())
+    }
+
+    #[test]
+    fn test_from_error() {
+        let error: YamlError = FromError::from_error(io::standard_error(io::PermissionDenied));
+        assert_eq!(YamlErrorCode::ReaderError, error.code);
+        assert_eq!(Some(io::PermissionDenied), error.io_error_kind());
+
+        assert_eq!(Ok("ok".to_string()), read_utf8("ok".as_bytes()));
+        match read_utf8(&[0xC3, 0x28]) {
+            Err(e) => assert_eq!(YamlErrorCode::ReaderError, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_mark_show() {
+        let mark = YamlMark { index: 40, line: 3, column: 11 };
+        assert_eq!((4, 12), (mark.line1(), mark.column1()));
+        assert_eq!("line 4, column 12".to_string(), format!("{}", mark));
+    }
+
+    #[test]
+    fn test_error_predicates() {
+        assert!(scanner_error().is_syntax_error());
+        assert!(!scanner_error().is_io_error());
+
+        let error = YamlError::from_io_error(YamlErrorType::YAML_READER_ERROR, io::standard_error(io::BrokenPipe));
+        assert!(error.is_io_error());
+        assert!(!error.is_syntax_error());
+        assert!(!error.is_limit_exceeded());
+        assert_eq!(2, YamlErrorType::YAML_READER_ERROR as uint);
+    }
+
+    #[test]
+    fn test_custom_renderer() {
+        assert_eq!("3번째 줄에서 스캐너 오류".to_string(), scanner_error().render_with(&KoreanRenderer))
+    }
+}
@@ -1,24 +1,29 @@
 use ffi;
 use ffi::{YamlEncoding, YamlSequenceStyle, YamlScalarStyle};
 use ffi::yaml_event_type_t::*;
+use libc;
 use std::mem;
 use std::ptr;
+use std::slice;
+use std::str;
 
 use codecs;
+use error::YamlMark;
+use scalar::YamlScalar;
 
-#[derive(Show, PartialEq, Copy)]
+#[derive(Show, PartialEq, Clone, Copy)]
 pub struct YamlVersionDirective {
     pub major: int,
     pub minor: int,
 }
 
-#[derive(Show, PartialEq)]
+#[derive(Show, PartialEq, Clone)]
 pub struct YamlTagDirective {
     pub handle: String,
     pub prefix: String,
 }
 
-#[derive(Show, PartialEq)]
+#[derive(Show, PartialEq, Clone)]
 pub struct YamlSequenceParam {
     pub anchor: Option<String>,
     pub tag: Option<String>,
@@ -26,7 +31,7 @@ pub struct YamlSequenceParam {
     pub style: YamlSequenceStyle
 }
 
-#[derive(Show, PartialEq)]
+#[derive(Show, PartialEq, Clone)]
 pub struct YamlScalarParam {
     pub anchor: Option<String>,
     pub tag: Option<String>,
@@ -36,7 +41,13 @@ pub struct YamlScalarParam {
     pub style: YamlScalarStyle
 }
 
-#[derive(Show, PartialEq)]
+impl YamlScalarParam {
+    pub fn to_scalar(&self, mark: Option<YamlMark>) -> YamlScalar {
+        YamlScalar::new(self.value.clone(), self.tag.clone(), self.style, mark)
+    }
+}
+
+#[derive(Show, PartialEq, Clone)]
 pub enum YamlEvent {
     YamlNoEvent,
     YamlStreamStartEvent(YamlEncoding),
@@ -131,3 +142,138 @@ impl YamlEvent {
         }
     }
 }
+
+#[derive(Show, PartialEq)]
+pub struct YamlMarkedEvent {
+    pub event: YamlEvent,
+    pub start_mark: YamlMark,
+    pub end_mark: YamlMark
+}
+
+impl YamlMarkedEvent {
+    pub unsafe fn load(event: &ffi::yaml_event_t) -> YamlMarkedEvent {
+        YamlMarkedEvent {
+            event: YamlEvent::load(event),
+            start_mark: YamlMark::conv(&event.start_mark),
+            end_mark: YamlMark::conv(&event.end_mark)
+        }
+    }
+
+    pub fn to_scalar(&self) -> Option<YamlScalar> {
+        match self.event {
+            YamlEvent::YamlScalarEvent(ref param) => Some(param.to_scalar(Some(self.start_mark))),
+            _ => None
+        }
+    }
+}
+
+pub struct YamlEventRef {
+    event_mem: ffi::yaml_event_t
+}
+
+impl Drop for YamlEventRef {
+    fn drop(&mut self) {
+        unsafe {
+            self.event_mem.delete()
+        }
+    }
+}
+
+fn borrow_c_str<'a>(c_str: &'a *const ffi::yaml_char_t) -> Option<&'a str> {
+    if *c_str == ptr::null() {
+        None
+    } else {
+        unsafe {
+            let len = libc::strlen(*c_str as *const libc::c_char) as uint;
+            str::from_utf8(slice::from_raw_buf(c_str, len)).ok()
+        }
+    }
+}
+
+impl YamlEventRef {
+    pub unsafe fn new(event_mem: ffi::yaml_event_t) -> YamlEventRef {
+        YamlEventRef {
+            event_mem: event_mem
+        }
+    }
+
+    pub fn event_type(&self) -> ffi::yaml_event_type_t {
+        self.event_mem.event_type
+    }
+
+    pub fn start_mark(&self) -> YamlMark {
+        YamlMark::conv(&self.event_mem.start_mark)
+    }
+
+    pub fn end_mark(&self) -> YamlMark {
+        YamlMark::conv(&self.event_mem.end_mark)
+    }
+
+    pub fn anchor<'a>(&'a self) -> Option<&'a str> {
+        unsafe {
+            match self.event_mem.event_type {
+                YAML_ALIAS_EVENT => {
+                    let evt_data: &'a ffi::yaml_alias_event_t = mem::transmute(&self.event_mem.data);
+                    borrow_c_str(&evt_data.anchor)
+                },
+                YAML_SCALAR_EVENT => {
+                    let evt_data: &'a ffi::yaml_scalar_event_t = mem::transmute(&self.event_mem.data);
+                    borrow_c_str(&evt_data.anchor)
+                },
+                YAML_SEQUENCE_START_EVENT => {
+                    let evt_data: &'a ffi::yaml_sequence_start_event_t = mem::transmute(&self.event_mem.data);
+                    borrow_c_str(&evt_data.anchor)
+                },
+                YAML_MAPPING_START_EVENT => {
+                    let evt_data: &'a ffi::yaml_mapping_start_event_t = mem::transmute(&self.event_mem.data);
+                    borrow_c_str(&evt_data.anchor)
+                },
+                _ => None
+            }
+        }
+    }
+
+    pub fn tag<'a>(&'a self) -> Option<&'a str> {
+        unsafe {
+            match self.event_mem.event_type {
+                YAML_SCALAR_EVENT => {
+                    let evt_data: &'a ffi::yaml_scalar_event_t = mem::transmute(&self.event_mem.data);
+                    borrow_c_str(&evt_data.tag)
+                },
+                YAML_SEQUENCE_START_EVENT => {
+                    let evt_data: &'a ffi::yaml_sequence_start_event_t = mem::transmute(&self.event_mem.data);
+                    borrow_c_str(&evt_data.tag)
+                },
+                YAML_MAPPING_START_EVENT => {
+                    let evt_data: &'a ffi::yaml_mapping_start_event_t = mem::transmute(&self.event_mem.data);
+                    borrow_c_str(&evt_data.tag)
+                },
+                _ => None
+            }
+        }
+    }
+
+    pub fn value_bytes<'a>(&'a self) -> Option<&'a [u8]> {
+        match self.event_mem.event_type {
+            YAML_SCALAR_EVENT => unsafe {
+                let evt_data: &'a ffi::yaml_scalar_event_t = mem::transmute(&self.event_mem.data);
+                if evt_data.value == ptr::null() {
+                    Some(&[])
+                } else {
+                    Some(slice::from_raw_buf(&evt_data.value, evt_data.length as uint))
+                }
+            },
+            _ => None
+        }
+    }
+
+    pub fn value<'a>(&'a self) -> Option<&'a str> {
+        self.value_bytes().and_then(|bytes| str::from_utf8(bytes).ok())
+    }
+
+    pub fn to_event(&self) -> YamlEvent {
+        unsafe {
+            YamlEvent::load(&self.event_mem)
+        }
+    }
+}
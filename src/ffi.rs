@@ -1,42 +1,46 @@
 pub use type_size::*;
 use libc::{c_char, c_uchar, c_int, c_void, size_t};
-use parser::YamlIoParser;
 use emitter::YamlEmitter;
 
 #[allow(non_camel_case_types)]
 pub type yaml_char_t = c_uchar;
 
 #[allow(non_camel_case_types)]
-pub type yaml_read_handler_t = extern fn(data: *mut YamlIoParser, buffer: *mut u8, size: size_t, size_read: *mut size_t) -> c_int;
+pub type yaml_read_handler_t = extern fn(data: *mut c_void, buffer: *mut u8, size: size_t, size_read: *mut size_t) -> c_int;
 
 #[allow(non_camel_case_types)]
 pub type yaml_write_handler_t = extern fn(data: *mut YamlEmitter, buffer: *const u8, size: size_t) -> c_int;
 
+pub static YAML_DEFAULT_SCALAR_TAG: &'static str = "tag:yaml.org,2002:str";
+pub static YAML_DEFAULT_SEQUENCE_TAG: &'static str = "tag:yaml.org,2002:seq";
+pub static YAML_DEFAULT_MAPPING_TAG: &'static str = "tag:yaml.org,2002:map";
+pub static YAML_BINARY_TAG: &'static str = "tag:yaml.org,2002:binary";
+
 #[repr(C)]
 #[derive(Show, PartialEq, Copy)]
 pub enum YamlErrorType {
     /** No error is produced. */
-    YAML_NO_ERROR,
+    YAML_NO_ERROR = 0,
 
     /** Cannot allocate or reallocate a block of memory. */
-    YAML_MEMORY_ERROR,
+    YAML_MEMORY_ERROR = 1,
 
     /** Cannot read or decode the input stream. */
-    YAML_READER_ERROR,
+    YAML_READER_ERROR = 2,
     /** Cannot scan the input stream. */
-    YAML_SCANNER_ERROR,
+    YAML_SCANNER_ERROR = 3,
     /** Cannot parse the input stream. */
-    YAML_PARSER_ERROR,
+    YAML_PARSER_ERROR = 4,
     /** Cannot compose a YAML document. */
-    YAML_COMPOSER_ERROR,
+    YAML_COMPOSER_ERROR = 5,
 
     /** Cannot write to the output stream. */
-    YAML_WRITER_ERROR,
+    YAML_WRITER_ERROR = 6,
     /** Cannot emit a YAML stream. */
-    YAML_EMITTER_ERROR
+    YAML_EMITTER_ERROR = 7
 }
 
-#[derive(Show, PartialEq, Copy)]
+#[derive(Show, PartialEq, Clone, Copy)]
 #[repr(C)]
 pub enum YamlSequenceStyle {
     /** Let the emitter choose the style. */
@@ -48,7 +52,7 @@ pub enum YamlSequenceStyle {
     YamlFlowSequenceStyle
 }
 
-#[derive(Show, PartialEq, Copy)]
+#[derive(Show, PartialEq, Clone, Copy)]
 #[repr(C)]
 pub enum YamlScalarStyle {
     /** Let the emitter choose the style. */
@@ -68,7 +72,7 @@ pub enum YamlScalarStyle {
     YamlFoldedScalarStyle
 }
 
-#[derive(Show, PartialEq, Copy)]
+#[derive(Show, PartialEq, Clone, Copy)]
 #[repr(C)]
 pub enum YamlEncoding {
     /** Let the parser choose the encoding. */
@@ -454,6 +458,72 @@ pub struct yaml_tag_directive_t {
 
 impl Copy for yaml_tag_directive_t {}
 
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct yaml_token_t {
+    pub token_type: yaml_token_type_t,
+    pub data: yaml_token_data_t,
+    pub start_mark: yaml_mark_t,
+    pub end_mark: yaml_mark_t
+}
+
+impl yaml_token_t {
+    pub unsafe fn delete(&mut self) {
+        yaml_token_delete(self);
+    }
+}
+
+#[derive(Copy)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct yaml_stream_start_token_t {
+    pub encoding: YamlEncoding
+}
+
+#[derive(Copy)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct yaml_version_directive_token_t {
+    pub major: c_int,
+    pub minor: c_int
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct yaml_tag_directive_token_t {
+    pub handle: *const yaml_char_t,
+    pub prefix: *const yaml_char_t
+}
+
+impl Copy for yaml_tag_directive_token_t {}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct yaml_alias_token_t {
+    pub value: *const yaml_char_t
+}
+
+impl Copy for yaml_alias_token_t {}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct yaml_tag_token_t {
+    pub handle: *const yaml_char_t,
+    pub suffix: *const yaml_char_t
+}
+
+impl Copy for yaml_tag_token_t {}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct yaml_scalar_token_t {
+    pub value: *const yaml_char_t,
+    pub length: size_t,
+    pub style: YamlScalarStyle
+}
+
+impl Copy for yaml_scalar_token_t {}
+
 #[link(name = "yaml")]
 extern {
     pub fn yaml_get_version_string() -> *const c_char;
@@ -474,12 +544,18 @@ extern {
         tag: *const yaml_char_t, style: YamlSequenceStyle) -> c_int;
     pub fn yaml_document_add_mapping(document: *mut yaml_document_t,
         tag: *const yaml_char_t, style: YamlSequenceStyle) -> c_int;
+    pub fn yaml_document_append_sequence_item(document: *mut yaml_document_t,
+        sequence: c_int, item: c_int) -> c_int;
+    pub fn yaml_document_append_mapping_pair(document: *mut yaml_document_t,
+        mapping: c_int, key: c_int, value: c_int) -> c_int;
     pub fn yaml_parser_initialize(parser: *mut yaml_parser_t) -> c_int;
     pub fn yaml_parser_set_encoding(parser: *mut yaml_parser_t, encoding: YamlEncoding) -> c_void;
     pub fn yaml_parser_delete(parser: *mut yaml_parser_t) -> c_void;
     pub fn yaml_parser_set_input_string(parser: *mut yaml_parser_t, input: *const yaml_char_t, size: size_t) -> c_void;
     pub fn yaml_parser_set_input(parser: *mut yaml_parser_t, handler: yaml_read_handler_t, data: *const c_void) -> c_void;
     pub fn yaml_parser_parse(parser: *mut yaml_parser_t, event: *mut yaml_event_t) -> c_int;
+    pub fn yaml_parser_scan(parser: *mut yaml_parser_t, token: *mut yaml_token_t) -> c_int;
+    pub fn yaml_token_delete(token: *mut yaml_token_t) -> c_void;
     pub fn yaml_parser_load(parser: *mut yaml_parser_t, document: *mut yaml_document_t) -> c_int;
     pub fn yaml_emitter_initialize(emitter: *mut yaml_emitter_t) -> c_int;
     pub fn yaml_emitter_emit(emitter: *mut yaml_emitter_t, event: *mut yaml_event_t) -> c_int;
@@ -0,0 +1,320 @@
+use ffi;
+use ffi::YamlErrorType;
+use ffi::YamlScalarStyle::YamlPlainScalarStyle;
+use error::{YamlError, YamlErrorCode, YamlErrorContext, YamlMark};
+use document::{YamlDocument, YamlNode, YamlNodeData, YamlScalarData};
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+use parser::YamlResourceLimits;
+
+use std::num::Float;
+use std::str;
+use std::slice;
+use std::io::MemWriter;
+use std::collections::HashSet;
+use regex::Regex;
+
+static JSON_NULL:Regex = regex!(r"^null$");
+static JSON_BOOL:Regex = regex!(r"^(true|false)$");
+static JSON_NUMBER:Regex = regex!(r"^-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][-+]?[0-9]+)?$");
+
+fn json_error(message: String) -> YamlError {
+    YamlError {
+        kind: YamlErrorType::YAML_EMITTER_ERROR,
+        code: YamlErrorCode::JsonUnrepresentable,
+        problem: Some(message),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+fn node_error(message: &str, mark: YamlMark) -> YamlError {
+    let mut error = json_error(message.to_string());
+    error.context = Some(YamlErrorContext {
+        byte_offset: mark.index,
+        problem_mark: mark,
+        context: None,
+        context_mark: mark
+    });
+    error
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(format!("\\u{:04x}", c as u32).as_slice()),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+fn key_string(key: &YamlStandardData) -> Result<String, YamlError> {
+    match *key {
+        YamlString(ref s) => Ok(s.clone()),
+        _ => Err(json_error(format!("mapping key {} is not a string", try!(to_json(key)))))
+    }
+}
+
+fn write_value(value: &YamlStandardData, out: &mut String) -> Result<(), YamlError> {
+    match *value {
+        YamlInteger(i) => out.push_str(i.to_string().as_slice()),
+        YamlFloat(f) if f.is_nan() || f.is_infinite() =>
+            return Err(json_error(format!("{} has no JSON representation", f))),
+        YamlFloat(f) => out.push_str(f.to_string().as_slice()),
+        YamlString(ref s) => write_string(s.as_slice(), out),
+        YamlNull => out.push_str("null"),
+        YamlBool(b) => out.push_str(b.to_string().as_slice()),
+        YamlSequence(ref items) | YamlSet(ref items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                try!(write_value(item, out));
+            }
+            out.push(']');
+        },
+        YamlMapping(ref pairs) | YamlOrderedMap(ref pairs) => try!(write_object(pairs.as_slice(), out)),
+        YamlPairs(ref pairs) => {
+            out.push('[');
+            for (i, pair) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                try!(write_object(slice::ref_slice(pair), out));
+            }
+            out.push(']');
+        }
+    }
+    Ok(())
+}
+
+fn write_object(pairs: &[(YamlStandardData, YamlStandardData)], out: &mut String) -> Result<(), YamlError> {
+    out.push('{');
+    for (i, &(ref key, ref value)) in pairs.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_string(try!(key_string(key)).as_slice(), out);
+        out.push(':');
+        try!(write_value(value, out));
+    }
+    out.push('}');
+    Ok(())
+}
+
+pub fn to_json(value: &YamlStandardData) -> Result<String, YamlError> {
+    let mut out = String::new();
+    try!(write_value(value, &mut out));
+    Ok(out)
+}
+
+fn is_json_string(scalar: &YamlScalarData, value: &str) -> bool {
+    match scalar.explicit_tag() {
+        Some(ref tag) if tag.as_slice() == ffi::YAML_DEFAULT_SCALAR_TAG => return true,
+        _ => ()
+    }
+    scalar.style() != YamlPlainScalarStyle
+        || !(JSON_NULL.is_match(value) || JSON_BOOL.is_match(value) || JSON_NUMBER.is_match(value))
+}
+
+fn write_io(writer: &mut Writer, text: &str) -> Result<(), YamlError> {
+    writer.write_str(text).map_err(|e| YamlError::from_io_error(YamlErrorType::YAML_WRITER_ERROR, e))
+}
+
+// nodes on the current path, nodes written so far, and how many of those
+// have been written again through an alias
+struct NodeState {
+    stack: Vec<int>,
+    seen: HashSet<int>,
+    expansions: uint,
+    limit: Option<uint>
+}
+
+fn visit(state: &mut NodeState, node: &YamlNodeData) -> Result<(), YamlError> {
+    if state.seen.insert(node.index()) {
+        return Ok(());
+    }
+    state.expansions += 1;
+    match state.limit {
+        Some(limit) if state.expansions > limit => {
+            let mut error = node_error("too many alias expansions", node.start_mark());
+            error.code = YamlErrorCode::AliasExpansionLimitExceeded;
+            Err(error)
+        },
+        _ => Ok(())
+    }
+}
+
+fn write_node(node: &YamlNode, writer: &mut Writer, state: &mut NodeState) -> Result<(), YamlError> {
+    match *node {
+        YamlNode::YamlScalarNode(ref scalar) => {
+            try!(visit(state, scalar as &YamlNodeData));
+            let value = scalar.get_value();
+            if is_json_string(scalar, value.as_slice()) {
+                let mut out = String::new();
+                write_string(value.as_slice(), &mut out);
+                write_io(writer, out.as_slice())
+            } else {
+                write_io(writer, value.as_slice())
+            }
+        },
+        YamlNode::YamlSequenceNode(ref sequence) => {
+            if state.stack.contains(&sequence.index()) {
+                return Err(node_error("recursive alias cannot be represented as JSON", sequence.start_mark()));
+            }
+            try!(visit(state, sequence as &YamlNodeData));
+            state.stack.push(sequence.index());
+            try!(write_io(writer, "["));
+            for (i, item) in sequence.values().enumerate() {
+                if i > 0 {
+                    try!(write_io(writer, ","));
+                }
+                try!(write_node(&try!(item), writer, state));
+            }
+            state.stack.pop();
+            write_io(writer, "]")
+        },
+        YamlNode::YamlMappingNode(ref mapping) => {
+            if state.stack.contains(&mapping.index()) {
+                return Err(node_error("recursive alias cannot be represented as JSON", mapping.start_mark()));
+            }
+            try!(visit(state, mapping as &YamlNodeData));
+            state.stack.push(mapping.index());
+            try!(write_io(writer, "{"));
+            for (i, pair) in mapping.pairs().enumerate() {
+                let (key, value) = try!(pair);
+                match key {
+                    YamlNode::YamlScalarNode(ref scalar) if is_json_string(scalar, scalar.get_value().as_slice()) => (),
+                    YamlNode::YamlScalarNode(ref scalar) =>
+                        return Err(node_error("mapping key is not a string", scalar.start_mark())),
+                    YamlNode::YamlSequenceNode(ref sequence) =>
+                        return Err(node_error("mapping key is not a string", sequence.start_mark())),
+                    YamlNode::YamlMappingNode(ref mapping) =>
+                        return Err(node_error("mapping key is not a string", mapping.start_mark()))
+                }
+                if i > 0 {
+                    try!(write_io(writer, ","));
+                }
+                try!(write_node(&key, writer, state));
+                try!(write_io(writer, ":"));
+                try!(write_node(&value, writer, state));
+            }
+            state.stack.pop();
+            write_io(writer, "}")
+        }
+    }
+}
+
+pub fn write_document(doc: &YamlDocument, writer: &mut Writer) -> Result<(), YamlError> {
+    write_document_with(doc, writer, &YamlResourceLimits::new())
+}
+
+// alias_expansions caps how many nodes are written again through an alias
+pub fn write_document_with(doc: &YamlDocument, writer: &mut Writer, limits: &YamlResourceLimits) -> Result<(), YamlError> {
+    if doc.is_empty() {
+        write_io(writer, "null")
+    } else {
+        let mut state = NodeState {
+            stack: Vec::new(),
+            seen: HashSet::new(),
+            expansions: 0,
+            limit: limits.alias_expansions
+        };
+        write_node(&try!(doc.root()), writer, &mut state)
+    }
+}
+
+pub fn document_to_string(doc: &YamlDocument) -> Result<String, YamlError> {
+    document_to_string_with(doc, &YamlResourceLimits::new())
+}
+
+pub fn document_to_string_with(doc: &YamlDocument, limits: &YamlResourceLimits) -> Result<String, YamlError> {
+    let mut writer = MemWriter::new();
+    try!(write_document_with(doc, &mut writer, limits));
+    Ok(str::from_utf8(writer.get_ref()).unwrap().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use json::to_json;
+    use error::YamlErrorCode;
+    use parser::{YamlParser, YamlByteParser, YamlResourceLimits};
+    use constructor::YamlStandardData::*;
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+    use std::f64;
+
+    #[test]
+    fn test_to_json() {
+        let value = YamlMapping(vec![
+            (YamlString("a".to_string()), YamlSequence(vec![YamlInteger(1), YamlNull, YamlBool(true)])),
+            (YamlString("2".to_string()), YamlString("x\"y\n".to_string()))
+        ]);
+
+        assert_eq!(Ok("{\"a\":[1,null,true],\"2\":\"x\\\"y\\n\"}".to_string()), to_json(&value));
+        match to_json(&YamlMapping(vec![(YamlInteger(2), YamlNull)])) {
+            Err(e) => assert_eq!(YamlErrorCode::JsonUnrepresentable, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+
+        let pairs = YamlPairs(vec![(YamlString("a".to_string()), YamlInteger(1)), (YamlString("a".to_string()), YamlSet(vec![]))]);
+        assert_eq!(Ok("[{\"a\":1},{\"a\":[]}]".to_string()), to_json(&pairs));
+    }
+
+    #[test]
+    fn test_non_finite_float() {
+        match to_json(&YamlFloat(f64::NAN)) {
+            Err(e) => assert_eq!(YamlErrorCode::JsonUnrepresentable, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_document_to_json() {
+        let data = "a: &x [1, -2.5e3, true, null, yes, '1', 1.]\nb: *x\n\"c\\td\": ~\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        assert_eq!(Ok("{\"a\":[1,-2.5e3,true,null,\"yes\",\"1\",\"1.\"],\"b\":[1,-2.5e3,true,null,\"yes\",\"1\",\"1.\"],\"c\\td\":\"~\"}".to_string()),
+                   doc.to_json_string());
+
+        let parser = YamlByteParser::init("{!!str 1: !!str 123, b: !!str true}".as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        assert_eq!(Ok("{\"1\":\"123\",\"b\":\"true\"}".to_string()), doc.to_json_string());
+    }
+
+    #[test]
+    fn test_document_to_json_errors() {
+        for data in ["1: a", "[a]: b", "&a [*a]"].iter() {
+            let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+            let doc = parser.load().next().unwrap().unwrap();
+            match doc.to_json_string() {
+                Err(e) => assert_eq!(YamlErrorCode::JsonUnrepresentable, e.code),
+                Ok(_) => panic!("should return an error")
+            }
+        }
+    }
+
+    #[test]
+    fn test_document_to_json_expansion_limit() {
+        let data = "- &a [x, x, x]\n- &b [*a, *a, *a]\n- [*b, *b, *b]\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let mut limits = YamlResourceLimits::new();
+        limits.alias_expansions = Some(5);
+
+        assert!(doc.to_json_string().is_ok());
+        match doc.to_json_string_with(&limits) {
+            Err(e) => assert_eq!(YamlErrorCode::AliasExpansionLimitExceeded, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+}
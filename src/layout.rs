@@ -0,0 +1,135 @@
+use ffi;
+use error::YamlError;
+use event::YamlEvent::*;
+use parser::{YamlParser, YamlByteParser};
+
+#[derive(Show, PartialEq)]
+pub struct YamlLayout {
+    pub blank_lines: Vec<(String, uint)>
+}
+
+fn blank_lines_above(lines: &[&str], line: uint) -> uint {
+    let mut count = 0;
+    while count < line && lines[line - count - 1].trim().is_empty() {
+        count += 1;
+    }
+    count
+}
+
+fn line_key(line: &str) -> Option<&str> {
+    if line.is_empty() || line.starts_with(" ") || line.starts_with("-") || line.starts_with("#")
+        || line.starts_with("...") {
+        return None;
+    }
+
+    match line.find_str(": ") {
+        Some(pos) => Some(line.slice_to(pos)),
+        None if line.ends_with(":") => Some(line.slice_to(line.len() - 1)),
+        None => None
+    }
+}
+
+impl YamlLayout {
+    pub fn record(source: &str) -> Result<YamlLayout, YamlError> {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut parser = try!(YamlByteParser::new(source.as_bytes(), ffi::YamlEncoding::YamlUtf8Encoding));
+        let mut blank_lines = Vec::new();
+        let mut depth = 0u;
+        let mut in_root_mapping = false;
+        let mut position = 0u;
+
+        loop {
+            let (evt, mark, _) = unsafe {
+                match parser.parse_marked_event() {
+                    Some(evt) => evt,
+                    None => return Err(parser.get_error())
+                }
+            };
+            let at_entry = depth == 1 && in_root_mapping;
+
+            match evt {
+                YamlNoEvent | YamlStreamEndEvent | YamlDocumentEndEvent(_) => break,
+                YamlMappingStartEvent(_) => {
+                    if depth == 0 {
+                        in_root_mapping = true;
+                    } else if at_entry {
+                        position += 1;
+                    }
+                    depth += 1;
+                },
+                YamlSequenceStartEvent(_) => {
+                    if at_entry {
+                        position += 1;
+                    }
+                    depth += 1;
+                },
+                YamlMappingEndEvent | YamlSequenceEndEvent => depth -= 1,
+                YamlScalarEvent(param) => if at_entry {
+                    if position % 2 == 0 {
+                        let count = blank_lines_above(lines.as_slice(), mark.line);
+                        if count > 0 {
+                            blank_lines.push((param.value, count));
+                        }
+                    }
+                    position += 1;
+                },
+                YamlAliasEvent(_) => if at_entry {
+                    position += 1;
+                },
+                _ => ()
+            }
+        }
+
+        Ok(YamlLayout {
+            blank_lines: blank_lines
+        })
+    }
+
+    pub fn blank_lines_before(&self, key: &str) -> uint {
+        self.blank_lines.iter().find(|&&(ref k, _)| k.as_slice() == key).map_or(0, |&(_, count)| count)
+    }
+
+    pub fn apply(&self, emitted: &str) -> String {
+        let mut out = String::new();
+        for (i, line) in emitted.lines().enumerate() {
+            if i > 0 {
+                match line_key(line) {
+                    Some(key) => for _ in range(0, self.blank_lines_before(key)) {
+                        out.push('\n');
+                    },
+                    None => ()
+                }
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use layout::YamlLayout;
+
+    #[test]
+    fn test_record_layout() {
+        let source = "a: 1\n\nb:\n  c: 2\n\n  d: 3\n\n\ne: [x,\n\n  y]\n";
+        let layout = YamlLayout::record(source).unwrap();
+
+        assert_eq!(vec![("b".to_string(), 1u), ("e".to_string(), 2u)], layout.blank_lines);
+        assert_eq!(0, layout.blank_lines_before("c"));
+    }
+
+    #[test]
+    fn test_apply_layout() {
+        let source = "a: 1\n\nb:\n  c: 2\n\n\nd: 3\n";
+        let layout = YamlLayout::record(source).unwrap();
+
+        assert_eq!(source, layout.apply("a: 1\nb:\n  c: 2\nd: 3\n").as_slice());
+    }
+
+    #[test]
+    fn test_record_parse_error() {
+        assert!(YamlLayout::record("a: [1").is_err());
+    }
+}
@@ -5,12 +5,16 @@
 #![feature(globs)]
 #![feature(phase)]
 #![feature(associated_types)]
+#![feature(macro_rules)]
 
 extern crate libc;
+extern crate time;
 
 #[phase(plugin)]
 extern crate regex_macros;
 extern crate regex;
+#[cfg(feature = "rustc-serialize")]
+extern crate "rustc-serialize" as rustc_serialize;
 
 use parser::YamlParser;
 use constructor::{YamlStandardData, YamlStandardConstructor, YamlConstructor};
@@ -18,15 +22,39 @@ use error::YamlError;
 
 pub mod ffi;
 pub mod error;
+pub mod diagnostic;
 pub mod event;
+pub mod tokens;
+pub mod comments;
 pub mod parser;
 pub mod emitter;
+pub mod adapters;
 pub mod document;
 pub mod codecs;
+pub mod scalar;
 pub mod constructor;
+pub mod schema;
+pub mod oracle;
+pub mod visitor;
+pub mod diff;
+pub mod layout;
+pub mod json;
+pub mod typed;
+pub mod convert;
+pub mod push;
+#[cfg(unix)]
+pub mod mmap;
+#[cfg(feature = "async")]
+pub mod async;
+#[cfg(feature = "rustc-serialize")]
+pub mod serialize;
+pub mod compare;
 
+mod path;
 mod type_size;
 
+pub type Result<T> = std::result::Result<T, YamlError>;
+
 pub fn version_string() -> String {
     let c_vsn = unsafe {
         std::c_str::CString::new(ffi::yaml_get_version_string(), false)
@@ -51,30 +79,233 @@ pub fn version() -> (int, int, int) {
     (c_major as int, c_minor as int, c_patch as int)
 }
 
-pub fn parse_bytes_utf8(bytes: &[u8]) -> Result<Vec<YamlStandardData>, YamlError> {
+#[derive(Show, PartialEq)]
+pub struct YamlCapabilities {
+    pub libyaml_version: (int, int, int),
+    pub libyaml_version_string: String,
+    pub async_reader: bool,
+    pub serde: bool,
+    pub vendored_libyaml: bool,
+    pub pure_rust_backend: bool,
+    pub comments: bool,
+    pub default_limits: parser::YamlResourceLimits,
+    pub default_schema: schema::YamlSchema,
+    pub default_strictness: parser::YamlStrictness,
+}
+
+pub fn capabilities() -> YamlCapabilities {
+    YamlCapabilities {
+        libyaml_version: version(),
+        libyaml_version_string: version_string(),
+        async_reader: cfg!(feature = "async"),
+        serde: cfg!(feature = "rustc-serialize"),
+        vendored_libyaml: false,
+        pure_rust_backend: false,
+        comments: true,
+        default_limits: parser::YamlResourceLimits::new(),
+        default_schema: schema::YamlSchema::Yaml11,
+        default_strictness: parser::YamlStrictness::WarnYaml11,
+    }
+}
+
+pub fn load_str(s: &str) -> Result<Vec<Box<document::YamlDocument>>> {
+    load_bytes(s.as_bytes(), ffi::YamlEncoding::YamlUtf8Encoding)
+}
+
+pub fn load_bytes(bytes: &[u8], encoding: ffi::YamlEncoding) -> Result<Vec<Box<document::YamlDocument>>> {
+    load_bytes_with(bytes, encoding, &parser::YamlResourceLimits::new())
+}
+
+pub fn load_bytes_with(bytes: &[u8], encoding: ffi::YamlEncoding,
+    limits: &parser::YamlResourceLimits) -> Result<Vec<Box<document::YamlDocument>>>
+{
+    let mut parser = try!(parser::YamlByteParser::new(bytes, encoding));
+    parser.set_limits(*limits);
+    parser.documents().collect()
+}
+
+fn is_delimiter_line(line: &[u8], delimiter: &str) -> bool {
+    let mut end = line.len();
+    while end > 0 && (line[end - 1] == b'\r' || line[end - 1] == b' ' || line[end - 1] == b'\t') {
+        end -= 1;
+    }
+    line.slice_to(end) == delimiter.as_bytes()
+}
+
+pub fn split_front_matter(input: &[u8]) -> Result<Option<(Box<document::YamlDocument>, &[u8])>> {
+    let mut pos = 0u;
+    while pos < input.len() {
+        let end = match input.slice_from(pos).position_elem(&b'\n') {
+            Some(i) => pos + i,
+            None => input.len()
+        };
+        let line = input.slice(pos, end);
+        let next = std::cmp::min(end + 1, input.len());
+
+        if pos == 0 {
+            if !is_delimiter_line(line, "---") {
+                return Ok(None);
+            }
+        } else if is_delimiter_line(line, "---") || is_delimiter_line(line, "...") {
+            let mut parser = try!(parser::YamlByteParser::new(input.slice_to(pos), ffi::YamlEncoding::YamlUtf8Encoding));
+            return match parser.documents().next() {
+                Some(Ok(doc)) => Ok(Some((doc, input.slice_from(next)))),
+                Some(Err(e)) => Err(e),
+                None => Ok(None)
+            };
+        }
+        pos = next;
+    }
+    Ok(None)
+}
+
+pub fn document_spans(input: &[u8]) -> Result<Vec<(uint, uint)>> {
+    let mut parser = try!(parser::YamlByteParser::new(input, ffi::YamlEncoding::YamlUtf8Encoding));
+    let mut spans = Vec::new();
+    let mut start = 0u;
+
+    for res in parser.marked_events() {
+        let marked = try!(res);
+        match marked.event {
+            event::YamlEvent::YamlDocumentStartEvent(..) => start = marked.start_mark.index,
+            event::YamlEvent::YamlDocumentEndEvent(_) => spans.push((start, marked.end_mark.index)),
+            _ => ()
+        }
+    }
+
+    let bom = if input.starts_with(&[0xEF, 0xBB, 0xBF]) { 3 } else { 0 };
+    let text = try!(std::str::from_utf8(input.slice_from(bom)));
+    Ok(spans.iter().map(|&(start, end)| {
+        (bom + codecs::char_to_byte_offset(text, start).unwrap_or(text.len()),
+         bom + codecs::char_to_byte_offset(text, end).unwrap_or(text.len()))
+    }).collect())
+}
+
+pub fn parse_bytes_utf8(bytes: &[u8]) -> Result<Vec<YamlStandardData>> {
     parse_bytes(bytes, ffi::YamlEncoding::YamlUtf8Encoding)
 }
 
-pub fn parse_bytes(bytes: &[u8], encoding: ffi::YamlEncoding) -> Result<Vec<YamlStandardData>, YamlError> {
-    let parser = parser::YamlByteParser::init(bytes, encoding);
-    let ctor = YamlStandardConstructor::new();
+pub fn parse_bytes(bytes: &[u8], encoding: ffi::YamlEncoding) -> Result<Vec<YamlStandardData>> {
+    parse_bytes_with(bytes, encoding, &parser::YamlResourceLimits::new())
+}
+
+pub fn parse_bytes_with(bytes: &[u8], encoding: ffi::YamlEncoding,
+    limits: &parser::YamlResourceLimits) -> Result<Vec<YamlStandardData>>
+{
+    let mut parser = try!(parser::YamlByteParser::init(bytes, encoding));
+    parser.set_limits(*limits);
+    let ctor = YamlStandardConstructor::with_limits(limits);
 
-    parser.load().map(|doc_res|
-        doc_res.and_then(|doc| ctor.construct(doc.root().unwrap()))
-    ).collect()
+    parser.load().map(|doc_res| doc_res.and_then(|doc| {
+        try!(doc.check_acyclic());
+        doc.root().and_then(|root| ctor.construct(root))
+    })).collect()
 }
 
-pub fn parse_io_utf8(reader: &mut Reader) -> Result<Vec<YamlStandardData>, YamlError> {
+pub fn parse_io_utf8(reader: &mut Reader) -> Result<Vec<YamlStandardData>> {
     parse_io(reader, ffi::YamlEncoding::YamlUtf8Encoding)
 }
 
-pub fn parse_io(reader: &mut Reader, encoding: ffi::YamlEncoding) -> Result<Vec<YamlStandardData>, YamlError> {
-    let parser = parser::YamlIoParser::init(reader, encoding);
-    let ctor = YamlStandardConstructor::new();
+pub fn parse_io(reader: &mut Reader, encoding: ffi::YamlEncoding) -> Result<Vec<YamlStandardData>> {
+    parse_io_with(reader, encoding, &parser::YamlResourceLimits::new())
+}
+
+pub fn parse_io_with(reader: &mut Reader, encoding: ffi::YamlEncoding,
+    limits: &parser::YamlResourceLimits) -> Result<Vec<YamlStandardData>>
+{
+    let mut parser = try!(parser::YamlIoParser::init(reader, encoding));
+    parser.set_limits(*limits);
+    let ctor = YamlStandardConstructor::with_limits(limits);
+
+    parser.load().map(|doc_res| doc_res.and_then(|doc| {
+        try!(doc.check_acyclic());
+        doc.root().and_then(|root| ctor.construct(root))
+    })).collect()
+}
+
+pub fn parse_file(path: &Path) -> Result<Vec<YamlStandardData>> {
+    parse_file_with(path, &parser::YamlResourceLimits::new())
+}
+
+pub fn parse_file_with(path: &Path, limits: &parser::YamlResourceLimits) -> Result<Vec<YamlStandardData>> {
+    let mut parser = try!(parser::YamlIoParser::open(path, ffi::YamlEncoding::YamlAnyEncoding));
+    parser.set_limits(*limits);
+    let filename = path.display().to_string();
+    let ctor = YamlStandardConstructor::with_limits(limits);
+
+    parser.documents().map(|doc_res| doc_res.and_then(|doc| {
+        try!(doc.check_acyclic());
+        doc.root().and_then(|root| ctor.construct(root))
+    }).map_err(|e| match e.filename {
+        Some(_) => e,
+        None => e.with_filename(filename.as_slice())
+    })).collect()
+}
 
-    parser.load().map(|doc_res|
-        doc_res.and_then(|doc| ctor.construct(doc.root().unwrap()))
-    ).collect()
+#[derive(Show, PartialEq, Copy)]
+pub struct SafeLoadOptions {
+    pub limits: parser::YamlResourceLimits
+}
+
+impl SafeLoadOptions {
+    pub fn new() -> SafeLoadOptions {
+        SafeLoadOptions {
+            limits: parser::YamlResourceLimits {
+                input_bytes: None,
+                scalar_bytes: Some(1 << 20),
+                document_events: None,
+                document_nodes: Some(100000),
+                depth: Some(128),
+                alias_expansions: Some(1000000),
+                stalled_reads: None,
+                stall_timeout: None,
+                output_bytes: None
+            }
+        }
+    }
+
+    pub fn builder(&self) -> parser::YamlParserBuilder {
+        parser::YamlParserBuilder::new()
+            .duplicate_keys(parser::YamlDuplicateKeys::RejectDuplicateKeys)
+            .tags(parser::YamlTags::CoreTagsOnly)
+            .limits(self.limits)
+    }
+}
+
+pub fn safe_load_str(s: &str) -> Result<Vec<YamlStandardData>> {
+    safe_load_bytes(s.as_bytes(), &SafeLoadOptions::new())
+}
+
+pub fn safe_load_bytes(bytes: &[u8], options: &SafeLoadOptions) -> Result<Vec<YamlStandardData>> {
+    let mut parser = try!(options.builder().encoding(ffi::YamlEncoding::YamlUtf8Encoding).bytes(bytes));
+    let ctor = YamlStandardConstructor::with_limits(&options.limits);
+
+    parser.documents().map(|doc_res| doc_res.and_then(|doc| {
+        try!(doc.check_acyclic());
+        doc.root().and_then(|root| ctor.construct(root))
+    })).collect()
+}
+
+pub fn convert_file(in_path: &Path, out_path: &Path, format: convert::Format,
+    options: &convert::Options) -> std::result::Result<(), convert::YamlConvertError>
+{
+    convert::convert_file(in_path, out_path, format, options)
+}
+
+pub fn make_overlay(base: &YamlStandardData, desired: &YamlStandardData) -> diff::YamlOverlay {
+    diff::make_overlay(base, desired)
+}
+
+pub fn apply_overlay(base: &YamlStandardData, overlay: &diff::YamlOverlay) -> YamlStandardData {
+    diff::apply_overlay(base, overlay)
+}
+
+pub fn deep_merge(base: &YamlStandardData, overlay: &YamlStandardData) -> YamlStandardData {
+    diff::deep_merge(base, overlay)
+}
+
+pub fn stream_equal(left: &mut Reader, right: &mut Reader) -> Result<Option<compare::YamlStreamDivergence>> {
+    compare::stream_equal(left, right)
 }
 
 #[cfg(test)]
@@ -82,6 +313,9 @@ mod test {
     use std::mem;
     use std::io;
     use constructor::YamlStandardData::*;
+    use error::YamlErrorCode;
+    use parser::{YamlParser, YamlParserBuilder};
+    use schema::YamlResolver;
 
     #[test]
     fn test_version_string() {
@@ -95,6 +329,22 @@ mod test {
         assert!((0, 1, 4) <= vsn && vsn < (0, 2, 0))
     }
 
+    #[test]
+    fn test_capabilities() {
+        let caps = super::capabilities();
+        assert_eq!(super::version(), caps.libyaml_version);
+        assert_eq!(super::version_string(), caps.libyaml_version_string);
+        assert_eq!(cfg!(feature = "async"), caps.async_reader);
+        assert_eq!(cfg!(feature = "rustc-serialize"), caps.serde);
+        assert!(!caps.pure_rust_backend);
+
+        // the defaults reported match what a freshly built parser uses
+        let mut parser = YamlParserBuilder::new().bytes("a".as_bytes()).unwrap();
+        assert_eq!(caps.default_limits, parser.limits());
+        assert_eq!(caps.default_strictness, parser.strictness());
+        assert_eq!(YamlResolver::new(caps.default_schema), parser.resolver());
+    }
+
     #[test]
     fn test_event_size() {
         assert_eq!(super::type_size::YAML_EVENT_T_SIZE, mem::size_of::<super::ffi::yaml_event_t>())
@@ -120,6 +370,11 @@ mod test {
         assert_eq!(super::type_size::YAML_NODE_T_SIZE, mem::size_of::<super::ffi::yaml_node_t>())
     }
 
+    #[test]
+    fn test_token_size() {
+        assert_eq!(super::type_size::YAML_TOKEN_T_SIZE, mem::size_of::<super::ffi::yaml_token_t>())
+    }
+
     #[test]
     fn test_parse_bytes() {
         let data = "[1, 2, 3]";
@@ -132,4 +387,103 @@ mod test {
         let mut reader = io::BufReader::new(data.as_bytes());
         assert_eq!(Ok(vec![YamlSequence(vec![YamlInteger(1), YamlInteger(2), YamlInteger(3)])]), super::parse_io_utf8(&mut reader))
     }
+
+    #[test]
+    fn test_load_str() {
+        let docs = super::load_str("a\n--- [b]\n").unwrap();
+        assert_eq!(2, docs.len());
+        assert!(docs[1].root().is_ok());
+
+        assert!(super::load_str("[a").is_err());
+        assert_eq!(0, super::load_str("").unwrap().len());
+    }
+
+    #[test]
+    fn test_split_front_matter() {
+        let data = "---\ntitle: Hello\ntags: [a, b]\n---\n# Body\n\ntext: here\n";
+        let (doc, body) = super::split_front_matter(data.as_bytes()).unwrap().unwrap();
+
+        assert_eq!("# Body\n\ntext: here\n".as_bytes(), body);
+        assert!(doc.get_node(1).is_some());
+
+        let (_, body) = super::split_front_matter("---\r\na: 1\r\n...\r\nbody".as_bytes()).unwrap().unwrap();
+        assert_eq!("body".as_bytes(), body);
+
+        assert!(super::split_front_matter("# No front matter\n---\n".as_bytes()).unwrap().is_none());
+        assert!(super::split_front_matter("---\na: 1\n".as_bytes()).unwrap().is_none());
+        assert!(super::split_front_matter("---\na: [1\n---\nbody".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_document_spans() {
+        let data = "a: 1\n--- [b]\n...\nc\n";
+        assert_eq!(Ok(vec![(0u, 5u), (5, 16), (17, 19)]), super::document_spans(data.as_bytes()));
+
+        let data = "\u00e9: 1\n--- b\n";
+        assert_eq!(Ok(vec![(0u, 6u), (6, 12)]), super::document_spans(data.as_bytes()));
+
+        assert!(super::document_spans("[a".as_bytes()).is_err());
+        assert!(super::document_spans(&[b'a', b':', b' ', 0xff]).is_err());
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let dir = io::TempDir::new("yaml-parse").unwrap();
+        let good = dir.path().join("good.yaml");
+        let bad = dir.path().join("bad.yaml");
+        io::File::create(&good).write_str("[1, 2]").unwrap();
+        io::File::create(&bad).write_str("a: 1\nb: [1\n").unwrap();
+
+        assert_eq!(Ok(vec![YamlSequence(vec![YamlInteger(1), YamlInteger(2)])]), super::parse_file(&good));
+        match super::parse_file(&bad) {
+            Err(e) => {
+                assert_eq!(Some(bad.display().to_string()), e.filename);
+                assert!(e.message().as_slice().starts_with(bad.display().to_string().as_slice()));
+            },
+            Ok(_) => panic!("should return an error")
+        }
+        match super::parse_file(&dir.path().join("missing.yaml")) {
+            Err(e) => assert!(e.filename.is_some()),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_parse_recursive_alias() {
+        match super::parse_bytes_utf8("&a [1, *a]".as_bytes()) {
+            Err(e) => assert_eq!(YamlErrorCode::RecursiveAlias, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_parse_bytes_with() {
+        let mut limits = super::parser::YamlResourceLimits::new();
+        limits.depth = Some(2);
+        match super::parse_bytes_with("[[[1]]]".as_bytes(), super::ffi::YamlEncoding::YamlUtf8Encoding, &limits) {
+            Err(e) => assert_eq!(YamlErrorCode::DepthLimitExceeded, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+        assert!(super::load_bytes_with("[[1]]".as_bytes(), super::ffi::YamlEncoding::YamlUtf8Encoding, &limits).is_ok());
+    }
+
+    #[test]
+    fn test_safe_load_str() {
+        assert_eq!(Ok(vec![YamlMapping(vec![(YamlString("a".to_string()), YamlSequence(vec![YamlInteger(1), YamlInteger(1)]))])]),
+                   super::safe_load_str("a: [&x 1, *x]"));
+
+        let laughs = "- &a [x, x, x, x, x, x, x, x, x, x]\n- &b [*a, *a, *a, *a, *a, *a, *a, *a, *a, *a]\n\
+                      - &c [*b, *b, *b, *b, *b, *b, *b, *b, *b, *b]\n- &d [*c, *c, *c, *c, *c, *c, *c, *c, *c, *c]\n\
+                      - &e [*d, *d, *d, *d, *d, *d, *d, *d, *d, *d]\n- &f [*e, *e, *e, *e, *e, *e, *e, *e, *e, *e]\n";
+        let cases = [(laughs.to_string(), YamlErrorCode::NodeLimitExceeded),
+                     ("{a: 1, a: 2}".to_string(), YamlErrorCode::DuplicateKey),
+                     ("!foo bar".to_string(), YamlErrorCode::TagRejected),
+                     (String::from_char(200, '['), YamlErrorCode::DepthLimitExceeded)];
+        for &(ref data, code) in cases.iter() {
+            match super::safe_load_str(data.as_slice()) {
+                Err(e) => assert_eq!(code, e.code),
+                Ok(_) => panic!("should return an error")
+            }
+        }
+    }
 }
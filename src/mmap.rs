@@ -0,0 +1,125 @@
+use libc;
+use ffi::YamlErrorType;
+use ffi;
+use error::{YamlError, YamlErrorCode};
+use parser::{YamlParser, YamlByteParser};
+
+use std::os;
+use std::os::{MemoryMap, MapReadable, MapFd};
+use std::mem;
+use std::slice;
+use std::ptr;
+use std::c_str::ToCStr;
+
+pub struct YamlMappedFile {
+    map: Option<MemoryMap>,
+    data: *const u8,
+    len: uint,
+    filename: String
+}
+
+fn map_error(filename: &str, problem: String) -> YamlError {
+    YamlError {
+        kind: YamlErrorType::YAML_READER_ERROR,
+        code: YamlErrorCode::ReaderError,
+        problem: Some(problem),
+        io_error: None,
+        context: None,
+        filename: Some(filename.to_string())
+    }
+}
+
+impl YamlMappedFile {
+    pub fn open(path: &Path) -> Result<YamlMappedFile, YamlError> {
+        let filename = path.display().to_string();
+
+        // size the mapping from the descriptor itself, so a file replaced
+        // between the lookup and the open can't mismatch the length
+        let fd = path.with_c_str(|p| unsafe { libc::open(p, libc::O_RDONLY, 0) });
+        if fd < 0 {
+            return Err(map_error(filename.as_slice(), format!("could not open file: {}", os::last_os_error())));
+        }
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+            let problem = format!("could not stat file: {}", os::last_os_error());
+            unsafe { libc::close(fd); }
+            return Err(map_error(filename.as_slice(), problem));
+        }
+        let len = stat.st_size as uint;
+
+        // mmap refuses empty mappings, so an empty file is served from an empty slice
+        if len == 0 {
+            unsafe { libc::close(fd); }
+            return Ok(YamlMappedFile { map: None, data: ptr::null(), len: 0, filename: filename });
+        }
+
+        let res = MemoryMap::new(len, &[MapReadable, MapFd(fd)]);
+        unsafe { libc::close(fd); }
+
+        match res {
+            Ok(map) => {
+                let data = map.data() as *const u8;
+                Ok(YamlMappedFile { map: Some(map), data: data, len: len, filename: filename })
+            },
+            Err(e) => Err(map_error(filename.as_slice(), format!("could not map file: {}", e)))
+        }
+    }
+
+    pub fn as_bytes<'a>(&'a self) -> &'a [u8] {
+        match self.map {
+            Some(_) => unsafe { slice::from_raw_buf(&self.data, self.len) },
+            None => &[]
+        }
+    }
+
+    pub fn parser<'a>(&'a self, encoding: ffi::YamlEncoding) -> Result<YamlByteParser<'a>, YamlError> {
+        let mut parser = try!(YamlByteParser::new(self.as_bytes(), encoding));
+        parser.set_filename(Some(self.filename.clone()));
+        Ok(parser)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mmap::YamlMappedFile;
+    use parser::YamlParser;
+    use document::YamlDocument;
+    use error::YamlError;
+    use ffi::YamlEncoding::YamlAnyEncoding;
+    use std::io::{File, TempDir};
+
+    #[test]
+    fn test_mapped_file() {
+        let dir = TempDir::new("yaml-mmap").unwrap();
+        let path = dir.path().join("in.yaml");
+        File::create(&path).write_str("a: [1, 2]\n--- b\n").unwrap();
+
+        let mapped = YamlMappedFile::open(&path).unwrap();
+        assert_eq!("a: [1, 2]\n--- b\n".as_bytes(), mapped.as_bytes());
+
+        let mut parser = mapped.parser(YamlAnyEncoding).unwrap();
+        let docs: Result<Vec<Box<YamlDocument>>, YamlError> = parser.documents().collect();
+        assert_eq!(2, docs.unwrap().len());
+    }
+
+    #[test]
+    fn test_mapped_file_errors() {
+        let dir = TempDir::new("yaml-mmap").unwrap();
+        let empty = dir.path().join("empty.yaml");
+        let bad = dir.path().join("bad.yaml");
+        File::create(&empty).write_str("").unwrap();
+        File::create(&bad).write_str("[1").unwrap();
+
+        let mapped = YamlMappedFile::open(&empty).unwrap();
+        assert_eq!(0, mapped.as_bytes().len());
+        assert!(mapped.parser(YamlAnyEncoding).unwrap().documents().next().is_none());
+
+        let mapped = YamlMappedFile::open(&bad).unwrap();
+        match mapped.parser(YamlAnyEncoding).unwrap().documents().next() {
+            Some(Err(e)) => assert_eq!(Some(bad.display().to_string()), e.filename),
+            _ => panic!("should return an error")
+        }
+
+        assert!(YamlMappedFile::open(&dir.path().join("missing.yaml")).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use ffi;
+use error::YamlError;
+use emitter::YamlEmitter;
+use parser::{YamlParser, YamlByteParser};
+use document::YamlDocument;
+use event::{YamlEvent, YamlScalarParam};
+use event::YamlEvent::*;
+use constructor::{YamlStandardData, YamlStandardConstructor, YamlConstructor};
+use constructor::YamlStandardData::*;
+use path::{child_path, key_segment};
+
+use std::io::MemWriter;
+use std::num::Float;
+
+#[derive(Show, PartialEq)]
+pub enum YamlDiscrepancyKind {
+    EmitError(YamlError),
+    ReparseError(YamlError),
+    DocumentCountMismatch(uint, uint),
+    LengthMismatch(uint, uint),
+    ValueMismatch(YamlStandardData, YamlStandardData),
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlDiscrepancy {
+    pub document: uint,
+    pub path: String,
+    pub kind: YamlDiscrepancyKind
+}
+
+fn discrepancy(document: uint, path: &str, kind: YamlDiscrepancyKind) -> YamlDiscrepancy {
+    YamlDiscrepancy {
+        document: document,
+        path: path.to_string(),
+        kind: kind
+    }
+}
+
+fn construct_all(docs: &[Box<YamlDocument>]) -> Result<Vec<YamlStandardData>, YamlError> {
+    let ctor = YamlStandardConstructor::new();
+    docs.iter().map(|doc| {
+        if doc.is_empty() {
+            Ok(YamlNull)
+        } else {
+            try!(doc.check_acyclic());
+            doc.root().and_then(|root| ctor.construct(root))
+        }
+    }).collect()
+}
+
+fn emit_all(docs: &[Box<YamlDocument>]) -> Result<Vec<u8>, YamlError> {
+    let mut writer = MemWriter::new();
+    {
+        let mut emitter = YamlEmitter::init(&mut writer);
+        try!(emitter.emit_stream(ffi::YamlEncoding::YamlUtf8Encoding, |e| {
+            for doc in docs.iter() {
+                // `events` keeps directives and shared nodes as anchors and
+                // aliases, but wraps each document in its own stream
+                let events = try!(doc.events());
+                for event in events.slice(1, events.len() - 1).iter() {
+                    match *event {
+                        // an empty document constructs as null, so emit one
+                        YamlDocumentEndEvent(_) if doc.is_empty() => try!(e.emit_event(&null_event())),
+                        _ => ()
+                    }
+                    try!(e.emit_event(event));
+                }
+            }
+            Ok(())
+        }));
+    }
+    Ok(writer.get_ref().to_vec())
+}
+
+fn null_event() -> YamlEvent {
+    YamlScalarEvent(YamlScalarParam {
+        anchor: None,
+        tag: None,
+        value: "~".to_string(),
+        plain_implicit: true,
+        quoted_implicit: false,
+        style: ffi::YamlScalarStyle::YamlPlainScalarStyle
+    })
+}
+
+pub fn compare_data(document: uint, path: &str,
+    left: &YamlStandardData, right: &YamlStandardData,
+    out: &mut Vec<YamlDiscrepancy>)
+{
+    match (left, right) {
+        (&YamlSequence(ref l), &YamlSequence(ref r)) => {
+            if l.len() != r.len() {
+                out.push(discrepancy(document, path, YamlDiscrepancyKind::LengthMismatch(l.len(), r.len())));
+                return;
+            }
+            for (i, (a, b)) in l.iter().zip(r.iter()).enumerate() {
+                compare_data(document, format!("{}[{}]", path, i).as_slice(), a, b, out);
+            }
+        },
+        (&YamlMapping(ref l), &YamlMapping(ref r)) => {
+            if l.len() != r.len() {
+                out.push(discrepancy(document, path, YamlDiscrepancyKind::LengthMismatch(l.len(), r.len())));
+                return;
+            }
+            for (i, (&(ref lk, ref lv), &(ref rk, ref rv))) in l.iter().zip(r.iter()).enumerate() {
+                let segment = child_path(path, key_segment(lk, i).as_slice());
+                compare_data(document, format!("{}<key>", segment).as_slice(), lk, rk, out);
+                compare_data(document, segment.as_slice(), lv, rv, out);
+            }
+        },
+        (&YamlFloat(a), &YamlFloat(b)) if a.is_nan() && b.is_nan() => (),
+        _ => if left != right {
+            out.push(discrepancy(document, path,
+                YamlDiscrepancyKind::ValueMismatch(left.clone(), right.clone())));
+        }
+    }
+}
+
+pub fn check_round_trip(bytes: &[u8], encoding: ffi::YamlEncoding) -> Result<Vec<YamlDiscrepancy>, YamlError> {
+    let parser = try!(YamlByteParser::init(bytes, encoding));
+    let docs: Vec<Box<YamlDocument>> = try!(parser.load().collect());
+    let original = try!(construct_all(docs.as_slice()));
+
+    let emitted = match emit_all(docs.as_slice()) {
+        Ok(emitted) => emitted,
+        Err(e) => return Ok(vec![discrepancy(0, "", YamlDiscrepancyKind::EmitError(e))])
+    };
+
+    let reparsed = match YamlByteParser::init(emitted.as_slice(), ffi::YamlEncoding::YamlUtf8Encoding)
+                                .and_then(|reparser| reparser.load().collect::<Result<Vec<Box<YamlDocument>>, YamlError>>())
+                                .and_then(|docs| construct_all(docs.as_slice())) {
+        Ok(reparsed) => reparsed,
+        Err(e) => return Ok(vec![discrepancy(0, "", YamlDiscrepancyKind::ReparseError(e))])
+    };
+
+    let mut out = Vec::new();
+    if original.len() != reparsed.len() {
+        out.push(discrepancy(0, "", YamlDiscrepancyKind::DocumentCountMismatch(original.len(), reparsed.len())));
+        return Ok(out);
+    }
+
+    for (i, (left, right)) in original.iter().zip(reparsed.iter()).enumerate() {
+        compare_data(i, "", left, right, &mut out);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use oracle::{check_round_trip, compare_data, construct_all, emit_all, YamlDiscrepancy};
+    use parser::{YamlParser, YamlByteParser};
+    use document::YamlDocument;
+    use oracle::YamlDiscrepancyKind::*;
+    use constructor::YamlStandardData::*;
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+
+    #[test]
+    fn test_round_trip() {
+        let data = "---\n[1, 'two', {a: [.nan, ~]}]\n--- !foo\nb: \"c\\nd\"\n";
+        assert_eq!(Ok(vec![]), check_round_trip(data.as_bytes(), YamlUtf8Encoding))
+    }
+
+    #[test]
+    fn test_round_trip_explicit_str() {
+        let data = "[!!str 12, !!str true, !!str ~]";
+        assert_eq!(Ok(vec![]), check_round_trip(data.as_bytes(), YamlUtf8Encoding))
+    }
+
+    #[test]
+    fn test_round_trip_parse_error() {
+        assert!(check_round_trip("[1, 2".as_bytes(), YamlUtf8Encoding).is_err())
+    }
+
+    #[test]
+    fn test_emit_all_keeps_structure() {
+        let data = "%YAML 1.1\n--- !foo\n[&a [1], *a]\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut docs: Vec<Box<YamlDocument>> = parser.load().map(|res| res.unwrap()).collect();
+        docs.push(YamlDocument::init(None, &[], true, true).unwrap());
+
+        let emitted = String::from_utf8(emit_all(docs.as_slice()).unwrap()).unwrap();
+        assert!(emitted.as_slice().starts_with("%YAML 1.1\n"));
+        assert!(emitted.as_slice().contains("&a"));
+        assert!(emitted.as_slice().contains("*a"));
+
+        let reparser = YamlByteParser::init(emitted.as_bytes(), YamlUtf8Encoding).unwrap();
+        let reparsed: Vec<Box<YamlDocument>> = reparser.load().map(|res| res.unwrap()).collect();
+        assert_eq!(2, reparsed.len());
+        assert_eq!(Ok(vec![YamlSequence(vec![YamlSequence(vec![YamlInteger(1)]), YamlSequence(vec![YamlInteger(1)])]),
+                           YamlNull]), construct_all(reparsed.as_slice()));
+    }
+
+    #[test]
+    fn test_compare_data() {
+        let left = YamlMapping(vec![(YamlString("a".to_string()), YamlSequence(vec![YamlInteger(1)]))]);
+        let right = YamlMapping(vec![(YamlString("a".to_string()), YamlSequence(vec![YamlString("1".to_string())]))]);
+        let mut out = Vec::new();
+        compare_data(0, "", &left, &right, &mut out);
+
+        assert_eq!(vec![YamlDiscrepancy {
+            document: 0,
+            path: "a[0]".to_string(),
+            kind: ValueMismatch(YamlInteger(1), YamlString("1".to_string()))
+        }], out)
+    }
+}
@@ -1,348 +1,2546 @@
 use libc;
+use time;
 
 use ffi;
-use error::{YamlError, YamlErrorContext, YamlMark};
-use event::YamlEvent;
+use error::{YamlError, YamlErrorCode, YamlErrorContext, YamlMark};
+use event::{YamlEvent, YamlMarkedEvent, YamlEventRef};
+use diagnostic::{YamlWarning, YamlWarningKind};
 use document::{YamlDocument};
+use tokens::YamlTokenStream;
+use schema::{YamlResolver, YamlSchema, YamlYaml11Construct, yaml11_construct, may_be_yaml11_construct};
 use codecs;
 
+use std::cmp;
 use std::mem;
-use std::io::{IoError, EndOfFile};
+use std::ptr;
+use std::any::Any;
+use std::io::{File, IoError, IoResult, EndOfFile, ResourceUnavailable, TimedOut};
+use std::io::timer;
+use std::time::Duration;
+use std::rt::unwind;
+use std::slice;
+use std::slice::bytes;
 use std::c_vec::CVec;
 
+fn stream_error(message: &str) -> YamlError {
+    YamlError {
+        kind: ffi::YamlErrorType::YAML_PARSER_ERROR,
+        code: YamlErrorCode::ParserError,
+        problem: Some(message.to_string()),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+pub static MAX_WARNINGS: uint = 1000;
+static MAX_STALL_BACKOFF_MS: i64 = 100;
+
+fn stall_error(detail: String) -> IoError {
+    IoError {
+        kind: TimedOut,
+        desc: "reader stalled",
+        detail: Some(detail)
+    }
+}
+
+fn initialize_error() -> YamlError {
+    YamlError {
+        kind: ffi::YamlErrorType::YAML_MEMORY_ERROR,
+        code: YamlErrorCode::MemoryError,
+        problem: Some("failed to initialize yaml_parser_t".to_string()),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+pub struct YamlMarkedEventStream<P> {
+    parser: P,
+    finished: bool,
+    document_start: uint
+}
+
+impl<P:YamlParser> Iterator for YamlMarkedEventStream<P> {
+    type Item = Result<YamlMarkedEvent, YamlError>;
+
+    fn next(&mut self) -> Option<Result<YamlMarkedEvent, YamlError>> {
+        if self.finished {
+            return None;
+        }
+
+        let res = unsafe {
+            match self.parser.parse_marked_event() {
+                Some((YamlEvent::YamlNoEvent, _, _)) => None,
+                Some((evt, start_mark, end_mark)) => Some(Ok(YamlMarkedEvent {
+                    event: evt,
+                    start_mark: start_mark,
+                    end_mark: end_mark
+                })),
+                None => match self.parser.get_error() {
+                    ref e if e.code == YamlErrorCode::NoError => None,
+                    e => Some(Err(e))
+                }
+            }
+        };
+
+        match res {
+            Some(Ok(YamlMarkedEvent { event: YamlEvent::YamlStreamEndEvent, .. })) => self.finished = true,
+            Some(Ok(YamlMarkedEvent { event: YamlEvent::YamlDocumentStartEvent(..), start_mark, .. })) =>
+                self.document_start = start_mark.index,
+            Some(Ok(_)) => (),
+            _ => self.finished = true
+        }
+        res
+    }
+}
+
+impl<P:YamlParser> YamlMarkedEventStream<P> {
+    // borrows the event from libyaml instead of copying it out, but steps
+    // the same state as `next`, so the stream still fuses and tracks documents
+    pub fn next_event_ref(&mut self) -> Option<Result<YamlEventRef, YamlError>> {
+        if self.finished {
+            return None;
+        }
+
+        let res = unsafe {
+            let mut event_mem = mem::uninitialized();
+            if !self.parser.base_parser_ref().parse(&mut event_mem) {
+                match self.parser.get_error() {
+                    ref e if e.code == YamlErrorCode::NoError => None,
+                    e => Some(Err(e))
+                }
+            } else {
+                let event = YamlEventRef::new(event_mem);
+                match event.event_type() {
+                    ffi::yaml_event_type_t::YAML_NO_EVENT => None,
+                    _ => {
+                        self.parser.base_parser_ref().check_warnings(&event.to_event(), &event.start_mark());
+                        Some(Ok(event))
+                    }
+                }
+            }
+        };
+
+        match res {
+            Some(Ok(ref event)) => match event.event_type() {
+                ffi::yaml_event_type_t::YAML_STREAM_END_EVENT => self.finished = true,
+                ffi::yaml_event_type_t::YAML_DOCUMENT_START_EVENT => self.document_start = event.start_mark().index,
+                _ => ()
+            },
+            _ => self.finished = true
+        }
+        res
+    }
+}
+
 pub struct YamlEventStream<P> {
-    parser: Box<P>,
+    inner: YamlMarkedEventStream<P>,
+    pending: Vec<Result<YamlEvent, YamlError>>
+}
+
+impl<P:YamlParser> YamlEventStream<P> {
+    pub fn peek_event(&mut self) -> Option<&Result<YamlEvent, YamlError>> {
+        if self.pending.is_empty() {
+            match self.inner.next() {
+                Some(res) => self.pending.push(res.map(|marked| marked.event)),
+                None => return None
+            }
+        }
+        self.pending.last()
+    }
+
+    // a character index, like `YamlMark::index`, not a byte offset
+    pub fn offset(&mut self) -> uint {
+        self.inner.parser.current_mark().index
+    }
+
+    // raw input bytes decoded so far; the reader may run ahead of the events
+    pub fn bytes_read(&mut self) -> uint {
+        self.inner.parser.bytes_read()
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<YamlWarning> {
+        self.inner.parser.take_warnings()
+    }
+
+    pub fn push_back(&mut self, event: YamlEvent) {
+        self.pending.push(Ok(event));
+    }
+
+    pub fn sequence_items<'s>(&'s mut self) -> YamlSequenceItems<'s, P> {
+        YamlSequenceItems {
+            stream: self,
+            started: false,
+            finished: false
+        }
+    }
+
+    // resyncing re-reads the input, so only byte parsers over UTF-8 support
+    // it; an IO parser can't rewind its reader and always returns false
+    pub fn recover_to_next_document(&mut self) -> bool {
+        self.pending.clear();
+        if !self.inner.parser.resync_after(self.inner.document_start) {
+            return false;
+        }
+
+        // the restarted parser opens a new stream, which the caller has already seen
+        self.inner.finished = false;
+        match self.inner.next() {
+            Some(Ok(YamlMarkedEvent { event: YamlEvent::YamlStreamStartEvent(_), .. })) => (),
+            Some(res) => self.pending.push(res.map(|marked| marked.event)),
+            None => ()
+        }
+        true
+    }
+
+    pub fn skip_node(&mut self) -> Result<(), YamlError> {
+        let mut depth = 0u;
+        loop {
+            let event = match self.next() {
+                Some(res) => try!(res),
+                None => return Err(stream_error("stream ended while skipping a node"))
+            };
+            match event {
+                YamlEvent::YamlScalarEvent(_) | YamlEvent::YamlAliasEvent(_) => (),
+                YamlEvent::YamlSequenceStartEvent(_) | YamlEvent::YamlMappingStartEvent(_) => depth += 1,
+                YamlEvent::YamlSequenceEndEvent | YamlEvent::YamlMappingEndEvent if depth > 0 => depth -= 1,
+                event => {
+                    self.push_back(event);
+                    return Err(stream_error("did not find expected node"));
+                }
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
 }
 
 impl<P:YamlParser> Iterator for YamlEventStream<P> {
     type Item = Result<YamlEvent, YamlError>;
 
     fn next(&mut self) -> Option<Result<YamlEvent, YamlError>> {
+        match self.pending.pop() {
+            Some(res) => Some(res),
+            None => self.inner.next().map(|res| res.map(|marked| marked.event))
+        }
+    }
+}
+
+pub struct YamlSequenceItems<'s, P: 's> {
+    stream: &'s mut YamlEventStream<P>,
+    started: bool,
+    finished: bool
+}
+
+impl<'s, P:YamlParser> YamlSequenceItems<'s, P> {
+    fn fail(&mut self, error: YamlError) -> Option<Result<Box<YamlDocument>, YamlError>> {
+        self.finished = true;
+        Some(Err(error))
+    }
+}
+
+impl<'s, P:YamlParser> Iterator for YamlSequenceItems<'s, P> {
+    type Item = Result<Box<YamlDocument>, YamlError>;
+
+    fn next(&mut self) -> Option<Result<Box<YamlDocument>, YamlError>> {
+        if self.finished {
+            return None;
+        }
+
+        while !self.started {
+            match self.stream.next() {
+                Some(Ok(YamlEvent::YamlStreamStartEvent(_))) | Some(Ok(YamlEvent::YamlDocumentStartEvent(..))) => (),
+                Some(Ok(YamlEvent::YamlSequenceStartEvent(_))) => self.started = true,
+                Some(Ok(event)) => {
+                    self.stream.push_back(event);
+                    return self.fail(stream_error("did not find expected top-level sequence"));
+                },
+                Some(Err(e)) => return self.fail(e),
+                None => {
+                    self.finished = true;
+                    return None;
+                }
+            }
+        }
+
+        if !self.stream.pending.is_empty() {
+            return self.fail(stream_error("cannot compose sequence items after events were pushed back"));
+        }
+
+        match self.stream.inner.next() {
+            Some(Ok(YamlMarkedEvent { event: YamlEvent::YamlSequenceEndEvent, .. })) => {
+                self.finished = true;
+                None
+            },
+            Some(Ok(marked)) => {
+                let res = YamlDocument::compose_value(&mut self.stream.inner.parser,
+                    marked.event, marked.start_mark, marked.end_mark);
+                if res.is_err() {
+                    self.finished = true;
+                }
+                Some(res)
+            },
+            Some(Err(e)) => self.fail(e),
+            None => self.fail(stream_error("stream ended inside the top-level sequence"))
+        }
+    }
+}
+
+pub struct YamlDocumentStream<P> {
+    parser: P,
+    finished: bool
+}
+
+impl<P:YamlParser> YamlDocumentStream<P> {
+    pub fn take_warnings(&mut self) -> Vec<YamlWarning> {
+        self.parser.take_warnings()
+    }
+}
+
+impl<P:YamlParser> Iterator for YamlDocumentStream<P> {
+    type Item = Result<Box<YamlDocument>, YamlError>;
+
+    fn next(&mut self) -> Option<Result<Box<YamlDocument>, YamlError>> {
+        if self.finished {
+            return None;
+        }
+
+        match YamlDocument::compose(&mut self.parser) {
+            Ok(Some(doc)) => Some(Ok(doc)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            },
+            Err(ref e) if e.code == YamlErrorCode::NoError => {
+                self.finished = true;
+                None
+            },
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+pub struct InternalEvent {
+    event_mem: ffi::yaml_event_t
+}
+
+impl Drop for InternalEvent {
+    fn drop(&mut self) {
         unsafe {
-            match self.parser.parse_event() {
-                Some(YamlEvent::YamlNoEvent) => None,
-                Some(evt) => Some(Ok(evt)),
-                None => Some(Err(self.parser.get_error()))
+            self.event_mem.delete()
+        }
+    }
+}
+
+#[derive(Show, PartialEq, Clone, Copy)]
+pub struct YamlResourceLimits {
+    pub input_bytes: Option<uint>,
+    // checked once libyaml has buffered the whole scalar, so this only bounds
+    // the copy into Rust; pair it with input_bytes to cap libyaml's memory
+    pub scalar_bytes: Option<uint>,
+    pub document_events: Option<uint>,
+    pub document_nodes: Option<uint>,
+    pub depth: Option<uint>,
+    pub alias_expansions: Option<uint>,
+    pub stalled_reads: Option<uint>,
+    pub stall_timeout: Option<Duration>,
+    pub output_bytes: Option<uint>
+}
+
+impl YamlResourceLimits {
+    pub fn new() -> YamlResourceLimits {
+        YamlResourceLimits {
+            input_bytes: None,
+            scalar_bytes: None,
+            document_events: None,
+            document_nodes: None,
+            depth: None,
+            alias_expansions: None,
+            stalled_reads: None,
+            stall_timeout: None,
+            output_bytes: None
+        }
+    }
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlDuplicateKeys {
+    AllowDuplicateKeys,
+    RejectDuplicateKeys
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlDuplicateAnchors {
+    AllowDuplicateAnchors,
+    RejectDuplicateAnchors
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlAnchors {
+    AllowAnchors,
+    RejectAnchors
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlTags {
+    AllowAllTags,
+    CoreTagsOnly
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlStrictness {
+    AllowYaml11,
+    WarnYaml11,
+    RejectYaml11
+}
+
+pub trait YamlParser: Sized {
+    unsafe fn base_parser_ref<'r>(&'r mut self) -> &'r mut YamlBaseParser;
+    unsafe fn get_error(&mut self) -> YamlError;
+
+    fn duplicate_key_policy(&mut self) -> YamlDuplicateKeys {
+        unsafe { self.base_parser_ref().duplicate_keys }
+    }
+
+    fn set_duplicate_key_policy(&mut self, policy: YamlDuplicateKeys) {
+        unsafe { self.base_parser_ref().duplicate_keys = policy; }
+    }
+
+    fn duplicate_anchor_policy(&mut self) -> YamlDuplicateAnchors {
+        unsafe { self.base_parser_ref().duplicate_anchors }
+    }
+
+    fn set_duplicate_anchor_policy(&mut self, policy: YamlDuplicateAnchors) {
+        unsafe { self.base_parser_ref().duplicate_anchors = policy; }
+    }
+
+    fn anchor_policy(&mut self) -> YamlAnchors {
+        unsafe { self.base_parser_ref().anchors }
+    }
+
+    fn set_anchor_policy(&mut self, policy: YamlAnchors) {
+        unsafe { self.base_parser_ref().anchors = policy; }
+    }
+
+    fn tag_policy(&mut self) -> YamlTags {
+        unsafe { self.base_parser_ref().tags }
+    }
+
+    fn set_tag_policy(&mut self, policy: YamlTags) {
+        unsafe { self.base_parser_ref().tags = policy; }
+    }
+
+    fn schema(&mut self) -> YamlSchema {
+        unsafe { self.base_parser_ref().resolver.schema() }
+    }
+
+    fn set_schema(&mut self, schema: YamlSchema) {
+        unsafe { self.base_parser_ref().resolver = YamlResolver::new(schema); }
+    }
+
+    fn resolver(&mut self) -> YamlResolver {
+        unsafe { self.base_parser_ref().resolver }
+    }
+
+    fn set_resolver(&mut self, resolver: YamlResolver) {
+        unsafe { self.base_parser_ref().resolver = resolver; }
+    }
+
+    fn strictness(&mut self) -> YamlStrictness {
+        unsafe { self.base_parser_ref().strictness }
+    }
+
+    fn set_strictness(&mut self, strictness: YamlStrictness) {
+        unsafe { self.base_parser_ref().strictness = strictness; }
+    }
+
+    fn limits(&mut self) -> YamlResourceLimits {
+        unsafe { self.base_parser_ref().limits }
+    }
+
+    fn set_limits(&mut self, limits: YamlResourceLimits) {
+        unsafe { self.base_parser_ref().limits = limits; }
+    }
+
+    fn node_limit(&mut self) -> Option<uint> {
+        unsafe { self.base_parser_ref().limits.document_nodes }
+    }
+
+    fn set_node_limit(&mut self, limit: Option<uint>) {
+        unsafe { self.base_parser_ref().limits.document_nodes = limit; }
+    }
+
+    fn depth_limit(&mut self) -> Option<uint> {
+        unsafe { self.base_parser_ref().limits.depth }
+    }
+
+    fn set_depth_limit(&mut self, limit: Option<uint>) {
+        unsafe { self.base_parser_ref().limits.depth = limit; }
+    }
+
+    fn event_limit(&mut self) -> Option<uint> {
+        unsafe { self.base_parser_ref().limits.document_events }
+    }
+
+    fn set_event_limit(&mut self, limit: Option<uint>) {
+        unsafe { self.base_parser_ref().limits.document_events = limit; }
+    }
+
+    fn scalar_limit(&mut self) -> Option<uint> {
+        unsafe { self.base_parser_ref().limits.scalar_bytes }
+    }
+
+    fn set_scalar_limit(&mut self, limit: Option<uint>) {
+        unsafe { self.base_parser_ref().limits.scalar_bytes = limit; }
+    }
+
+    fn detected_encoding(&mut self) -> Option<ffi::YamlEncoding> {
+        match unsafe { self.base_parser_ref().parser_mem.encoding } {
+            ffi::YamlEncoding::YamlAnyEncoding => None,
+            encoding => Some(encoding)
+        }
+    }
+
+    fn has_bom(&mut self) -> bool {
+        unsafe { self.base_parser_ref().bom }
+    }
+
+    fn set_cancel_hook<F: FnMut() -> bool + 'static>(&mut self, hook: F) {
+        unsafe { self.base_parser_ref().cancel_hook = Some(box hook as Box<YamlCancelCheck + 'static>); }
+    }
+
+    fn is_cancelled(&mut self) -> bool {
+        unsafe { self.base_parser_ref().cancelled }
+    }
+
+    fn resync_after(&mut self, index: uint) -> bool {
+        unsafe { self.base_parser_ref().resync_after(index) }
+    }
+
+    fn current_mark(&mut self) -> YamlMark {
+        unsafe { YamlMark::conv(&self.base_parser_ref().parser_mem.mark) }
+    }
+
+    fn bytes_read(&mut self) -> uint {
+        unsafe { self.base_parser_ref().parser_mem.offset as uint }
+    }
+
+    fn warnings(&mut self) -> Vec<YamlWarning> {
+        unsafe { self.base_parser_ref().warnings.clone() }
+    }
+
+    fn take_warnings(&mut self) -> Vec<YamlWarning> {
+        unsafe { mem::replace(&mut self.base_parser_ref().warnings, Vec::new()) }
+    }
+
+    fn report_warning(&mut self, warning: YamlWarning) {
+        unsafe { self.base_parser_ref().add_warning(warning); }
+    }
+
+    fn filename(&mut self) -> Option<String> {
+        unsafe { self.base_parser_ref().filename.clone() }
+    }
+
+    fn set_filename(&mut self, filename: Option<String>) {
+        unsafe { self.base_parser_ref().filename = filename; }
+    }
+
+    unsafe fn parse_marked_event(&mut self) -> Option<(YamlEvent, YamlMark, YamlMark)> {
+        let mut event = InternalEvent {
+            event_mem: mem::uninitialized()
+        };
+
+        if !self.base_parser_ref().parse(&mut event.event_mem) {
+            None
+        } else {
+            let marked = YamlMarkedEvent::load(&event.event_mem);
+            self.base_parser_ref().check_warnings(&marked.event, &marked.start_mark);
+            Some((marked.event, marked.start_mark, marked.end_mark))
+        }
+    }
+
+    unsafe fn parse_event(&mut self) -> Option<YamlEvent> {
+        self.parse_marked_event().map(|(evt, _, _)| evt)
+    }
+
+    fn parse(self: Box<Self>) -> YamlEventStream<Box<Self>> {
+        event_stream(self)
+    }
+
+    fn parse_marked(self: Box<Self>) -> YamlMarkedEventStream<Box<Self>> {
+        marked_event_stream(self)
+    }
+
+    fn load(self: Box<Self>) -> YamlDocumentStream<Box<Self>> {
+        document_stream(self)
+    }
+
+    fn load_all(self: Box<Self>) -> Result<Vec<Box<YamlDocument>>, YamlError> {
+        self.load().collect()
+    }
+
+    fn tokens(self: Box<Self>) -> YamlTokenStream<Self> {
+        YamlTokenStream::new(self)
+    }
+
+    fn events<'p>(&'p mut self) -> YamlEventStream<&'p mut Self> {
+        event_stream(self)
+    }
+
+    fn marked_events<'p>(&'p mut self) -> YamlMarkedEventStream<&'p mut Self> {
+        marked_event_stream(self)
+    }
+
+    fn documents<'p>(&'p mut self) -> YamlDocumentStream<&'p mut Self> {
+        document_stream(self)
+    }
+}
+
+impl<P: YamlParser> YamlParser for Box<P> {
+    unsafe fn base_parser_ref<'r>(&'r mut self) -> &'r mut YamlBaseParser {
+        (**self).base_parser_ref()
+    }
+
+    unsafe fn get_error(&mut self) -> YamlError {
+        (**self).get_error()
+    }
+}
+
+impl<'p, P: YamlParser> YamlParser for &'p mut P {
+    unsafe fn base_parser_ref<'r>(&'r mut self) -> &'r mut YamlBaseParser {
+        (**self).base_parser_ref()
+    }
+
+    unsafe fn get_error(&mut self) -> YamlError {
+        (**self).get_error()
+    }
+}
+
+fn marked_event_stream<P: YamlParser>(parser: P) -> YamlMarkedEventStream<P> {
+    YamlMarkedEventStream {
+        parser: parser,
+        finished: false,
+        document_start: 0
+    }
+}
+
+fn event_stream<P: YamlParser>(parser: P) -> YamlEventStream<P> {
+    YamlEventStream {
+        inner: marked_event_stream(parser),
+        pending: Vec::new()
+    }
+}
+
+fn document_stream<P: YamlParser>(parser: P) -> YamlDocumentStream<P> {
+    YamlDocumentStream {
+        parser: parser,
+        finished: false
+    }
+}
+
+extern fn handle_reader_cb<R: Reader>(data: *mut libc::c_void, buffer: *mut u8, size: libc::size_t, size_read: *mut libc::size_t) -> libc::c_int {
+    unsafe {
+        let parser: &mut YamlIoParser<R> = mem::transmute(data);
+        let mut ret = 0;
+        // unwinding into libyaml is undefined behavior, so a panic in the reader
+        // is caught here and raised again by get_error once libyaml has returned
+        let res = unwind::try(|| ret = read_into_buffer(&mut *parser, buffer, size, size_read));
+        match res {
+            Ok(()) => ret,
+            Err(cause) => {
+                parser.panic = Some(cause);
+                0
+            }
+        }
+    }
+}
+
+unsafe fn read_into_buffer<R: Reader>(parser: &mut YamlIoParser<R>, buffer: *mut u8, size: libc::size_t, size_read: *mut libc::size_t) -> libc::c_int {
+    if parser.base_parser.check_cancel() {
+        return 0;
+    }
+    let mut buf = CVec::new(buffer, size as uint);
+    let res = if parser.read_buffer_size.is_some() {
+        parser.read_buffered(buf.as_mut_slice())
+    } else {
+        parser.read_into(buf.as_mut_slice())
+    };
+    match res {
+        Ok(size) => {
+            parser.total_read += size;
+            match parser.base_parser.limits.input_bytes {
+                Some(limit) if parser.total_read > limit => {
+                    parser.base_parser.rejection = Some((ffi::YamlErrorType::YAML_READER_ERROR,
+                        YamlErrorCode::InputLimitExceeded, format!("input is larger than {} bytes", limit), None));
+                    return 0;
+                },
+                _ => ()
+            }
+            if parser.head.len() < 3 {
+                let wanted = cmp::min(3 - parser.head.len(), size);
+                parser.head.push_all(buf.as_slice().slice_to(wanted));
+                parser.base_parser.bom = starts_with_bom(parser.head.as_slice());
+            }
+            *size_read = size as libc::size_t;
+            return 1;
+        },
+        Err(err) => {
+            match err.kind {
+                EndOfFile => {
+                    *size_read = 0;
+                    return 1;
+                },
+                _ => {
+                    parser.io_error = Some(err);
+                    return 0;
+                }
+            }
+        }
+    }
+}
+
+fn starts_with_bom(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0xEF, 0xBB, 0xBF]) || bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF])
+}
+
+unsafe fn has_anchor(event: &ffi::yaml_event_t) -> bool {
+    match event.event_type {
+        ffi::yaml_event_type_t::YAML_ALIAS_EVENT => true,
+        ffi::yaml_event_type_t::YAML_SCALAR_EVENT => {
+            let data: &ffi::yaml_scalar_event_t = mem::transmute(&event.data);
+            data.anchor != ptr::null()
+        },
+        ffi::yaml_event_type_t::YAML_SEQUENCE_START_EVENT => {
+            let data: &ffi::yaml_sequence_start_event_t = mem::transmute(&event.data);
+            data.anchor != ptr::null()
+        },
+        ffi::yaml_event_type_t::YAML_MAPPING_START_EVENT => {
+            let data: &ffi::yaml_mapping_start_event_t = mem::transmute(&event.data);
+            data.anchor != ptr::null()
+        },
+        _ => false
+    }
+}
+
+unsafe fn event_tag(event: &ffi::yaml_event_t) -> *const ffi::yaml_char_t {
+    match event.event_type {
+        ffi::yaml_event_type_t::YAML_SCALAR_EVENT => {
+            let data: &ffi::yaml_scalar_event_t = mem::transmute(&event.data);
+            data.tag
+        },
+        ffi::yaml_event_type_t::YAML_SEQUENCE_START_EVENT => {
+            let data: &ffi::yaml_sequence_start_event_t = mem::transmute(&event.data);
+            data.tag
+        },
+        ffi::yaml_event_type_t::YAML_MAPPING_START_EVENT => {
+            let data: &ffi::yaml_mapping_start_event_t = mem::transmute(&event.data);
+            data.tag
+        },
+        _ => ptr::null()
+    }
+}
+
+// the types of the yaml.org type repository; application types that happen
+// to use the same prefix, like !!python/object, are not among them
+fn is_core_tag(tag: &str) -> bool {
+    if tag == "!" {
+        return true;
+    }
+    if !tag.starts_with("tag:yaml.org,2002:") {
+        return false;
+    }
+    match tag.slice_from("tag:yaml.org,2002:".len()) {
+        "str" | "int" | "float" | "bool" | "null" | "seq" | "map" | "binary" | "timestamp" |
+        "merge" | "omap" | "pairs" | "set" | "value" => true,
+        _ => false
+    }
+}
+
+fn yaml11_message(construct: YamlYaml11Construct, value: &str) -> String {
+    match construct {
+        YamlYaml11Construct::WordBoolean =>
+            format!("plain scalar {} is a boolean in YAML 1.1 but a string in YAML 1.2", value),
+        YamlYaml11Construct::Sexagesimal =>
+            format!("plain scalar {} is a base-60 number in YAML 1.1 but a string in YAML 1.2", value),
+        YamlYaml11Construct::ValueKey =>
+            "plain scalar = is a value key in YAML 1.1 but a string in YAML 1.2".to_string()
+    }
+}
+
+fn is_document_marker(line: &[u8]) -> bool {
+    line.starts_with("---".as_bytes()) && match line.get(3) {
+        None | Some(&b' ') | Some(&b'\t') | Some(&b'\r') | Some(&b'\n') => true,
+        _ => false
+    }
+}
+
+pub trait YamlCancelCheck {
+    fn should_cancel(&mut self) -> bool;
+}
+
+impl<F: FnMut() -> bool> YamlCancelCheck for F {
+    fn should_cancel(&mut self) -> bool {
+        (*self)()
+    }
+}
+
+pub struct YamlBaseParser {
+    parser_mem: ffi::yaml_parser_t,
+    encoding: ffi::YamlEncoding,
+    duplicate_keys: YamlDuplicateKeys,
+    duplicate_anchors: YamlDuplicateAnchors,
+    anchors: YamlAnchors,
+    tags: YamlTags,
+    strictness: YamlStrictness,
+    resolver: YamlResolver,
+    limits: YamlResourceLimits,
+    depth: uint,
+    document_events: uint,
+    rejection: Option<(ffi::YamlErrorType, YamlErrorCode, String, Option<YamlMark>)>,
+    bom: bool,
+    filename: Option<String>,
+    cancel_hook: Option<Box<YamlCancelCheck + 'static>>,
+    cancelled: bool,
+    input: Option<(*const u8, uint)>,
+    warnings: Vec<YamlWarning>,
+    document_anchors: Vec<(String, YamlMark)>,
+    input_cursor: (uint, uint),
+    checked_line: Option<uint>
+}
+
+impl YamlBaseParser {
+    unsafe fn new(encoding: ffi::YamlEncoding) -> YamlBaseParser {
+        YamlBaseParser {
+            parser_mem: mem::uninitialized(),
+            encoding: encoding,
+            duplicate_keys: YamlDuplicateKeys::AllowDuplicateKeys,
+            duplicate_anchors: YamlDuplicateAnchors::AllowDuplicateAnchors,
+            anchors: YamlAnchors::AllowAnchors,
+            tags: YamlTags::AllowAllTags,
+            strictness: YamlStrictness::WarnYaml11,
+            resolver: YamlResolver::new(YamlSchema::Yaml11),
+            limits: YamlResourceLimits::new(),
+            depth: 0,
+            document_events: 0,
+            rejection: None,
+            bom: false,
+            filename: None,
+            cancel_hook: None,
+            cancelled: false,
+            input: None,
+            warnings: Vec::new(),
+            document_anchors: Vec::new(),
+            input_cursor: (0, 0),
+            checked_line: None
+        }
+    }
+
+    unsafe fn initialize(&mut self) -> bool {
+        if ffi::yaml_parser_initialize(&mut self.parser_mem) == 0 {
+            return false;
+        }
+        ffi::yaml_parser_set_encoding(&mut self.parser_mem, self.encoding);
+        true
+    }
+
+    // rewinds libyaml's state in place, keeping the buffers, queue and stacks
+    // it already allocated; the loader's alias stack is never filled here
+    unsafe fn recycle(&mut self) {
+        let old = self.parser_mem;
+
+        let mut token = old.tokens.head as *mut ffi::yaml_token_t;
+        while token as *const libc::c_void != old.tokens.tail {
+            ffi::yaml_token_delete(token);
+            token = token.offset(1);
+        }
+        let mut directive = old.tag_directives.start as *const ffi::yaml_tag_directive_t;
+        while directive as *const libc::c_void != old.tag_directives.top {
+            libc::free((*directive).handle as *mut libc::c_void);
+            libc::free((*directive).prefix as *mut libc::c_void);
+            directive = directive.offset(1);
+        }
+
+        let buffer = |b: ffi::yaml_buffer_t| ffi::yaml_buffer_t { start: b.start, end: b.end, pointer: b.start, last: b.start };
+        let stack = |s: ffi::yaml_stack_t| ffi::yaml_stack_t { start: s.start, end: s.end, top: s.start };
+
+        self.parser_mem = mem::zeroed();
+        self.parser_mem.buffer = buffer(old.buffer);
+        self.parser_mem.raw_buffer = buffer(old.raw_buffer);
+        self.parser_mem.tokens = ffi::yaml_queue_t { start: old.tokens.start, end: old.tokens.end,
+                                                     head: old.tokens.start, tail: old.tokens.start };
+        self.parser_mem.indents = stack(old.indents);
+        self.parser_mem.simple_keys = stack(old.simple_keys);
+        self.parser_mem.states = stack(old.states);
+        self.parser_mem.marks = stack(old.marks);
+        self.parser_mem.tag_directives = stack(old.tag_directives);
+        self.parser_mem.aliases = stack(old.aliases);
+        ffi::yaml_parser_set_encoding(&mut self.parser_mem, self.encoding);
+    }
+
+    // reuses libyaml's allocations when it has them; a parser whose earlier
+    // initialize failed is left zeroed, so dropping it frees nothing, and
+    // every later parse reports the failure instead of touching libyaml
+    unsafe fn reset(&mut self) -> bool {
+        self.bom = false;
+        self.cancelled = false;
+        self.depth = 0;
+        self.document_events = 0;
+        self.rejection = None;
+        self.warnings.clear();
+        self.document_anchors.clear();
+        self.input_cursor = (0, 0);
+        self.checked_line = None;
+        if self.parser_mem.buffer.start != ptr::null() {
+            self.recycle();
+            return true;
+        }
+        if !self.initialize() {
+            self.parser_mem = mem::zeroed();
+            let error = initialize_error();
+            self.rejection = Some((error.kind, error.code, error.problem.unwrap(), None));
+            return false;
+        }
+        true
+    }
+
+    unsafe fn set_input_string(&mut self, input: *const u8, size: uint) {
+        self.input = Some((input, size));
+        ffi::yaml_parser_set_input_string(&mut self.parser_mem, input, size as libc::size_t);
+    }
+
+    // restarts string input at the first `---` line past the error that also
+    // starts after the document beginning at character `index`
+    unsafe fn resync_after(&mut self, index: uint) -> bool {
+        let (ptr, len) = match self.input {
+            Some(input) if self.parser_mem.encoding == ffi::YamlEncoding::YamlUtf8Encoding => input,
+            _ => return false
+        };
+        let input: &[u8] = slice::from_raw_buf(&ptr, len);
+        let skip = if input.starts_with(&[0xEF, 0xBB, 0xBF]) { 3 } else { 0 };
+
+        let error_pos = match self.parser_mem.error {
+            ffi::YamlErrorType::YAML_READER_ERROR => cmp::min(self.parser_mem.problem_offset as uint, len),
+            _ => skip + codecs::utf8_byte_offset(input.slice_from(skip), self.parser_mem.problem_mark.index as uint)
+        };
+        let lower = skip + codecs::utf8_byte_offset(input.slice_from(skip), index);
+
+        let mut pos = error_pos;
+        while pos > 0 && input[pos - 1] != b'\n' {
+            pos -= 1;
+        }
+        while pos <= lower || !is_document_marker(input.slice_from(pos)) {
+            pos = match input.slice_from(pos).position_elem(&b'\n') {
+                Some(i) => pos + i + 1,
+                None => return false
+            };
+        }
+
+        let prefix = input.slice(skip, pos);
+        let mark = ffi::yaml_mark_t {
+            index: prefix.iter().filter(|&&b| b & 0xC0 != 0x80).count() as libc::size_t,
+            line: prefix.iter().filter(|&&b| b == b'\n').count() as libc::size_t,
+            column: 0
+        };
+        let bom = self.bom;
+
+        if !self.reset() {
+            return false;
+        }
+        ffi::yaml_parser_set_input_string(&mut self.parser_mem, ptr.offset(pos as int), (len - pos) as libc::size_t);
+        self.parser_mem.mark = mark;
+        self.parser_mem.offset = pos as libc::size_t;
+        self.bom = bom;
+        true
+    }
+
+    // byte offset of character `index` in the string input; events arrive in
+    // input order, so the scan resumes from the previous lookup
+    fn input_byte_offset(&mut self, input: &[u8], index: uint) -> uint {
+        let (mut chars, mut pos) = if index < self.input_cursor.0 { (0, 0) } else { self.input_cursor };
+        while chars < index && pos < input.len() {
+            pos += 1;
+            while pos < input.len() && input[pos] & 0xC0 == 0x80 {
+                pos += 1;
+            }
+            chars += 1;
+        }
+        self.input_cursor = (chars, pos);
+        pos
+    }
+
+    unsafe fn check_indentation(&mut self, mark: &YamlMark) {
+        let (ptr, len) = match self.input {
+            Some(input) if self.parser_mem.encoding == ffi::YamlEncoding::YamlUtf8Encoding => input,
+            _ => return
+        };
+        if self.checked_line == Some(mark.line) {
+            return;
+        }
+        self.checked_line = Some(mark.line);
+
+        let input: &[u8] = slice::from_raw_buf(&ptr, len);
+        let input = if self.bom { input.slice_from(3) } else { input };
+        let start = self.input_byte_offset(input, mark.index - mark.column);
+        for (column, &b) in input.slice_from(start).iter().enumerate() {
+            match b {
+                b' ' => (),
+                b'\t' => {
+                    let tab_mark = YamlMark { index: mark.index - mark.column + column, line: mark.line, column: column };
+                    self.add_warning(YamlWarning::new(YamlWarningKind::TabIndentation,
+                                                      "found tab character in indentation".to_string(), tab_mark));
+                    return;
+                },
+                _ => return
+            }
+        }
+    }
+
+    // warnings past the cap are dropped, so hostile input cannot grow the list without bound
+    fn add_warning(&mut self, warning: YamlWarning) {
+        if self.warnings.len() < MAX_WARNINGS {
+            self.warnings.push(warning);
+        }
+    }
+
+    fn check_anchor(&mut self, anchor: &Option<String>, mark: &YamlMark) {
+        let name = match *anchor {
+            Some(ref name) => name,
+            None => return
+        };
+        match self.document_anchors.iter().find(|&&(ref a, _)| a == name) {
+            Some(&(_, first_mark)) => {
+                let mut warning = YamlWarning::new(YamlWarningKind::DuplicateAnchor,
+                                                   format!("anchor {} is redefined", name), *mark);
+                warning.related_mark = Some(first_mark);
+                self.add_warning(warning);
+            },
+            None => ()
+        }
+        self.document_anchors.push((name.clone(), *mark));
+    }
+
+    unsafe fn check_warnings(&mut self, event: &YamlEvent, mark: &YamlMark) {
+        match *event {
+            YamlEvent::YamlDocumentStartEvent(..) => self.document_anchors.clear(),
+            YamlEvent::YamlScalarEvent(ref param) => {
+                let construct = match (&param.tag, param.style) {
+                    (&None, ffi::YamlScalarStyle::YamlPlainScalarStyle) => yaml11_construct(param.value.as_slice()),
+                    _ => None
+                };
+                match construct {
+                    Some(construct) if self.strictness != YamlStrictness::AllowYaml11 => {
+                        let kind = if construct == YamlYaml11Construct::WordBoolean {
+                            YamlWarningKind::DeprecatedBoolean
+                        } else {
+                            YamlWarningKind::Yaml11Construct
+                        };
+                        self.add_warning(YamlWarning::new(kind, yaml11_message(construct, param.value.as_slice()), *mark));
+                    },
+                    _ => ()
+                }
+                self.check_anchor(&param.anchor, mark);
+            },
+            YamlEvent::YamlSequenceStartEvent(ref param) | YamlEvent::YamlMappingStartEvent(ref param) =>
+                self.check_anchor(&param.anchor, mark),
+            _ => ()
+        }
+        match *event {
+            YamlEvent::YamlStreamStartEvent(_) | YamlEvent::YamlStreamEndEvent => (),
+            _ => self.check_indentation(mark)
+        }
+    }
+
+    fn check_cancel(&mut self) -> bool {
+        let cancel = match self.cancel_hook {
+            Some(ref mut hook) => hook.should_cancel(),
+            None => false
+        };
+        if cancel {
+            self.cancelled = true;
+        }
+        self.cancelled
+    }
+
+    unsafe fn check_event(&mut self, event: &ffi::yaml_event_t) -> bool {
+        if event.event_type == ffi::yaml_event_type_t::YAML_DOCUMENT_START_EVENT {
+            self.document_events = 0;
+        }
+        self.document_events += 1;
+        let mark = YamlMark::conv(&event.start_mark);
+        match (self.input, self.limits.input_bytes) {
+            (Some((_, len)), Some(limit)) if len > limit => {
+                self.rejection = Some((ffi::YamlErrorType::YAML_READER_ERROR, YamlErrorCode::InputLimitExceeded,
+                                       format!("input is larger than {} bytes", limit), None));
+                return false;
+            },
+            _ => ()
+        }
+        match self.limits.document_events {
+            Some(limit) if self.document_events > limit => {
+                self.rejection = Some((ffi::YamlErrorType::YAML_PARSER_ERROR, YamlErrorCode::EventLimitExceeded,
+                                       format!("document has more than {} events", limit), Some(mark)));
+                return false;
+            },
+            _ => ()
+        }
+
+        match event.event_type {
+            ffi::yaml_event_type_t::YAML_SEQUENCE_START_EVENT | ffi::yaml_event_type_t::YAML_MAPPING_START_EVENT =>
+                self.depth += 1,
+            ffi::yaml_event_type_t::YAML_SEQUENCE_END_EVENT | ffi::yaml_event_type_t::YAML_MAPPING_END_EVENT =>
+                self.depth -= 1,
+            _ => ()
+        }
+        match self.limits.depth {
+            Some(limit) if self.depth > limit => {
+                self.rejection = Some((ffi::YamlErrorType::YAML_PARSER_ERROR, YamlErrorCode::DepthLimitExceeded,
+                                       format!("collections are nested more than {} deep", limit), Some(mark)));
+                return false;
+            },
+            _ => ()
+        }
+
+        if self.anchors == YamlAnchors::RejectAnchors && has_anchor(event) {
+            self.rejection = Some((ffi::YamlErrorType::YAML_PARSER_ERROR, YamlErrorCode::AnchorRejected,
+                                   "found an anchor or alias".to_string(), Some(mark)));
+            return false;
+        }
+
+        if self.tags == YamlTags::CoreTagsOnly {
+            match codecs::decode_c_str(event_tag(event)) {
+                Some(ref tag) if !is_core_tag(tag.as_slice()) => {
+                    self.rejection = Some((ffi::YamlErrorType::YAML_PARSER_ERROR, YamlErrorCode::TagRejected,
+                                           format!("found tag {} outside the core schema", tag), Some(mark)));
+                    return false;
+                },
+                _ => ()
+            }
+        }
+
+        // checked before the value is copied out of the event, but after
+        // libyaml has already scanned and buffered all of it
+        match self.limits.scalar_bytes {
+            Some(limit) if event.event_type == ffi::yaml_event_type_t::YAML_SCALAR_EVENT => {
+                let data: &ffi::yaml_scalar_event_t = mem::transmute(&event.data);
+                if data.length as uint > limit {
+                    self.rejection = Some((ffi::YamlErrorType::YAML_PARSER_ERROR, YamlErrorCode::ScalarLimitExceeded,
+                                           format!("scalar is longer than {} bytes", limit), Some(mark)));
+                    return false;
+                }
+            },
+            _ => ()
+        }
+
+        // after the length check, and only for scalars short enough or plain
+        // enough to be a 1.1 construct, so long values are never decoded here
+        if self.strictness == YamlStrictness::RejectYaml11
+            && event.event_type == ffi::yaml_event_type_t::YAML_SCALAR_EVENT {
+            let data: &ffi::yaml_scalar_event_t = mem::transmute(&event.data);
+            if data.tag == ptr::null() && data.style == ffi::YamlScalarStyle::YamlPlainScalarStyle
+                && may_be_yaml11_construct(CVec::new(data.value as *mut u8, data.length as uint).as_slice()) {
+                match codecs::decode_buf(data.value, data.length) {
+                    Some(ref value) => match yaml11_construct(value.as_slice()) {
+                        Some(construct) => {
+                            self.rejection = Some((ffi::YamlErrorType::YAML_PARSER_ERROR, YamlErrorCode::Yaml11Rejected,
+                                                   yaml11_message(construct, value.as_slice()), Some(mark)));
+                            return false;
+                        },
+                        None => ()
+                    },
+                    None => ()
+                }
+            }
+        }
+        true
+    }
+
+    unsafe fn parse(&mut self, event: &mut ffi::yaml_event_t) -> bool {
+        if self.check_cancel() || self.rejection.is_some()
+            || ffi::yaml_parser_parse(&mut self.parser_mem, event) == 0 {
+            return false;
+        }
+        if !self.check_event(event) {
+            event.delete();
+            return false;
+        }
+        true
+    }
+
+    pub unsafe fn scan(&mut self, token: &mut ffi::yaml_token_t) -> bool {
+        !self.check_cancel() && ffi::yaml_parser_scan(&mut self.parser_mem, token) != 0
+    }
+
+    unsafe fn build_error(&self) -> YamlError {
+        if self.cancelled {
+            return YamlError {
+                kind: ffi::YamlErrorType::YAML_PARSER_ERROR,
+                code: YamlErrorCode::Cancelled,
+                problem: Some("the cancel hook requested an abort".to_string()),
+                io_error: None,
+                context: None,
+                filename: self.filename.clone()
+            };
+        }
+
+        match self.rejection {
+            Some((kind, code, ref message, mark)) => return YamlError {
+                kind: kind,
+                code: code,
+                problem: Some(message.clone()),
+                io_error: None,
+                context: mark.map(|mark| YamlErrorContext {
+                    byte_offset: mark.index,
+                    problem_mark: mark,
+                    context: None,
+                    context_mark: mark
+                }),
+                filename: self.filename.clone()
+            },
+            None => ()
+        }
+
+        let context = YamlErrorContext {
+            byte_offset: self.parser_mem.problem_offset as uint,
+            problem_mark: YamlMark::conv(&self.parser_mem.problem_mark),
+            context: codecs::decode_c_str(self.parser_mem.context as *const ffi::yaml_char_t),
+            context_mark: YamlMark::conv(&self.parser_mem.context_mark),
+        };
+
+        YamlError {
+            kind: self.parser_mem.error,
+            code: YamlErrorCode::from_kind(self.parser_mem.error),
+            problem: codecs::decode_c_str(self.parser_mem.problem as *const ffi::yaml_char_t),
+            io_error: None,
+            context: Some(context),
+            filename: self.filename.clone()
+        }
+    }
+}
+
+impl Drop for YamlBaseParser {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::yaml_parser_delete(&mut self.parser_mem);
+        }
+    }
+}
+
+pub struct YamlByteParser<'r> {
+    base_parser: YamlBaseParser
+}
+
+impl<'r> YamlParser for YamlByteParser<'r> {
+    unsafe fn base_parser_ref<'a>(&'a mut self) -> &'a mut YamlBaseParser {
+        // libyaml points string input back at the parser itself, which may have moved
+        let parser_mem: *const ffi::yaml_parser_t = &self.base_parser.parser_mem;
+        self.base_parser.parser_mem.read_handler_data = parser_mem as *const libc::c_void;
+        &mut self.base_parser
+    }
+
+    unsafe fn get_error(&mut self) -> YamlError {
+        self.base_parser.build_error()
+    }
+}
+
+impl<'r> YamlByteParser<'r> {
+    pub fn init(bytes: &'r [u8], encoding: ffi::YamlEncoding) -> Result<Box<YamlByteParser<'r>>, YamlError> {
+        YamlByteParser::new(bytes, encoding).map(|parser| box parser)
+    }
+
+    pub fn new(bytes: &'r [u8], encoding: ffi::YamlEncoding) -> Result<YamlByteParser<'r>, YamlError> {
+        unsafe {
+            let mut parser = YamlByteParser {
+                base_parser: YamlBaseParser::new(encoding)
+            };
+
+            if !parser.base_parser.initialize() {
+                return Err(initialize_error());
+            }
+
+            parser.base_parser.set_input_string(bytes.as_ptr(), bytes.len());
+            parser.base_parser.bom = starts_with_bom(bytes);
+
+            Ok(parser)
+        }
+    }
+
+    pub fn reset(&mut self, bytes: &'r [u8]) -> Result<(), YamlError> {
+        unsafe {
+            if !self.base_parser.reset() {
+                return Err(initialize_error());
+            }
+            self.base_parser.set_input_string(bytes.as_ptr(), bytes.len());
+            self.base_parser.bom = starts_with_bom(bytes);
+        }
+        Ok(())
+    }
+}
+
+pub struct YamlIoParser<R> {
+    base_parser: YamlBaseParser,
+    reader: R,
+    io_error: Option<IoError>,
+    utf8_aligned: bool,
+    pending: Vec<u8>,
+    head: Vec<u8>,
+    read_buffer_size: Option<uint>,
+    read_buffer: Vec<u8>,
+    read_pos: uint,
+    read_len: uint,
+    total_read: uint,
+    panic: Option<Box<Any + Send>>
+}
+
+fn utf8_boundary(buf: &[u8]) -> uint {
+    let len = buf.len();
+    let mut i = len;
+    while i > 0 && len - i < 4 {
+        i -= 1;
+        let b = buf[i];
+        if b & 0xC0 != 0x80 {
+            let width = if b < 0x80 { 1 } else if b >= 0xF0 { 4 } else if b >= 0xE0 { 3 } else { 2 };
+            return if i + width > len { i } else { len };
+        }
+    }
+    len
+}
+
+impl<R: Reader> YamlParser for YamlIoParser<R> {
+    unsafe fn base_parser_ref<'a>(&'a mut self) -> &'a mut YamlBaseParser {
+        // the read callback finds the reader through this pointer, so keep it current
+        let data: *mut YamlIoParser<R> = self;
+        self.base_parser.parser_mem.read_handler_data = data as *const libc::c_void;
+        &mut self.base_parser
+    }
+
+    unsafe fn get_error(&mut self) -> YamlError {
+        match self.panic.take() {
+            Some(cause) => unwind::begin_unwind(cause, &(file!(), line!())),
+            None => ()
+        }
+        let mut error = self.base_parser.build_error();
+        mem::swap(&mut (error.io_error), &mut (self.io_error));
+        return error;
+    }
+}
+
+impl YamlIoParser<File> {
+    pub fn open(path: &Path, encoding: ffi::YamlEncoding) -> Result<YamlIoParser<File>, YamlError> {
+        let filename = path.display().to_string();
+        let file = try!(File::open(path).map_err(|e| {
+            YamlError::from_io_error(ffi::YamlErrorType::YAML_READER_ERROR, e).with_filename(filename.as_slice())
+        }));
+        let mut parser = try!(YamlIoParser::new(file, encoding));
+        parser.set_filename(Some(filename));
+        Ok(parser)
+    }
+}
+
+impl<R: Reader> YamlIoParser<R> {
+    pub fn init(reader: R, encoding: ffi::YamlEncoding) -> Result<Box<YamlIoParser<R>>, YamlError> {
+        YamlIoParser::new(reader, encoding).map(|parser| box parser)
+    }
+
+    pub fn new(reader: R, encoding: ffi::YamlEncoding) -> Result<YamlIoParser<R>, YamlError> {
+        unsafe {
+            let mut parser = YamlIoParser {
+                base_parser: YamlBaseParser::new(encoding),
+                reader: reader,
+                io_error: None,
+                utf8_aligned: false,
+                pending: Vec::new(),
+                head: Vec::new(),
+                read_buffer_size: None,
+                read_buffer: Vec::new(),
+                read_pos: 0,
+                read_len: 0,
+                total_read: 0,
+                panic: None
+            };
+
+            if !parser.base_parser.initialize() {
+                return Err(initialize_error());
+            }
+
+            ffi::yaml_parser_set_input(&mut parser.base_parser.parser_mem, handle_reader_cb::<R>, ptr::null());
+
+            Ok(parser)
+        }
+    }
+
+    // returns the previous reader; if libyaml cannot be initialized again the
+    // new reader is dropped and the parser only reports that failure
+    pub fn reset(&mut self, reader: R) -> Result<R, YamlError> {
+        unsafe {
+            if !self.base_parser.reset() {
+                return Err(initialize_error());
+            }
+            ffi::yaml_parser_set_input(&mut self.base_parser.parser_mem, handle_reader_cb::<R>, ptr::null());
+        }
+        self.io_error = None;
+        self.panic = None;
+        self.pending.clear();
+        self.head.clear();
+        self.read_pos = 0;
+        self.read_len = 0;
+        self.total_read = 0;
+        Ok(mem::replace(&mut self.reader, reader))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub fn set_utf8_aligned(&mut self, aligned: bool) {
+        self.utf8_aligned = aligned;
+    }
+
+    pub fn set_input_limit(&mut self, limit: Option<uint>) {
+        self.base_parser.limits.input_bytes = limit;
+    }
+
+    pub fn set_stall_limit(&mut self, limit: Option<uint>) {
+        self.base_parser.limits.stalled_reads = limit;
+    }
+
+    pub fn set_stall_timeout(&mut self, timeout: Option<Duration>) {
+        self.base_parser.limits.stall_timeout = timeout;
+    }
+
+    pub fn set_read_buffer_size(&mut self, size: Option<uint>) {
+        self.read_buffer_size = size;
+        self.read_buffer = match size {
+            Some(size) => Vec::from_elem(cmp::max(size, 1), 0u8),
+            None => Vec::new()
+        };
+        self.read_pos = 0;
+        self.read_len = 0;
+    }
+
+    // with a stall limit or timeout, reads that return no data are retried
+    // instead of being taken as the end of the input, sleeping a little longer
+    // after each empty read so a nonblocking reader is not polled in a busy loop
+    fn read_source(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let limits = self.base_parser.limits;
+        if buf.is_empty() || (limits.stalled_reads.is_none() && limits.stall_timeout.is_none()) {
+            return self.reader.read(buf);
+        }
+
+        let deadline = limits.stall_timeout.map(|timeout| time::precise_time_ns() + timeout.num_nanoseconds().unwrap_or(0) as u64);
+        let mut backoff = 1i64;
+        let mut stalls = 0u;
+        loop {
+            match self.reader.read(buf) {
+                Ok(0) => (),
+                Err(ref e) if e.kind == ResourceUnavailable || e.kind == TimedOut => (),
+                res => return res
+            }
+            stalls += 1;
+            match limits.stalled_reads {
+                Some(limit) if stalls > limit => return Err(stall_error(format!("{} consecutive reads returned no data", stalls))),
+                _ => ()
+            }
+
+            let mut pause = Duration::milliseconds(backoff);
+            match deadline {
+                Some(deadline) => {
+                    let now = time::precise_time_ns();
+                    if now >= deadline {
+                        return Err(stall_error(format!("no data arrived within {}", limits.stall_timeout.unwrap())));
+                    }
+                    pause = cmp::min(pause, Duration::nanoseconds((deadline - now) as i64));
+                },
+                None => ()
+            }
+            timer::sleep(pause);
+            backoff = cmp::min(backoff * 2, MAX_STALL_BACKOFF_MS);
+        }
+    }
+
+    fn read_into(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if self.utf8_aligned {
+            self.read_aligned(buf)
+        } else {
+            self.read_source(buf)
+        }
+    }
+
+    fn read_buffered(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if self.read_pos == self.read_len {
+            let mut chunk = mem::replace(&mut self.read_buffer, Vec::new());
+            let res = self.read_into(chunk.as_mut_slice());
+            self.read_buffer = chunk;
+            self.read_pos = 0;
+            self.read_len = 0;
+            self.read_len = try!(res);
+        }
+
+        let available = self.read_buffer.slice(self.read_pos, self.read_len);
+        let mut size = cmp::min(buf.len(), available.len());
+        if self.utf8_aligned && size < available.len() {
+            let cut = utf8_boundary(available.slice_to(size));
+            if cut > 0 {
+                size = cut;
+            }
+        }
+        bytes::copy_memory(buf, available.slice_to(size));
+        self.read_pos += size;
+        Ok(size)
+    }
+
+    fn read_aligned(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        loop {
+            let held = self.pending.len();
+            if held >= buf.len() {
+                // held-back bytes come first, even if buf cannot take a whole character
+                let size = buf.len();
+                bytes::copy_memory(buf, self.pending.slice_to(size));
+                self.pending = self.pending.slice_from(size).to_vec();
+                return Ok(size);
+            }
+            bytes::copy_memory(buf, self.pending.as_slice());
+            self.pending.clear();
+
+            let got = match self.read_source(buf.slice_from_mut(held)) {
+                Ok(got) => got,
+                Err(ref err) if err.kind == EndOfFile && held > 0 => 0,
+                Err(err) => return Err(err)
+            };
+            if got == 0 {
+                return Ok(held);
+            }
+
+            let total = held + got;
+            let cut = utf8_boundary(buf.slice_to(total));
+            self.pending.push_all(buf.slice(cut, total));
+            if cut > 0 {
+                return Ok(cut);
+            }
+        }
+    }
+} 
+
+#[derive(Show, PartialEq, Copy)]
+pub struct YamlParserBuilder {
+    encoding: ffi::YamlEncoding,
+    utf8_aligned: bool,
+    read_buffer_size: Option<uint>,
+    duplicate_keys: YamlDuplicateKeys,
+    duplicate_anchors: YamlDuplicateAnchors,
+    anchors: YamlAnchors,
+    tags: YamlTags,
+    strictness: YamlStrictness,
+    resolver: YamlResolver,
+    limits: YamlResourceLimits
+}
+
+impl YamlParserBuilder {
+    pub fn new() -> YamlParserBuilder {
+        YamlParserBuilder {
+            encoding: ffi::YamlEncoding::YamlAnyEncoding,
+            utf8_aligned: false,
+            read_buffer_size: None,
+            duplicate_keys: YamlDuplicateKeys::AllowDuplicateKeys,
+            duplicate_anchors: YamlDuplicateAnchors::AllowDuplicateAnchors,
+            anchors: YamlAnchors::AllowAnchors,
+            tags: YamlTags::AllowAllTags,
+            strictness: YamlStrictness::WarnYaml11,
+            resolver: YamlResolver::new(YamlSchema::Yaml11),
+            limits: YamlResourceLimits::new()
+        }
+    }
+
+    pub fn encoding(mut self, encoding: ffi::YamlEncoding) -> YamlParserBuilder {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn utf8_aligned(mut self, aligned: bool) -> YamlParserBuilder {
+        self.utf8_aligned = aligned;
+        self
+    }
+
+    pub fn read_buffer_size(mut self, size: uint) -> YamlParserBuilder {
+        self.read_buffer_size = Some(size);
+        self
+    }
+
+    pub fn stall_limit(mut self, limit: uint) -> YamlParserBuilder {
+        self.limits.stalled_reads = Some(limit);
+        self
+    }
+
+    pub fn stall_timeout(mut self, timeout: Duration) -> YamlParserBuilder {
+        self.limits.stall_timeout = Some(timeout);
+        self
+    }
+
+    pub fn duplicate_keys(mut self, policy: YamlDuplicateKeys) -> YamlParserBuilder {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    pub fn duplicate_anchors(mut self, policy: YamlDuplicateAnchors) -> YamlParserBuilder {
+        self.duplicate_anchors = policy;
+        self
+    }
+
+    pub fn anchors(mut self, policy: YamlAnchors) -> YamlParserBuilder {
+        self.anchors = policy;
+        self
+    }
+
+    pub fn tags(mut self, policy: YamlTags) -> YamlParserBuilder {
+        self.tags = policy;
+        self
+    }
+
+    pub fn strictness(mut self, strictness: YamlStrictness) -> YamlParserBuilder {
+        self.strictness = strictness;
+        self
+    }
+
+    pub fn schema(mut self, schema: YamlSchema) -> YamlParserBuilder {
+        self.resolver = YamlResolver::new(schema);
+        self
+    }
+
+    pub fn resolver(mut self, resolver: YamlResolver) -> YamlParserBuilder {
+        self.resolver = resolver;
+        self
+    }
+
+    pub fn limits(mut self, limits: YamlResourceLimits) -> YamlParserBuilder {
+        self.limits = limits;
+        self
+    }
+
+    pub fn node_limit(mut self, limit: uint) -> YamlParserBuilder {
+        self.limits.document_nodes = Some(limit);
+        self
+    }
+
+    pub fn depth_limit(mut self, limit: uint) -> YamlParserBuilder {
+        self.limits.depth = Some(limit);
+        self
+    }
+
+    pub fn event_limit(mut self, limit: uint) -> YamlParserBuilder {
+        self.limits.document_events = Some(limit);
+        self
+    }
+
+    pub fn scalar_limit(mut self, limit: uint) -> YamlParserBuilder {
+        self.limits.scalar_bytes = Some(limit);
+        self
+    }
+
+    pub fn input_limit(mut self, limit: uint) -> YamlParserBuilder {
+        self.limits.input_bytes = Some(limit);
+        self
+    }
+
+    pub fn bytes<'r>(&self, bytes: &'r [u8]) -> Result<YamlByteParser<'r>, YamlError> {
+        let mut parser = try!(YamlByteParser::new(bytes, self.encoding));
+        parser.set_duplicate_key_policy(self.duplicate_keys);
+        parser.set_duplicate_anchor_policy(self.duplicate_anchors);
+        parser.set_anchor_policy(self.anchors);
+        parser.set_tag_policy(self.tags);
+        parser.set_strictness(self.strictness);
+        parser.set_resolver(self.resolver);
+        parser.set_limits(self.limits);
+        Ok(parser)
+    }
+
+    pub fn reader<R: Reader>(&self, reader: R) -> Result<YamlIoParser<R>, YamlError> {
+        let mut parser = try!(YamlIoParser::new(reader, self.encoding));
+        parser.set_utf8_aligned(self.utf8_aligned);
+        parser.set_read_buffer_size(self.read_buffer_size);
+        parser.set_duplicate_key_policy(self.duplicate_keys);
+        parser.set_duplicate_anchor_policy(self.duplicate_anchors);
+        parser.set_anchor_policy(self.anchors);
+        parser.set_tag_policy(self.tags);
+        parser.set_strictness(self.strictness);
+        parser.set_resolver(self.resolver);
+        parser.set_limits(self.limits);
+        Ok(parser)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use event::{YamlEvent, YamlMarkedEvent, YamlSequenceParam, YamlScalarParam};
+    use event::YamlEvent::*;
+    use document::{YamlDocument, YamlNode};
+    use parser;
+    use parser::YamlParser;
+    use error::{YamlError, YamlErrorCode, YamlMark};
+    use ffi;
+    use ffi::YamlErrorType;
+    use ffi::YamlEncoding::*;
+    use ffi::YamlScalarStyle::*;
+    use ffi::YamlSequenceStyle::*;
+    use schema::YamlSchema;
+    use diagnostic::YamlWarningKind;
+    use constructor::{YamlConstructor, YamlStandardConstructor};
+    use constructor::YamlStandardData::*;
+    use std::cmp;
+    use std::io;
+    use std::uint;
+    use std::time::Duration;
+
+    #[test]
+    fn test_byte_parser() {
+        let data = "[1, 2, 3]";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let expected = Ok(vec![
+            YamlStreamStartEvent(YamlUtf8Encoding),
+            YamlDocumentStartEvent(None, vec![], true),
+            YamlSequenceStartEvent(YamlSequenceParam{anchor: None, tag: None, implicit: true, style: YamlFlowSequenceStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "1".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "2".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "3".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlSequenceEndEvent,
+            YamlDocumentEndEvent(true),
+            YamlStreamEndEvent
+        ]);
+
+        let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+
+        assert_eq!(expected, stream);
+    }
+
+    #[test]
+    fn test_io_parser() {
+        let data = "[1, 2, 3]";
+        let reader = io::BufReader::new(data.as_bytes());
+        let parser = parser::YamlIoParser::init(reader, YamlUtf8Encoding).unwrap();
+        let expected = Ok(vec![
+            YamlStreamStartEvent(YamlUtf8Encoding),
+            YamlDocumentStartEvent(None, vec![], true),
+            YamlSequenceStartEvent(YamlSequenceParam{anchor: None, tag: None, implicit: true, style: YamlFlowSequenceStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "1".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "2".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "3".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlSequenceEndEvent,
+            YamlDocumentEndEvent(true),
+            YamlStreamEndEvent
+        ]);
+
+        let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+
+        assert_eq!(expected, stream);
+    }
+
+    struct ChunkReader<'a> {
+        data: &'a [u8],
+        pos: uint,
+        chunk: uint
+    }
+
+    impl<'a> Reader for ChunkReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+            if self.pos == self.data.len() {
+                return Err(io::standard_error(io::EndOfFile));
+            }
+            let size = ::std::cmp::min(::std::cmp::min(self.chunk, buf.len()), self.data.len() - self.pos);
+            ::std::slice::bytes::copy_memory(buf, self.data.slice(self.pos, self.pos + size));
+            self.pos += size;
+            Ok(size)
+        }
+    }
+
+    #[test]
+    fn test_io_parser_into_inner() {
+        let data = "[a, b]\n";
+        let mut reader = ChunkReader { data: data.as_bytes(), pos: 0, chunk: 2 };
+        {
+            let by_ref: &mut Reader = &mut reader;
+            let parser = parser::YamlIoParser::init(by_ref, YamlUtf8Encoding).unwrap();
+            assert_eq!(1, parser.load_all().unwrap().len());
+        }
+        assert_eq!(data.len(), reader.pos);
+
+        let reader = ChunkReader { data: data.as_bytes(), pos: 0, chunk: 3 };
+        let mut parser = parser::YamlIoParser::new(reader, YamlUtf8Encoding).unwrap();
+        assert_eq!(8, parser.events().count());
+        assert_eq!(data.len(), parser.into_inner().pos);
+    }
+
+    struct RecordingReader<'a> {
+        data: &'a [u8],
+        pos: uint,
+        requests: Vec<uint>
+    }
+
+    impl<'a> Reader for RecordingReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+            self.requests.push(buf.len());
+            if self.pos == self.data.len() {
+                return Err(io::standard_error(io::EndOfFile));
+            }
+            let size = ::std::cmp::min(buf.len(), self.data.len() - self.pos);
+            ::std::slice::bytes::copy_memory(buf, self.data.slice(self.pos, self.pos + size));
+            self.pos += size;
+            Ok(size)
+        }
+    }
+
+    #[test]
+    fn test_read_buffer_size() {
+        let data = "- \u2603 one\n- two\n- [three, four]\n";
+        let expected: Result<Vec<YamlEvent>, YamlError> =
+            parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+
+        for &(size, aligned) in [(3u, false), (3u, true), (1u << 20, false)].iter() {
+            let reader = RecordingReader { data: data.as_bytes(), pos: 0, requests: Vec::new() };
+            let mut parser = parser::YamlParserBuilder::new()
+                .read_buffer_size(size)
+                .utf8_aligned(aligned)
+                .reader(reader).unwrap();
+            let stream: Result<Vec<YamlEvent>, YamlError> = parser.events().collect();
+            assert_eq!(expected, stream);
+
+            let reader = parser.into_inner();
+            assert!(reader.requests.iter().all(|&len| len <= size));
+            assert!(reader.requests.contains(&size));
+        }
+    }
+
+    #[test]
+    fn test_utf8_boundary() {
+        let snowman = "a\u2603".as_bytes();
+        assert_eq!(4, parser::utf8_boundary(snowman));
+        assert_eq!(1, parser::utf8_boundary(snowman.slice_to(3)));
+        assert_eq!(1, parser::utf8_boundary(snowman.slice_to(2)));
+        assert_eq!(1, parser::utf8_boundary(snowman.slice_to(1)));
+        assert_eq!(0, parser::utf8_boundary("\U0001F600".as_bytes().slice_to(3)));
+    }
+
+    #[test]
+    fn test_utf8_aligned_reads() {
+        let data = "- \ud55c\uae00 \u2603\n- \U0001F600\U0001F601\n- \"\u00e9\u00e8\"\n";
+        let expected: Result<Vec<YamlEvent>, YamlError> =
+            parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+
+        for chunk in range(1u, 6) {
+            let reader = ChunkReader { data: data.as_bytes(), pos: 0, chunk: chunk };
+            let mut parser = parser::YamlIoParser::init(reader, YamlUtf8Encoding).unwrap();
+            parser.set_utf8_aligned(true);
+            let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+            assert_eq!(expected, stream);
+        }
+    }
+
+    #[test]
+    fn test_utf8_aligned_small_buffer() {
+        let data = "a\u2603b\U0001F600";
+        let reader = ChunkReader { data: data.as_bytes(), pos: 0, chunk: 2 };
+        let mut parser = parser::YamlIoParser::new(reader, YamlUtf8Encoding).unwrap();
+        parser.set_utf8_aligned(true);
+
+        let mut out = Vec::new();
+        let mut buf = [0u8, ..2];
+        loop {
+            match parser.read_aligned(&mut buf) {
+                Ok(0) => break,
+                Ok(size) => out.push_all(buf.slice_to(size)),
+                Err(ref e) if e.kind == io::EndOfFile => break,
+                Err(e) => panic!("unexpected error: {}", e)
+            }
+        }
+        assert_eq!(data.as_bytes(), out.as_slice());
+    }
+
+    #[test]
+    fn test_byte_parser_mapping() {
+        let data = "{\"a\": 1, \"b\":2}";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let expected = Ok(vec![
+            YamlStreamStartEvent(YamlUtf8Encoding),
+            YamlDocumentStartEvent(None, vec![], true),
+            YamlMappingStartEvent(YamlSequenceParam{anchor: None, tag: None, implicit: true, style: YamlFlowSequenceStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "a".to_string(), plain_implicit: false, quoted_implicit: true, style: YamlDoubleQuotedScalarStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "1".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "b".to_string(), plain_implicit: false, quoted_implicit: true, style: YamlDoubleQuotedScalarStyle}),
+            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "2".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+            YamlMappingEndEvent,
+            YamlDocumentEndEvent(true),
+            YamlStreamEndEvent
+        ]);
+
+        let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+
+        assert_eq!(expected, stream);
+    }
+
+    #[test]
+    fn test_parser_error() {
+        let data = "\"ab";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
+
+        let stream_start = stream.next();
+        assert_eq!(Some(Ok(YamlStreamStartEvent(YamlUtf8Encoding))), stream_start);
+
+        let stream_err = stream.next();
+        match stream_err {
+            Some(Err(err)) => {
+                assert_eq!(YamlErrorType::YAML_SCANNER_ERROR, err.kind);
+                assert_eq!(YamlErrorCode::ScannerError, err.code);
+                assert_eq!("E0101".to_string(), err.code.to_code_string());
+            },
+            evt => panic!("unexpected result: {}", evt),
+        }
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_marked_events() {
+        let data = "a: [b]";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let events: Vec<YamlMarkedEvent> = parser.parse_marked().map(|evt| evt.unwrap()).collect();
+
+        assert_eq!(YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "b".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
+                   events[5].event);
+        assert_eq!(YamlMark { index: 4, line: 0, column: 4 }, events[5].start_mark);
+        assert_eq!(YamlMark { index: 5, line: 0, column: 5 }, events[5].end_mark);
+        assert_eq!(YamlMark { index: 3, line: 0, column: 3 }, events[4].start_mark);
+        assert_eq!(YamlMark { index: 6, line: 0, column: 6 }, events[6].end_mark);
+    }
+
+    #[test]
+    fn test_stack_parser() {
+        let snippets = ["a", "[b, c]", "{d: e}"];
+        for snippet in snippets.iter() {
+            let mut parser = parser::YamlByteParser::new(snippet.as_bytes(), YamlUtf8Encoding).unwrap();
+            {
+                let docs: Result<Vec<Box<YamlDocument>>, YamlError> = parser.documents().collect();
+                assert_eq!(1, docs.unwrap().len());
+            }
+            assert!(parser.documents().next().is_none());
+        }
+
+        let moved = vec![parser::YamlByteParser::new("[x, y]".as_bytes(), YamlUtf8Encoding).unwrap()];
+        for mut parser in moved.into_iter() {
+            let expected: Result<Vec<YamlEvent>, YamlError> =
+                parser::YamlByteParser::init("[x, y]".as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+            let stream: Result<Vec<YamlEvent>, YamlError> = parser.events().collect();
+            assert_eq!(expected, stream);
+        }
+    }
+
+    #[test]
+    fn test_parser_reset() {
+        let first = "[a, b]";
+        let second = "{c: d}";
+        let mut parser = parser::YamlByteParser::new("[unterminated".as_bytes(), YamlUtf8Encoding).unwrap();
+        assert!(parser.documents().next().unwrap().is_err());
+
+        let buffer = parser.base_parser.parser_mem.buffer.start;
+        for data in [first, second].iter() {
+            parser.reset(data.as_bytes()).unwrap();
+            assert_eq!(buffer, parser.base_parser.parser_mem.buffer.start);
+            let expected: Result<Vec<YamlEvent>, YamlError> =
+                parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+            let stream: Result<Vec<YamlEvent>, YamlError> = parser.events().collect();
+            assert_eq!(expected, stream);
+        }
+
+        let mut parser = parser::YamlIoParser::new(io::BufReader::new(first.as_bytes()), YamlUtf8Encoding).unwrap();
+        assert_eq!(1, parser.documents().count());
+        assert!(parser.reset(io::BufReader::new(second.as_bytes())).is_ok());
+        match parser.documents().next() {
+            Some(Ok(doc)) => match doc.root() {
+                Ok(YamlNode::YamlMappingNode(_)) => (),
+                _ => panic!("unexpected result")
+            },
+            _ => panic!("unexpected result")
+        }
+    }
+
+    #[test]
+    fn test_parser_builder() {
+        let data = "{a: 1, a: 2, b: [x], c: {b: 3}}";
+        let mut parser = parser::YamlParserBuilder::new().bytes(data.as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+
+        let builder = parser::YamlParserBuilder::new()
+            .encoding(YamlUtf8Encoding)
+            .duplicate_keys(parser::YamlDuplicateKeys::RejectDuplicateKeys);
+        let mut parser = builder.bytes(data.as_bytes()).unwrap();
+        match parser.documents().next() {
+            Some(Err(e)) => {
+                assert_eq!(YamlErrorCode::DuplicateKey, e.code);
+                assert_eq!(7, e.context.unwrap().problem_mark.column);
+            },
+            _ => panic!("should return an error")
+        }
+
+        let mut parser = builder.utf8_aligned(true).reader(io::BufReader::new("{a: 1, b: {a: 2}}".as_bytes())).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+
+        for data in ["{a: 1, 'a': 2}", "{!!str 1: x, '1': y}"].iter() {
+            let mut parser = builder.bytes(data.as_bytes()).unwrap();
+            match parser.documents().next() {
+                Some(Err(e)) => assert_eq!(YamlErrorCode::DuplicateKey, e.code),
+                _ => panic!("should return an error")
+            }
+        }
+        let mut parser = builder.bytes("{1: x, '1': y, \"a\": z}".as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let data = "[a, [b, c], *x]\n--- [a, b]\n";
+        let mut parser = parser::YamlParserBuilder::new().node_limit(4).bytes(data.as_bytes()).unwrap();
+        match parser.documents().next() {
+            Some(Err(e)) => {
+                assert_eq!(YamlErrorCode::NodeLimitExceeded, e.code);
+                assert!(e.is_limit_exceeded());
+            },
+            _ => panic!("should return an error")
+        }
+
+        let mut parser = parser::YamlParserBuilder::new().node_limit(4).bytes("--- [a, b]\n--- [c, d, e]\n".as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_event_and_input_limits() {
+        let data = "--- [a, b]\n--- [c, d, e]\n";
+        let mut parser = parser::YamlParserBuilder::new().event_limit(6).bytes(data.as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+        match parser.documents().next() {
+            Some(Err(e)) => assert_eq!(YamlErrorCode::EventLimitExceeded, e.code),
+            _ => panic!("should return an error")
+        }
+
+        let builder = parser::YamlParserBuilder::new().input_limit(16).read_buffer_size(4);
+        let mut parser = builder.reader(io::BufReader::new(data.as_bytes())).unwrap();
+        match parser.documents().last() {
+            Some(Err(e)) => {
+                assert_eq!(YamlErrorCode::InputLimitExceeded, e.code);
+                assert!(e.io_error.is_none());
+            },
+            _ => panic!("should return an error")
+        }
+
+        let mut parser = builder.reader(io::BufReader::new("[a, b]".as_bytes())).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let mut limits = parser::YamlResourceLimits::new();
+        limits.input_bytes = Some(8);
+        let builder = parser::YamlParserBuilder::new().limits(limits);
+        assert!(builder.bytes("[a, b]".as_bytes()).unwrap().documents().next().unwrap().is_ok());
+        match builder.bytes("[a, b, c, d]".as_bytes()).unwrap().documents().next() {
+            Some(Err(e)) => assert_eq!(YamlErrorCode::InputLimitExceeded, e.code),
+            _ => panic!("should return an error")
+        }
+
+        limits.input_bytes = None;
+        limits.depth = Some(2);
+        let mut parser = parser::YamlParserBuilder::new().limits(limits).depth_limit(3).bytes("[[[a]]]".as_bytes()).unwrap();
+        assert_eq!(Some(3), parser.limits().depth);
+        assert!(parser.documents().next().unwrap().is_ok());
+        parser.reset("[[[[a]]]]".as_bytes()).unwrap();
+        match parser.documents().next() {
+            Some(Err(e)) => assert_eq!(YamlErrorCode::DepthLimitExceeded, e.code),
+            _ => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_reject_anchors() {
+        let builder = parser::YamlParserBuilder::new().anchors(parser::YamlAnchors::RejectAnchors);
+        let mut parser = builder.bytes("{a: [1, 2], b: !!str 3}".as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+
+        for data in ["a: &x 1", "- &x [1]\n- *x", "b: &y {c: d}"].iter() {
+            let mut parser = builder.bytes(data.as_bytes()).unwrap();
+            match parser.documents().next() {
+                Some(Err(e)) => assert_eq!(YamlErrorCode::AnchorRejected, e.code),
+                _ => panic!("should return an error")
             }
         }
     }
-}
-
-pub struct YamlDocumentStream<P> {
-    parser: Box<P>,
-}
 
-impl<P:YamlParser> Iterator for YamlDocumentStream<P> {
-    type Item = Result<Box<YamlDocument>, YamlError>;
+    #[test]
+    fn test_core_tags_only() {
+        let builder = parser::YamlParserBuilder::new().tags(parser::YamlTags::CoreTagsOnly);
+        let mut parser = builder.bytes("{a: !!str 1, b: ! 2, c: !!seq [3]}".as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
 
-    fn next(&mut self) -> Option<Result<Box<YamlDocument>, YamlError>> {
-        unsafe {
-            match YamlDocument::parser_load(&mut self.parser.base_parser_ref().parser_mem) {
-                Some(doc) => if doc.is_empty() {
-                    None
-                } else {
-                    Some(Ok(doc))
-                },
-                None => Some(Err(self.parser.get_error()))
+        for data in ["a: !local 1", "- !!python/object {}", "%TAG !e! tag:example.com,2000:\n--- !e!point [1, 2]"].iter() {
+            let mut parser = builder.bytes(data.as_bytes()).unwrap();
+            match parser.documents().next() {
+                Some(Err(e)) => assert_eq!(YamlErrorCode::TagRejected, e.code),
+                _ => panic!("should return an error")
             }
         }
     }
-}
 
-pub struct InternalEvent {
-    event_mem: ffi::yaml_event_t
-}
+    #[test]
+    fn test_strictness() {
+        let data = "a: yes\nb: 1:30\nc: {=: 1}\nd: 'no'\n";
+        let mut parser = parser::YamlParserBuilder::new().bytes(data.as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+        let kinds: Vec<YamlWarningKind> = parser.take_warnings().iter().map(|w| w.kind).collect();
+        assert_eq!(vec![YamlWarningKind::DeprecatedBoolean, YamlWarningKind::Yaml11Construct,
+                        YamlWarningKind::Yaml11Construct], kinds);
 
-impl Drop for InternalEvent {
-    fn drop(&mut self) {
-        unsafe {
-            self.event_mem.delete()
+        let builder = parser::YamlParserBuilder::new().strictness(parser::YamlStrictness::AllowYaml11);
+        let mut parser = builder.bytes(data.as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+        assert!(parser.warnings().is_empty());
+
+        let mut many = String::new();
+        for _ in range(0, parser::MAX_WARNINGS + 10) {
+            many.push_str("- yes\n");
         }
-    }
-}
+        let mut parser = parser::YamlParserBuilder::new().bytes(many.as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+        assert_eq!(parser::MAX_WARNINGS, parser.take_warnings().len());
 
-pub trait YamlParser: Sized {
-    unsafe fn base_parser_ref<'r>(&'r mut self) -> &'r mut YamlBaseParser;
-    unsafe fn get_error(&mut self) -> YamlError;
+        let builder = parser::YamlParserBuilder::new().strictness(parser::YamlStrictness::RejectYaml11);
+        let mut parser = builder.bytes(data.as_bytes()).unwrap();
+        match parser.documents().next() {
+            Some(Err(e)) => {
+                assert_eq!(YamlErrorCode::Yaml11Rejected, e.code);
+                assert_eq!(3, e.context.unwrap().problem_mark.column);
+            },
+            _ => panic!("should return an error")
+        }
+        let mut parser = builder.bytes("[true, '1:30', !!str yes]".as_bytes()).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
+    }
 
-    unsafe fn parse_event(&mut self) -> Option<YamlEvent> {
-        let mut event = InternalEvent {
-            event_mem: mem::uninitialized()
-        };
+    #[test]
+    fn test_core_schema() {
+        let data = "[yes, 017, 0o17, ~]";
+        let mut parser = parser::YamlParserBuilder::new().schema(YamlSchema::Core).bytes(data.as_bytes()).unwrap();
+        let doc = parser.documents().next().unwrap().unwrap();
+        let ctor = YamlStandardConstructor::new();
 
-        if !self.base_parser_ref().parse(&mut event.event_mem) {
-            None
-        } else {
-            Some(YamlEvent::load(&event.event_mem))
+        assert_eq!(YamlSchema::Core, doc.schema());
+        assert_eq!(Ok(YamlSequence(vec![YamlString("yes".to_string()), YamlInteger(17), YamlInteger(15), YamlNull])),
+                   ctor.construct(doc.root().unwrap()));
+        match doc.root() {
+            Ok(YamlNode::YamlSequenceNode(seq)) => match seq.values().next() {
+                Some(Ok(YamlNode::YamlScalarNode(scalar))) => assert_eq!(None, scalar.as_bool()),
+                _ => panic!("unexpected node")
+            },
+            _ => panic!("unexpected root")
         }
     }
 
-    fn parse(self: Box<Self>) -> YamlEventStream<Self> {
-        YamlEventStream {
-            parser: self,
+    #[test]
+    fn test_scalar_limit() {
+        let data = "[abc, 'abcd', \"\\u00e9\\u00e9\\u00e9\"]";
+        let builder = parser::YamlParserBuilder::new().scalar_limit(3);
+        let mut parser = builder.bytes(data.as_bytes()).unwrap();
+        let mut events = parser.events();
+        assert!(events.by_ref().take(4).all(|res| res.is_ok()));
+        match events.next() {
+            Some(Err(e)) => {
+                assert_eq!(YamlErrorCode::ScalarLimitExceeded, e.code);
+                assert_eq!(6, e.context.unwrap().problem_mark.column);
+            },
+            evt => panic!("unexpected event: {}", evt)
         }
-    }
+        assert!(events.next().is_none());
 
-    fn load(self: Box<Self>) -> YamlDocumentStream<Self> {
-        YamlDocumentStream {
-            parser: self,
+        let mut parser = parser::YamlParserBuilder::new().scalar_limit(4).bytes(data.as_bytes()).unwrap();
+        match parser.documents().next() {
+            Some(Err(e)) => assert_eq!(YamlErrorCode::ScalarLimitExceeded, e.code),
+            _ => panic!("should return an error")
         }
     }
-}
 
-extern fn handle_reader_cb(data: *mut YamlIoParser, buffer: *mut u8, size: libc::size_t, size_read: *mut libc::size_t) -> libc::c_int {
-    unsafe {
-        let mut buf = CVec::new(buffer, size as uint);
-        let parser = &mut *data;
-        match parser.reader.read(buf.as_mut_slice()) {
-            Ok(size) => {
-                *size_read = size as libc::size_t;
-                return 1;
-            },
-            Err(err) => {
-                match err.kind {
-                    EndOfFile => {
-                        *size_read = 0;
-                        return 1;
-                    },
-                    _ => {
-                        parser.io_error = Some(err);
-                        return 0;
-                    }
-                }
+    fn utf16_bytes(s: &str, big_endian: bool, bom: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let units: Vec<u16> = if bom {
+            Some(0xFEFFu16).into_iter().chain(s.utf16_units()).collect()
+        } else {
+            s.utf16_units().collect()
+        };
+        for unit in units.iter() {
+            let (hi, lo) = ((*unit >> 8) as u8, (*unit & 0xFF) as u8);
+            if big_endian {
+                bytes.push(hi);
+                bytes.push(lo);
+            } else {
+                bytes.push(lo);
+                bytes.push(hi);
             }
         }
+        bytes
     }
-}
 
-pub struct YamlBaseParser {
-    parser_mem: ffi::yaml_parser_t,
-}
+    #[test]
+    fn test_utf16_input() {
+        let data = "- caf\u00e9\n- \U0001F600\n";
+        let expected: Vec<YamlEvent> = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+                                           .skip(1).map(|evt| evt.unwrap()).collect();
 
-impl YamlBaseParser {
-    unsafe fn new() -> YamlBaseParser {
-        YamlBaseParser {
-            parser_mem: mem::uninitialized()
+        let cases = [
+            (false, true, YamlAnyEncoding, YamlUtf16LeEncoding),
+            (true, true, YamlAnyEncoding, YamlUtf16BeEncoding),
+            (false, false, YamlUtf16LeEncoding, YamlUtf16LeEncoding),
+            (true, false, YamlUtf16BeEncoding, YamlUtf16BeEncoding)
+        ];
+        for &(big_endian, bom, requested, detected) in cases.iter() {
+            let bytes = utf16_bytes(data, big_endian, bom);
+
+            let mut events = parser::YamlByteParser::init(bytes.as_slice(), requested).unwrap().parse().map(|evt| evt.unwrap());
+            assert_eq!(Some(YamlStreamStartEvent(detected)), events.next());
+            assert_eq!(expected, events.collect::<Vec<YamlEvent>>());
+
+            let reader = io::BufReader::new(bytes.as_slice());
+            let mut events = parser::YamlIoParser::init(reader, requested).unwrap().parse().map(|evt| evt.unwrap());
+            assert_eq!(Some(YamlStreamStartEvent(detected)), events.next());
+            assert_eq!(expected, events.collect::<Vec<YamlEvent>>());
         }
     }
 
-    unsafe fn initialize(&mut self) -> bool {
-        ffi::yaml_parser_initialize(&mut self.parser_mem) != 0
-    }
+    #[test]
+    fn test_detected_encoding() {
+        let mut parser = parser::YamlByteParser::new("a".as_bytes(), YamlAnyEncoding).unwrap();
+        assert_eq!(None, parser.detected_encoding());
+        assert!(parser.events().next().is_some());
+        assert_eq!(Some(YamlUtf8Encoding), parser.detected_encoding());
+        assert!(!parser.has_bom());
 
-    unsafe fn set_input_string(&mut self, input: *const u8, size: uint) {
-        ffi::yaml_parser_set_input_string(&mut self.parser_mem, input, size as libc::size_t);
+        let bytes = utf16_bytes("a", true, true);
+        let mut parser = parser::YamlByteParser::new(bytes.as_slice(), YamlAnyEncoding).unwrap();
+        assert!(parser.events().next().is_some());
+        assert_eq!(Some(YamlUtf16BeEncoding), parser.detected_encoding());
+        assert!(parser.has_bom());
+
+        let data = "\ufeffa: 1";
+        let reader = ChunkReader { data: data.as_bytes(), pos: 0, chunk: 1 };
+        let mut parser = parser::YamlIoParser::new(reader, YamlAnyEncoding).unwrap();
+        assert_eq!(8, parser.events().count());
+        assert_eq!(Some(YamlUtf8Encoding), parser.detected_encoding());
+        assert!(parser.has_bom());
     }
 
-    unsafe fn parse(&mut self, event: &mut ffi::yaml_event_t) -> bool {
-        ffi::yaml_parser_parse(&mut self.parser_mem, event) != 0
+    #[test]
+    fn test_utf16_odd_length() {
+        let mut bytes = utf16_bytes("a", false, true);
+        bytes.push(0);
+        let parser = parser::YamlByteParser::init(bytes.as_slice(), YamlAnyEncoding).unwrap();
+        let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+
+        match stream {
+            Err(e) => assert_eq!(YamlErrorCode::ReaderError, e.code),
+            Ok(_) => panic!("should return an error")
+        }
     }
 
-    unsafe fn build_error(&self) -> YamlError {
-        let context = YamlErrorContext {
-            byte_offset: self.parser_mem.problem_offset as uint,
-            problem_mark: YamlMark::conv(&self.parser_mem.problem_mark),
-            context: codecs::decode_c_str(self.parser_mem.context as *const ffi::yaml_char_t),
-            context_mark: YamlMark::conv(&self.parser_mem.context_mark),
-        };
+    #[test]
+    fn test_peek_event() {
+        let data = "[a]";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
 
-        YamlError {
-            kind: self.parser_mem.error,
-            problem: codecs::decode_c_str(self.parser_mem.problem as *const ffi::yaml_char_t),
-            io_error: None,
-            context: Some(context)
+        assert_eq!(Some(&Ok(YamlStreamStartEvent(YamlUtf8Encoding))), stream.peek_event());
+        assert_eq!(Some(Ok(YamlStreamStartEvent(YamlUtf8Encoding))), stream.next());
+        assert!(stream.next().unwrap().is_ok());
+
+        match stream.peek_event() {
+            Some(&Ok(YamlSequenceStartEvent(_))) => (),
+            evt => panic!("unexpected result: {}", evt)
+        }
+        let seq_start = stream.next().unwrap().unwrap();
+        let scalar = stream.next().unwrap().unwrap();
+        stream.push_back(scalar);
+        stream.push_back(seq_start);
+
+        match stream.next() {
+            Some(Ok(YamlSequenceStartEvent(_))) => (),
+            evt => panic!("unexpected result: {}", evt)
+        }
+        match stream.next() {
+            Some(Ok(YamlScalarEvent(param))) => assert_eq!("a".to_string(), param.value),
+            evt => panic!("unexpected result: {}", evt)
         }
+        assert_eq!(Some(Ok(YamlSequenceEndEvent)), stream.next());
     }
-}
 
-impl Drop for YamlBaseParser {
-    fn drop(&mut self) {
-        unsafe {
-            ffi::yaml_parser_delete(&mut self.parser_mem);
+    #[test]
+    fn test_skip_node() {
+        let data = "{a: {b: [1, {c: d}], e: *x}, f: g}";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
+
+        for _ in range(0u, 4) {
+            assert!(stream.next().unwrap().is_ok());
         }
+        assert!(stream.skip_node().is_ok());
+        match stream.next() {
+            Some(Ok(YamlScalarEvent(param))) => assert_eq!("f".to_string(), param.value),
+            evt => panic!("unexpected result: {}", evt)
+        }
+        assert!(stream.skip_node().is_ok());
+        assert!(stream.skip_node().is_err());
+        assert_eq!(Some(Ok(YamlMappingEndEvent)), stream.next());
     }
-}
 
-pub struct YamlByteParser<'r> {
-    base_parser: YamlBaseParser
-}
+    #[test]
+    fn test_sequence_items() {
+        let data = "- {id: 1}\n- [a, b]\n- c\n";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
+        let items: Vec<_> = stream.sequence_items().map(|res| res.unwrap()).collect();
 
-impl<'r> YamlParser for YamlByteParser<'r> {
-    unsafe fn base_parser_ref<'a>(&'a mut self) -> &'a mut YamlBaseParser {
-        &mut self.base_parser
+        assert_eq!(3, items.len());
+        match items[2].root() {
+            Ok(YamlNode::YamlScalarNode(scalar)) => assert_eq!("c".to_string(), scalar.get_value()),
+            node => panic!("unexpected node: {}", node)
+        }
+        assert_eq!(Some(Ok(YamlDocumentEndEvent(true))), stream.next());
     }
 
-    unsafe fn get_error(&mut self) -> YamlError {
-        self.base_parser.build_error()
+    #[test]
+    fn test_sequence_items_error() {
+        let data = "a: 1";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
+        let mut items = stream.sequence_items();
+
+        assert!(items.next().unwrap().is_err());
+        assert!(items.next().is_none());
     }
-}
 
-impl<'r> YamlByteParser<'r> {
-    pub fn init(bytes: &'r [u8], encoding: ffi::YamlEncoding) -> Box<YamlByteParser<'r>> {
-        unsafe {
-            let mut parser = box YamlByteParser {
-                base_parser: YamlBaseParser::new()
-            };
+    #[test]
+    fn test_next_event_ref() {
+        let data = "[&a x, !t 'y', *a]";
+        let mut parser = parser::YamlByteParser::new(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.marked_events();
+        let mut values = Vec::new();
+        let mut count = 0u;
 
-            if !parser.base_parser.initialize() {
-                panic!("failed to initialize yaml_parser_t");
+        loop {
+            let event = match stream.next_event_ref() {
+                Some(res) => res.unwrap(),
+                None => break
+            };
+            count += 1;
+            match event.value() {
+                Some(value) => values.push((value.to_string(), event.anchor().map(|a| a.to_string()),
+                                            event.tag().map(|t| t.to_string()))),
+                None => ()
+            }
+            if event.event_type() == ffi::yaml_event_type_t::YAML_ALIAS_EVENT {
+                assert_eq!(Some("a"), event.anchor());
+                assert_eq!(YamlAliasEvent("a".to_string()), event.to_event());
             }
+        }
 
-            ffi::yaml_parser_set_encoding(&mut parser.base_parser.parser_mem, encoding);
-            parser.base_parser.set_input_string(bytes.as_ptr(), bytes.len());
+        assert_eq!(9, count);
+        assert_eq!(vec![("x".to_string(), Some("a".to_string()), None),
+                        ("y".to_string(), None, Some("!t".to_string()))], values);
+        assert!(stream.next_event_ref().is_none());
+        assert!(stream.next().is_none());
+    }
 
-            parser
+    #[test]
+    fn test_next_event_ref_warnings() {
+        let data = "[yes, &a x, &a y]";
+        let mut parser = parser::YamlParserBuilder::new().bytes(data.as_bytes()).unwrap();
+        {
+            let mut stream = parser.marked_events();
+            loop {
+                match stream.next_event_ref() {
+                    Some(res) => { res.unwrap(); },
+                    None => break
+                }
+            }
         }
+
+        let kinds: Vec<YamlWarningKind> = parser.take_warnings().iter().map(|w| w.kind).collect();
+        assert_eq!(vec![YamlWarningKind::DeprecatedBoolean, YamlWarningKind::DuplicateAnchor], kinds);
     }
-}
 
-pub struct YamlIoParser<'r> {
-    base_parser: YamlBaseParser,
-    reader: &'r mut (Reader+'r),
-    io_error: Option<IoError>,
-}
+    struct PanickingReader;
 
-impl<'r> YamlParser for YamlIoParser<'r> {
-    unsafe fn base_parser_ref<'a>(&'a mut self) -> &'a mut YamlBaseParser {
-        &mut self.base_parser
+    impl Reader for PanickingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::IoResult<uint> {
+            panic!("reader failed")
+        }
     }
 
-    unsafe fn get_error(&mut self) -> YamlError {
-        let mut error = self.base_parser.build_error();
-        mem::swap(&mut (error.io_error), &mut (self.io_error));
-        return error;
+    struct StallingReader {
+        reader: io::BufReader<'static>,
+        stalls: uint,
+        stalled: uint
     }
-}
-
-impl<'r> YamlIoParser<'r> {
-    pub fn init<'a>(reader: &'a mut Reader, encoding: ffi::YamlEncoding) -> Box<YamlIoParser<'a>> {
-        unsafe {
-            let mut parser = box YamlIoParser {
-                base_parser: YamlBaseParser::new(),
-                reader: reader,
-                io_error: None
-            };
 
-            if !parser.base_parser.initialize() {
-                panic!("failed to initialize yaml_parser_t");
+    impl Reader for StallingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+            if self.stalled < self.stalls {
+                self.stalled += 1;
+                return Ok(0);
             }
+            self.stalled = 0;
+            self.reader.read(buf.slice_to_mut(cmp::min(buf.len(), 2)))
+        }
+    }
 
-            ffi::yaml_parser_set_encoding(&mut parser.base_parser.parser_mem, encoding);
+    #[test]
+    fn test_stall_limit() {
+        let data = "[a, b, c]";
+        let builder = parser::YamlParserBuilder::new().stall_limit(3);
+        let reader = StallingReader { reader: io::BufReader::new(data.as_bytes()), stalls: 3, stalled: 0 };
+        let mut parser = builder.reader(reader).unwrap();
+        let expected: Vec<_> = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse()
+                                    .map(|evt| evt.unwrap()).collect();
+        let events: Vec<_> = parser.events().map(|evt| evt.unwrap()).collect();
+        assert_eq!(expected, events);
+
+        let reader = StallingReader { reader: io::BufReader::new(data.as_bytes()), stalls: 4, stalled: 0 };
+        let mut parser = builder.reader(reader).unwrap();
+        match parser.documents().next() {
+            Some(Err(e)) => {
+                assert_eq!(YamlErrorCode::ReaderError, e.code);
+                assert_eq!(Some(io::TimedOut), e.io_error_kind());
+            },
+            _ => panic!("should return an error")
+        }
 
-            ffi::yaml_parser_set_input(&mut parser.base_parser.parser_mem, handle_reader_cb, mem::transmute(&mut *parser));
+        let builder = parser::YamlParserBuilder::new().stall_timeout(Duration::milliseconds(50));
+        let reader = StallingReader { reader: io::BufReader::new(data.as_bytes()), stalls: 2, stalled: 0 };
+        let mut parser = builder.reader(reader).unwrap();
+        assert!(parser.documents().next().unwrap().is_ok());
 
-            parser
+        let reader = StallingReader { reader: io::BufReader::new(data.as_bytes()), stalls: uint::MAX, stalled: 0 };
+        let mut parser = builder.reader(reader).unwrap();
+        match parser.documents().next() {
+            Some(Err(e)) => assert_eq!(Some(io::TimedOut), e.io_error_kind()),
+            _ => panic!("should return an error")
         }
     }
-} 
 
-#[cfg(test)]
-mod test {
-    use event::{YamlEvent, YamlSequenceParam, YamlScalarParam};
-    use event::YamlEvent::*;
-    use document::{YamlDocument, YamlNode};
-    use parser;
-    use parser::YamlParser;
-    use error::YamlError;
-    use ffi::YamlErrorType;
-    use ffi::YamlEncoding::*;
-    use ffi::YamlScalarStyle::*;
-    use ffi::YamlSequenceStyle::*;
-    use std::io;
+    #[test]
+    #[should_fail]
+    fn test_reader_panic() {
+        let parser = parser::YamlIoParser::init(PanickingReader, YamlUtf8Encoding).unwrap();
+        parser.load_all().ok();
+    }
 
     #[test]
-    fn test_byte_parser() {
-        let data = "[1, 2, 3]";
-        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
-        let expected = Ok(vec![
-            YamlStreamStartEvent(YamlUtf8Encoding),
-            YamlDocumentStartEvent(None, vec![], true),
-            YamlSequenceStartEvent(YamlSequenceParam{anchor: None, tag: None, implicit: true, style: YamlFlowSequenceStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "1".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "2".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "3".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlSequenceEndEvent,
-            YamlDocumentEndEvent(true),
-            YamlStreamEndEvent
-        ]);
+    fn test_stream_offset() {
+        let data = "- a\n- bb\n- éé\n";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
+        let mut offsets = Vec::new();
 
-        let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+        assert_eq!(0, stream.offset());
+        while stream.next().is_some() {
+            offsets.push(stream.offset());
+        }
 
-        assert_eq!(expected, stream);
+        assert!(offsets.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(data.chars().count(), *offsets.last().unwrap());
+        assert_eq!(data.len(), stream.bytes_read());
     }
 
     #[test]
-    fn test_io_parser() {
-        let data = "[1, 2, 3]";
-        let mut reader = io::BufReader::new(data.as_bytes());
-        let parser = parser::YamlIoParser::init(&mut reader, YamlUtf8Encoding);
-        let expected = Ok(vec![
-            YamlStreamStartEvent(YamlUtf8Encoding),
-            YamlDocumentStartEvent(None, vec![], true),
-            YamlSequenceStartEvent(YamlSequenceParam{anchor: None, tag: None, implicit: true, style: YamlFlowSequenceStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "1".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "2".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "3".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlSequenceEndEvent,
-            YamlDocumentEndEvent(true),
-            YamlStreamEndEvent
-        ]);
+    fn test_cancel_hook() {
+        let data = "[1, 2, 3, 4, 5]";
+        let mut parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut calls = 0u;
+        parser.set_cancel_hook(move || {
+            calls += 1;
+            calls > 4
+        });
 
-        let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+        let res: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+        match res {
+            Err(e) => assert_eq!(YamlErrorCode::Cancelled, e.code),
+            Ok(_) => panic!("should return an error")
+        }
 
-        assert_eq!(expected, stream);
+        let reader = io::BufReader::new(data.as_bytes());
+        let mut parser = parser::YamlIoParser::init(reader, YamlUtf8Encoding).unwrap();
+        parser.set_cancel_hook(|| true);
+        match parser.load_all() {
+            Err(e) => assert_eq!(YamlErrorCode::Cancelled, e.code),
+            Ok(_) => panic!("should return an error")
+        }
     }
 
     #[test]
-    fn test_byte_parser_mapping() {
-        let data = "{\"a\": 1, \"b\":2}";
-        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
-        let expected = Ok(vec![
-            YamlStreamStartEvent(YamlUtf8Encoding),
-            YamlDocumentStartEvent(None, vec![], true),
-            YamlMappingStartEvent(YamlSequenceParam{anchor: None, tag: None, implicit: true, style: YamlFlowSequenceStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "a".to_string(), plain_implicit: false, quoted_implicit: true, style: YamlDoubleQuotedScalarStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "1".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "b".to_string(), plain_implicit: false, quoted_implicit: true, style: YamlDoubleQuotedScalarStyle}),
-            YamlScalarEvent(YamlScalarParam{anchor: None, tag: None, value: "2".to_string(), plain_implicit: true, quoted_implicit: false, style: YamlPlainScalarStyle}),
-            YamlMappingEndEvent,
-            YamlDocumentEndEvent(true),
-            YamlStreamEndEvent
-        ]);
+    fn test_recover_to_next_document() {
+        let data = "a: 1\n--- [1, }\n---\nb: 2\n--- @bad\n--- c\n";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
+        let mut values = Vec::new();
+        let mut error_lines = Vec::new();
 
-        let stream: Result<Vec<YamlEvent>, YamlError> = parser.parse().collect();
+        loop {
+            match stream.next() {
+                Some(Ok(YamlScalarEvent(param))) => values.push(param.value),
+                Some(Ok(_)) => (),
+                Some(Err(e)) => {
+                    error_lines.push(e.context.unwrap().problem_mark.line);
+                    if !stream.recover_to_next_document() {
+                        break;
+                    }
+                },
+                None => break
+            }
+        }
 
-        assert_eq!(expected, stream);
+        assert_eq!(vec![1u, 4], error_lines);
+        assert_eq!(vec!["a".to_string(), "1".to_string(), "1".to_string(),
+                        "b".to_string(), "2".to_string(), "c".to_string()], values);
     }
 
     #[test]
-    fn test_parser_error() {
-        let data = "\"ab";
-        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+    fn test_io_parser_cannot_recover() {
+        let data = "--- [1, }\n--- a\n";
+        let reader = io::BufReader::new(data.as_bytes());
+        let parser = parser::YamlIoParser::init(reader, YamlUtf8Encoding).unwrap();
         let mut stream = parser.parse();
 
-        let stream_start = stream.next();
-        assert_eq!(Some(Ok(YamlStreamStartEvent(YamlUtf8Encoding))), stream_start);
+        assert!(stream.by_ref().any(|res| res.is_err()));
+        assert!(!stream.recover_to_next_document());
+    }
 
-        let stream_err = stream.next();
-        match stream_err {
-            Some(Err(err)) => assert_eq!(YamlErrorType::YAML_SCANNER_ERROR, err.kind),
-            evt => panic!("unexpected result: {}", evt),
-        }
+    #[test]
+    fn test_event_stream_end() {
+        let data = "a";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.parse();
+
+        assert_eq!(5, stream.by_ref().count());
+        assert!(stream.next().is_none());
+        assert!(stream.next().is_none());
     }
 
     #[test]
     fn test_document() {
         let data = "[1, 2, 3]";
-        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
         let docs_res:Result<Vec<Box<YamlDocument>>, YamlError> = parser.load().collect();
 
         match docs_res {
             Err(e) => panic!("unexpected result: {}", e),
-            Ok(docs) => match docs.as_slice().first().and_then(|doc| doc.root()) {
+            Ok(docs) => match docs.as_slice().first().and_then(|doc| doc.root().ok()) {
                 Some(YamlNode::YamlSequenceNode(seq)) => {
                     let values:Vec<String> = seq.values().map(|node| {
-                        match node {
+                        match node.unwrap() {
                             YamlNode::YamlScalarNode(scalar) => scalar.get_value(),
                             _ => panic!("unexpected scalar")
                         }
@@ -354,17 +2552,97 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_load_all() {
+        let data = "a\n--- [b]\n--- {c: d}\n";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let docs = parser.load_all().unwrap();
+
+        assert_eq!(3, docs.len());
+        assert!(docs.iter().all(|doc| doc.root().is_ok()));
+    }
+
+    #[test]
+    fn test_load_stops_after_error() {
+        let data = "a\n--- [b\n--- c\n";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let mut stream = parser.load();
+
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+        assert!(stream.next().is_none());
+
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        assert!(parser.load_all().is_err());
+    }
+
+    #[test]
+    fn test_document_anchor() {
+        let data = "hr:\n  - &SS Sammy Sosa\nrbi:\n  - *SS\n";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+
+        match doc.get_by_anchor("SS") {
+            Some(YamlNode::YamlScalarNode(scalar)) => assert_eq!("Sammy Sosa".to_string(), scalar.get_value()),
+            _ => panic!("unexpected result")
+        }
+        assert!(doc.get_by_anchor("nope").is_none());
+    }
+
+    #[test]
+    fn test_undefined_alias() {
+        let data = "[*a]";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+
+        match parser.load().next() {
+            Some(Err(err)) => assert_eq!(YamlErrorType::YAML_COMPOSER_ERROR, err.kind),
+            _ => panic!("unexpected result")
+        }
+    }
+
+    #[test]
+    fn test_duplicate_anchor() {
+        let data = "[&a 1, &a 2, *a]";
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        match doc.get_by_anchor("a") {
+            Some(YamlNode::YamlScalarNode(scalar)) => assert_eq!("2".to_string(), scalar.get_value()),
+            _ => panic!("unexpected result")
+        }
+
+        let mut parser = parser::YamlParserBuilder::new()
+            .duplicate_anchors(parser::YamlDuplicateAnchors::RejectDuplicateAnchors)
+            .bytes(data.as_bytes()).unwrap();
+        match parser.documents().next() {
+            Some(Err(err)) => {
+                let context = err.context.unwrap();
+                assert_eq!(YamlErrorCode::DuplicateAnchor, err.code);
+                assert_eq!(1, context.context_mark.column);
+                assert_eq!(7, context.problem_mark.column);
+            },
+            _ => panic!("unexpected result")
+        }
+
+        let mut parser = parser::YamlByteParser::new("- [&a 1, &a 2]\n--- &a 3\n".as_bytes(), YamlUtf8Encoding).unwrap();
+        assert!(parser.events().all(|res| res.is_ok()));
+        let warnings = parser.take_warnings();
+        assert_eq!(1, warnings.len());
+        assert_eq!(9, warnings[0].mark.column);
+        assert_eq!(3, warnings[0].related_mark.unwrap().column);
+    }
+
     #[test]
     fn test_mapping_document() {
         let data = "{\"a\": 1, \"b\": 2}";
-        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding);
+        let parser = parser::YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
         let docs_res:Result<Vec<Box<YamlDocument>>, YamlError> = parser.load().collect();
 
         match docs_res {
             Err(e) => panic!("unexpected result: {}", e),
-            Ok(docs) => match docs.as_slice().first().and_then(|doc| doc.root()) {
+            Ok(docs) => match docs.as_slice().first().and_then(|doc| doc.root().ok()) {
                 Some(YamlNode::YamlMappingNode(seq)) => {
-                    let values:Vec<(String, String)> = seq.pairs().map(|(key, value)| {
+                    let values:Vec<(String, String)> = seq.pairs().map(|pair| pair.unwrap()).map(|(key, value)| {
                         (
                             match key {
                                 YamlNode::YamlScalarNode(scalar) => scalar.get_value(),
@@ -0,0 +1,20 @@
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+
+// dotted paths shared by the round-trip oracle and diff reports
+pub fn child_path(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}.{}", path, segment)
+    }
+}
+
+pub fn key_segment(key: &YamlStandardData, index: uint) -> String {
+    match *key {
+        YamlString(ref s) => s.clone(),
+        YamlInteger(i) => i.to_string(),
+        YamlBool(b) => b.to_string(),
+        _ => format!("<key {}>", index)
+    }
+}
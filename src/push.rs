@@ -0,0 +1,438 @@
+use ffi;
+use ffi::YamlErrorType;
+use error::{YamlError, YamlErrorCode};
+use event::YamlEvent;
+use parser::{YamlParser, YamlIoParser, YamlResourceLimits};
+use codecs;
+
+use std::cell::Cell;
+use std::cmp;
+use std::io;
+use std::io::{IoResult, EndOfFile, ResourceUnavailable};
+use std::slice::bytes;
+use std::collections::RingBuf;
+
+#[derive(Show, PartialEq)]
+pub enum YamlPoll<T> {
+    Ready(T),
+    Pending
+}
+
+struct ReplayReader<'a> {
+    data: &'a [u8],
+    pos: uint,
+    exhausted: &'a Cell<bool>
+}
+
+impl<'a> Reader for ReplayReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if self.pos == self.data.len() {
+            self.exhausted.set(true);
+            return Err(io::standard_error(EndOfFile));
+        }
+
+        let size = cmp::min(buf.len(), self.data.len() - self.pos);
+        bytes::copy_memory(buf, self.data.slice(self.pos, self.pos + size));
+        self.pos += size;
+        Ok(size)
+    }
+}
+
+fn input_limit_error(limit: uint) -> YamlError {
+    YamlError {
+        kind: YamlErrorType::YAML_READER_ERROR,
+        code: YamlErrorCode::InputLimitExceeded,
+        problem: Some(format!("buffered input is larger than {} bytes", limit)),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+// every fill parses the document in progress again from its first byte, so a
+// single large document fed in small chunks costs time quadratic in its size;
+// input_bytes in the limits caps how much is held back
+pub struct YamlPushParser {
+    encoding: ffi::YamlEncoding,
+    buffer: Vec<u8>,
+    eof: bool,
+    finished: bool,
+    stale: bool,
+    delivered: uint,
+    limits: YamlResourceLimits,
+    ready: RingBuf<Result<YamlEvent, YamlError>>
+}
+
+impl YamlPushParser {
+    pub fn new(encoding: ffi::YamlEncoding) -> YamlPushParser {
+        YamlPushParser {
+            encoding: encoding,
+            buffer: Vec::new(),
+            eof: false,
+            finished: false,
+            stale: false,
+            delivered: 0,
+            limits: YamlResourceLimits::new(),
+            ready: RingBuf::new()
+        }
+    }
+
+    // input_bytes bounds the bytes held back for the document in progress,
+    // not the whole stream
+    pub fn set_limits(&mut self, limits: YamlResourceLimits) {
+        self.limits = limits;
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.push_all(data);
+        self.stale = false;
+    }
+
+    pub fn finish(&mut self) {
+        self.eof = true;
+        self.stale = false;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished && self.ready.is_empty()
+    }
+
+    // input held back for the documents that have not been parsed in full
+    pub fn buffered(&self) -> uint {
+        self.buffer.len()
+    }
+
+    // libyaml cannot suspend inside its read callback, so the buffered input is
+    // parsed again and only events produced before the parser ran out of
+    // buffered bytes are trusted. The bytes of finished documents are dropped,
+    // so a parse only covers the document in progress, and nothing is parsed
+    // again until more input arrives.
+    fn fill(&mut self) {
+        if self.finished || self.stale || !self.ready.is_empty() {
+            return;
+        }
+
+        match self.limits.input_bytes {
+            Some(limit) if self.buffer.len() > limit => {
+                self.ready.push_back(Err(input_limit_error(limit)));
+                self.finished = true;
+                return;
+            },
+            _ => ()
+        }
+
+        let mut consumed = None;
+        {
+            let exhausted = Cell::new(false);
+            let reader = ReplayReader {
+                data: self.buffer.as_slice(),
+                pos: 0,
+                exhausted: &exhausted
+            };
+            let mut parser = match YamlIoParser::new(reader, self.encoding) {
+                Ok(parser) => parser,
+                Err(e) => {
+                    self.ready.push_back(Err(e));
+                    self.finished = true;
+                    return;
+                }
+            };
+            parser.set_limits(self.limits);
+            let mut index = 0;
+
+            loop {
+                let res = unsafe { parser.parse_marked_event() };
+                if exhausted.get() && !self.eof {
+                    self.stale = true;
+                    break;
+                }
+
+                match res {
+                    None => {
+                        self.ready.push_back(Err(unsafe { parser.get_error() }));
+                        self.finished = true;
+                        break;
+                    },
+                    Some((YamlEvent::YamlNoEvent, _, _)) => {
+                        self.finished = true;
+                        break;
+                    },
+                    Some((evt, start_mark, _)) => {
+                        // a later document starts with explicit markers, so parsing
+                        // from its first byte yields the same events
+                        let later_document = match evt {
+                            YamlEvent::YamlDocumentStartEvent(..) => index > 1,
+                            _ => false
+                        };
+                        if later_document && parser.detected_encoding() == Some(ffi::YamlEncoding::YamlUtf8Encoding) {
+                            consumed = Some((start_mark.index, index));
+                        }
+                        if index == self.delivered {
+                            self.ready.push_back(Ok(evt));
+                            self.delivered += 1;
+                        }
+                    }
+                }
+                index += 1;
+            }
+        }
+
+        match consumed {
+            Some((chars, skipped)) if !self.finished => {
+                let bom = if self.buffer.starts_with(&[0xEF, 0xBB, 0xBF]) { 3 } else { 0 };
+                let pos = bom + codecs::utf8_byte_offset(self.buffer.slice_from(bom), chars);
+                self.buffer = self.buffer.slice_from(pos).to_vec();
+                // the new stream start stands in for the one already delivered
+                self.delivered = self.delivered - skipped + 1;
+            },
+            _ => ()
+        }
+    }
+
+    pub fn next_event(&mut self) -> YamlPoll<Option<Result<YamlEvent, YamlError>>> {
+        self.fill();
+        match self.ready.pop_front() {
+            Some(res) => YamlPoll::Ready(Some(res)),
+            None if self.finished => YamlPoll::Ready(None),
+            None => YamlPoll::Pending
+        }
+    }
+
+    pub fn drain(&mut self) -> Vec<Result<YamlEvent, YamlError>> {
+        self.fill();
+        let mut events = Vec::new();
+        loop {
+            match self.ready.pop_front() {
+                Some(res) => events.push(res),
+                None => return events
+            }
+        }
+    }
+}
+
+pub struct YamlResumableParser<R> {
+    reader: R,
+    push: YamlPushParser,
+    chunk_size: uint,
+    needs_more_input: bool,
+    failed: bool
+}
+
+impl<R: Reader> YamlResumableParser<R> {
+    pub fn new(reader: R, encoding: ffi::YamlEncoding) -> YamlResumableParser<R> {
+        YamlResumableParser {
+            reader: reader,
+            push: YamlPushParser::new(encoding),
+            chunk_size: 4096,
+            needs_more_input: false,
+            failed: false
+        }
+    }
+
+    pub fn needs_more_input(&self) -> bool {
+        self.needs_more_input
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub fn poll_event(&mut self) -> YamlPoll<Option<Result<YamlEvent, YamlError>>> {
+        self.needs_more_input = false;
+        if self.failed {
+            return YamlPoll::Ready(None);
+        }
+
+        loop {
+            match self.push.next_event() {
+                YamlPoll::Pending => (),
+                ready => return ready
+            }
+
+            let mut chunk = Vec::from_elem(self.chunk_size, 0u8);
+            match self.reader.read(chunk.as_mut_slice()) {
+                Ok(0) => {
+                    self.needs_more_input = true;
+                    return YamlPoll::Pending;
+                },
+                Ok(size) => self.push.feed(chunk.slice_to(size)),
+                Err(ref e) if e.kind == EndOfFile => self.push.finish(),
+                Err(ref e) if e.kind == ResourceUnavailable => {
+                    self.needs_more_input = true;
+                    return YamlPoll::Pending;
+                },
+                Err(e) => {
+                    self.failed = true;
+                    return YamlPoll::Ready(Some(Err(YamlError::from_io_error(YamlErrorType::YAML_READER_ERROR, e))));
+                }
+            }
+        }
+    }
+
+    pub fn next_event(&mut self) -> Option<Result<YamlEvent, YamlError>> {
+        match self.poll_event() {
+            YamlPoll::Ready(res) => res,
+            YamlPoll::Pending => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use push::{YamlPushParser, YamlResumableParser, YamlPoll};
+    use event::YamlEvent;
+    use parser::{YamlParser, YamlByteParser, YamlResourceLimits};
+    use error::{YamlError, YamlErrorCode};
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+    use std::io;
+    use std::io::IoResult;
+
+    struct WouldBlockReader {
+        data: Vec<u8>,
+        pos: uint,
+        blocked: bool
+    }
+
+    impl Reader for WouldBlockReader {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+            self.blocked = !self.blocked;
+            if self.blocked {
+                return Err(io::standard_error(io::ResourceUnavailable));
+            }
+            if self.pos == self.data.len() {
+                return Err(io::standard_error(io::EndOfFile));
+            }
+            buf[0] = self.data[self.pos];
+            self.pos += 1;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_push_parser() {
+        let data = "a: [1, 'two']\n--- b\n";
+        let mut parser = YamlPushParser::new(YamlUtf8Encoding);
+        let mut events = Vec::new();
+
+        assert_eq!(YamlPoll::Pending, parser.next_event());
+        for chunk in data.as_bytes().chunks(3) {
+            parser.feed(chunk);
+            events.extend(parser.drain().into_iter().map(|evt| evt.unwrap()));
+        }
+        parser.finish();
+        events.extend(parser.drain().into_iter().map(|evt| evt.unwrap()));
+
+        let expected: Result<Vec<YamlEvent>, YamlError> =
+            YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+        assert_eq!(expected, Ok(events));
+        assert!(parser.is_finished());
+        assert_eq!(YamlPoll::Ready(None), parser.next_event());
+    }
+
+    #[test]
+    fn test_push_parser_input_limit() {
+        let mut limits = YamlResourceLimits::new();
+        limits.input_bytes = Some(64);
+
+        let mut parser = YamlPushParser::new(YamlUtf8Encoding);
+        parser.set_limits(limits);
+        for _ in range(0u, 50) {
+            parser.feed("--- [a, b]\n".as_bytes());
+            assert!(parser.drain().iter().all(|evt| evt.is_ok()));
+        }
+
+        let mut parser = YamlPushParser::new(YamlUtf8Encoding);
+        parser.set_limits(limits);
+        let mut events = Vec::new();
+        for _ in range(0u, 50) {
+            parser.feed("- item\n".as_bytes());
+            events.extend(parser.drain().into_iter());
+        }
+        match events.last() {
+            Some(&Err(ref e)) => assert_eq!(YamlErrorCode::InputLimitExceeded, e.code),
+            _ => panic!("should return an error")
+        }
+        assert!(parser.is_finished());
+    }
+
+    #[test]
+    fn test_push_parser_drops_parsed_documents() {
+        let doc = "--- {a: [1, 'two']}\n";
+        let mut parser = YamlPushParser::new(YamlUtf8Encoding);
+        let mut events = Vec::new();
+        let mut data = String::new();
+
+        for _ in range(0u, 100) {
+            data.push_str(doc);
+            for chunk in doc.as_bytes().chunks(4) {
+                parser.feed(chunk);
+                events.extend(parser.drain().into_iter().map(|evt| evt.unwrap()));
+            }
+            assert!(parser.buffered() <= 2 * doc.len());
+        }
+        parser.finish();
+        events.extend(parser.drain().into_iter().map(|evt| evt.unwrap()));
+
+        let expected: Result<Vec<YamlEvent>, YamlError> =
+            YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+        assert_eq!(expected, Ok(events));
+    }
+
+    #[test]
+    fn test_push_parser_error() {
+        let mut parser = YamlPushParser::new(YamlUtf8Encoding);
+        parser.feed("[1, 2".as_bytes());
+        assert!(parser.drain().iter().all(|evt| evt.is_ok()));
+
+        parser.finish();
+        let events = parser.drain();
+        assert!(events.last().unwrap().is_err());
+        assert!(parser.is_finished());
+    }
+
+    #[test]
+    fn test_resumable_parser() {
+        let data = "a: [1, 'two']\n";
+        let reader = WouldBlockReader { data: data.as_bytes().to_vec(), pos: 0, blocked: false };
+        let mut parser = YamlResumableParser::new(reader, YamlUtf8Encoding);
+        let mut events = Vec::new();
+        let mut pauses = 0u;
+
+        loop {
+            match parser.next_event() {
+                Some(evt) => events.push(evt.unwrap()),
+                None if parser.needs_more_input() => pauses += 1,
+                None => break
+            }
+        }
+
+        let expected: Result<Vec<YamlEvent>, YamlError> =
+            YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+        assert_eq!(expected, Ok(events));
+        assert!(pauses > 0);
+        assert_eq!(data.len(), parser.into_inner().pos);
+    }
+
+    #[test]
+    fn test_poll_event() {
+        let data = "[1, 2]";
+        let reader = WouldBlockReader { data: data.as_bytes().to_vec(), pos: 0, blocked: false };
+        let mut parser = YamlResumableParser::new(reader, YamlUtf8Encoding);
+        let mut events = Vec::new();
+        let mut pending = 0u;
+
+        loop {
+            match parser.poll_event() {
+                YamlPoll::Pending => pending += 1,
+                YamlPoll::Ready(Some(evt)) => events.push(evt.unwrap()),
+                YamlPoll::Ready(None) => break
+            }
+        }
+
+        let expected: Result<Vec<YamlEvent>, YamlError> =
+            YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap().parse().collect();
+        assert_eq!(expected, Ok(events));
+        assert!(pending > 0);
+        assert_eq!(YamlPoll::Ready(None), parser.poll_event());
+    }
+}
@@ -0,0 +1,218 @@
+use ffi::YamlScalarStyle;
+use error::{YamlError, YamlMark};
+use codecs;
+use constructor;
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+use schema;
+use schema::{YamlResolver, YamlSchema};
+
+// events and nodes keep their own string fields; this is the common form
+// each layer hands out for type-aware work, via `to_scalar`
+#[derive(Show, PartialEq)]
+pub struct YamlScalar {
+    pub value: String,
+    pub tag: Option<String>,
+    pub style: YamlScalarStyle,
+    pub mark: Option<YamlMark>
+}
+
+fn is_non_specific(tag: &Option<String>) -> bool {
+    match *tag {
+        None => true,
+        Some(ref t) => t.as_slice() == "!"
+    }
+}
+
+impl YamlScalar {
+    pub fn new(value: String, tag: Option<String>, style: YamlScalarStyle, mark: Option<YamlMark>) -> YamlScalar {
+        YamlScalar {
+            value: value,
+            tag: tag,
+            style: style,
+            mark: mark
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        self.value.as_slice()
+    }
+
+    pub fn typed_value(&self) -> Result<YamlStandardData, YamlError> {
+        self.typed_value_with(&YamlResolver::new(YamlSchema::Yaml11))
+    }
+
+    pub fn typed_value_with(&self, resolver: &YamlResolver) -> Result<YamlStandardData, YamlError> {
+        let mark = self.mark.unwrap_or(YamlMark { index: 0, line: 0, column: 0 });
+        let tag = if is_non_specific(&self.tag) { None } else { self.tag.as_ref().map(|t| t.as_slice()) };
+        constructor::resolve_tagged_scalar_with(resolver, tag, self.value.clone(), self.style, &mark)
+    }
+
+    fn resolved(&self, resolver: &YamlResolver) -> Option<YamlStandardData> {
+        self.typed_value_with(resolver).ok()
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_i64_with(&YamlResolver::new(YamlSchema::Yaml11))
+    }
+
+    pub fn as_i64_with(&self, resolver: &YamlResolver) -> Option<i64> {
+        match self.resolved(resolver) {
+            Some(YamlInteger(i)) => Some(i as i64),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_f64_with(&YamlResolver::new(YamlSchema::Yaml11))
+    }
+
+    pub fn as_f64_with(&self, resolver: &YamlResolver) -> Option<f64> {
+        match self.resolved(resolver) {
+            Some(YamlFloat(f)) => Some(f),
+            Some(YamlInteger(i)) => Some(i as f64),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_bool_with(&YamlResolver::new(YamlSchema::Yaml11))
+    }
+
+    pub fn as_bool_with(&self, resolver: &YamlResolver) -> Option<bool> {
+        match self.resolved(resolver) {
+            Some(YamlBool(b)) => Some(b),
+            _ => None
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.is_null_with(&YamlResolver::new(YamlSchema::Yaml11))
+    }
+
+    pub fn is_null_with(&self, resolver: &YamlResolver) -> bool {
+        match self.resolved(resolver) {
+            Some(YamlNull) => true,
+            _ => false
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        match self.tag.as_ref().map(|t| t.as_slice()) {
+            Some("tag:yaml.org,2002:binary") => codecs::base64_decode(self.value.as_slice()),
+            _ => None
+        }
+    }
+
+    pub fn resolved_tag(&self) -> Result<String, YamlError> {
+        if !is_non_specific(&self.tag) {
+            return Ok(self.tag.clone().unwrap());
+        }
+
+        Ok(schema::data_tag(&try!(self.typed_value())).to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use scalar::YamlScalar;
+    use parser::{YamlParser, YamlByteParser};
+    use document::YamlNode;
+    use constructor::YamlStandardData::*;
+    use schema::{YamlResolver, YamlSchema};
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+    use ffi::YamlScalarStyle::*;
+
+    #[test]
+    fn test_plain_scalar() {
+        let scalar = YamlScalar::new("0x1F".to_string(), None, YamlPlainScalarStyle, None);
+        assert_eq!("0x1F", scalar.text());
+        assert_eq!(Ok(YamlInteger(31)), scalar.typed_value());
+        assert_eq!(Ok("tag:yaml.org,2002:int".to_string()), scalar.resolved_tag());
+    }
+
+    #[test]
+    fn test_quoted_scalar() {
+        let scalar = YamlScalar::new("1".to_string(), None, YamlSingleQuotedScalarStyle, None);
+        assert_eq!(Ok(YamlString("1".to_string())), scalar.typed_value());
+        assert_eq!(Ok("tag:yaml.org,2002:str".to_string()), scalar.resolved_tag());
+    }
+
+    #[test]
+    fn test_explicit_tag() {
+        let scalar = YamlScalar::new("1".to_string(), Some("!foo".to_string()), YamlPlainScalarStyle, None);
+        assert_eq!(Ok("!foo".to_string()), scalar.resolved_tag());
+    }
+
+    #[test]
+    fn test_event_and_node_agree() {
+        let data = "[~, \"a\\tb\"]";
+        let mut parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let events: Vec<YamlScalar> = parser.marked_events().filter_map(|evt| evt.unwrap().to_scalar()).collect();
+
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let nodes: Vec<YamlScalar> = match doc.root() {
+            Ok(YamlNode::YamlSequenceNode(seq)) => seq.values().map(|node| match node.unwrap() {
+                YamlNode::YamlScalarNode(scalar) => scalar.to_scalar(),
+                _ => panic!("unexpected node")
+            }).collect(),
+            _ => panic!("unexpected root")
+        };
+
+        assert_eq!(2, events.len());
+        assert_eq!(2, nodes.len());
+        for (evt, node) in events.iter().zip(nodes.iter()) {
+            assert_eq!(evt, node);
+        }
+        assert_eq!(Ok(YamlNull), nodes[0].typed_value());
+        assert_eq!(Ok(YamlString("a\tb".to_string())), nodes[1].typed_value());
+        assert_eq!(Some(4), nodes[1].mark.map(|m| m.column));
+    }
+
+    #[test]
+    fn test_explicit_core_tags() {
+        let data = "[!!str 12, !!int \"12\", !!float 3, !!int abc, 12]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let nodes: Vec<YamlScalar> = match doc.root() {
+            Ok(YamlNode::YamlSequenceNode(seq)) => seq.values().map(|node| match node.unwrap() {
+                YamlNode::YamlScalarNode(scalar) => scalar.to_scalar(),
+                _ => panic!("unexpected node")
+            }).collect(),
+            _ => panic!("unexpected root")
+        };
+
+        assert_eq!(Ok(YamlString("12".to_string())), nodes[0].typed_value());
+        assert_eq!(None, nodes[0].as_i64());
+        assert_eq!(Ok(YamlInteger(12)), nodes[1].typed_value());
+        assert_eq!(Ok(YamlFloat(3.0)), nodes[2].typed_value());
+        assert!(nodes[3].typed_value().is_err());
+        assert_eq!(None, nodes[4].tag);
+        assert_eq!(Ok(YamlInteger(12)), nodes[4].typed_value());
+    }
+
+    #[test]
+    fn test_typed_accessors() {
+        let data = "[12, 1.5, yes, ~, '12', !!int '7']";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let scalars: Vec<YamlScalar> = match doc.root() {
+            Ok(YamlNode::YamlSequenceNode(seq)) => seq.values().map(|node| match node.unwrap() {
+                YamlNode::YamlScalarNode(scalar) => scalar.to_scalar(),
+                _ => panic!("unexpected node")
+            }).collect(),
+            _ => panic!("unexpected root")
+        };
+
+        assert_eq!(Some(12), scalars[0].as_i64());
+        assert_eq!(Some(12.0), scalars[0].as_f64());
+        assert_eq!(Some(1.5), scalars[1].as_f64());
+        assert_eq!(None, scalars[1].as_i64());
+        assert_eq!(None, scalars[2].as_bool());
+        assert_eq!(Some(true), scalars[2].as_bool_with(&YamlResolver::new(YamlSchema::Yaml11).with_word_booleans(true)));
+        assert!(scalars[3].is_null());
+        assert_eq!(None, scalars[4].as_i64());
+        assert_eq!(Some(7), scalars[5].as_i64());
+    }
+}
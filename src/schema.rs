@@ -0,0 +1,473 @@
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+
+use std::f64;
+use std::num::{Float, FromStrRadix};
+use regex::Regex;
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlSchema {
+    Yaml11,
+    Core,
+    Json,
+    Failsafe,
+}
+
+static YAML11_BIN_INT:Regex = regex!(r"^([-+]?)0b([0-1_]+)$");
+static YAML11_OCT_INT:Regex = regex!(r"^([-+]?)0([0-7_]+)$");
+static YAML11_OCT_INT_12:Regex = regex!(r"^([-+]?)0o([0-7_]+)$");
+static YAML11_LEADING_ZERO:Regex = regex!(r"^[-+]?0[0-9_]+$");
+static YAML11_DEC_INT:Regex = regex!(r"^([-+]?)(0|[1-9][0-9_]*)$");
+static YAML11_HEX_INT:Regex = regex!(r"^([-+]?)0x([0-9a-fA-F_]+)$");
+static YAML11_BASE60_INT:Regex = regex!(r"^([-+]?)([1-9][0-9_]*(:[0-5]?[0-9])+)$");
+static YAML11_FLOAT:Regex = regex!(r"^([-+]?)(([0-9][0-9_]*)?\.[0-9_]*([eE][-+][0-9]+)?)$");
+static YAML11_BASE60_FLOAT:Regex = regex!(r"^([-+]?)([0-9][0-9_]*(:[0-5]?[0-9])+\.[0-9_]*)$");
+static YAML11_INF:Regex = regex!(r"^([-+]?)\.(inf|Inf|INF)$");
+static YAML11_NAN:Regex = regex!(r"^\.(nan|NaN|NAN)$");
+static YAML11_NULL:Regex = regex!(r"^(~|null|Null|NULL|)$");
+static YAML11_TRUE:Regex = regex!(r"^(true|True|TRUE)$");
+static YAML11_FALSE:Regex = regex!(r"^(false|False|FALSE)$");
+static YAML11_WORD_TRUE:Regex = regex!(r"^(y|Y|yes|Yes|YES|on|On|ON)$");
+static YAML11_WORD_FALSE:Regex = regex!(r"^(n|N|no|No|NO|off|Off|OFF)$");
+
+static CORE_OCT_INT:Regex = regex!(r"^()0o([0-7]+)$");
+static CORE_DEC_INT:Regex = regex!(r"^([-+]?)([0-9]+)$");
+static CORE_HEX_INT:Regex = regex!(r"^()0x([0-9a-fA-F]+)$");
+static CORE_FLOAT:Regex = regex!(r"^([-+]?)((\.[0-9]+|[0-9]+(\.[0-9]*)?)([eE][-+]?[0-9]+)?)$");
+static CORE_NULL:Regex = regex!(r"^(~|null|Null|NULL|)$");
+static CORE_TRUE:Regex = regex!(r"^(true|True|TRUE)$");
+static CORE_FALSE:Regex = regex!(r"^(false|False|FALSE)$");
+
+static JSON_INT:Regex = regex!(r"^(-?)(0|[1-9][0-9]*)$");
+static JSON_FLOAT:Regex = regex!(r"^(-?)((0|[1-9][0-9]*)(\.[0-9]+)?([eE][-+]?[0-9]+)?)$");
+
+fn strip_underscores(digits: &str) -> String {
+    digits.chars().filter(|&c| c != '_').collect()
+}
+
+fn signed_int(sign: &str, digits: &str, radix: uint) -> Option<YamlStandardData> {
+    // parse the sign with the digits, so the most negative value still fits
+    let text = format!("{}{}", if sign == "-" { "-" } else { "" }, strip_underscores(digits));
+    let value: Option<int> = FromStrRadix::from_str_radix(text.as_slice(), radix);
+    value.map(|i| YamlInteger(i))
+}
+
+fn signed_float(sign: &str, digits: &str) -> Option<YamlStandardData> {
+    strip_underscores(digits).parse().map(|f: f64| YamlFloat(if sign == "-" { -f } else { f }))
+}
+
+fn base60_int(sign: &str, digits: &str) -> Option<YamlStandardData> {
+    let mut value = 0i;
+    for part in digits.split(':') {
+        let part: Option<int> = strip_underscores(part).parse();
+        value = match part.and_then(|p| value.checked_mul(60).and_then(|v| v.checked_add(p))) {
+            Some(v) => v,
+            None => return None
+        };
+    }
+    Some(YamlInteger(if sign == "-" { -value } else { value }))
+}
+
+fn base60_float(sign: &str, digits: &str) -> Option<YamlStandardData> {
+    let mut value = 0.0f64;
+    for part in digits.split(':') {
+        let part: Option<f64> = strip_underscores(part).parse();
+        value = match part {
+            Some(p) => value * 60.0 + p,
+            None => return None
+        };
+    }
+    Some(YamlFloat(if sign == "-" { -value } else { value }))
+}
+
+fn resolve_yaml11(value: &str, word_booleans: bool, sexagesimal: bool) -> Option<YamlStandardData> {
+    match YAML11_BIN_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 2),
+        None => ()
+    }
+    // 1.1 octal is a leading zero; the 1.2 0o prefix is accepted as well
+    match YAML11_OCT_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 8),
+        None => ()
+    }
+    match YAML11_OCT_INT_12.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 8),
+        None => ()
+    }
+    // not valid octal, and not a decimal or float either
+    if YAML11_LEADING_ZERO.is_match(value) {
+        return None;
+    }
+    match YAML11_HEX_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 16),
+        None => ()
+    }
+    match YAML11_DEC_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 10),
+        None => ()
+    }
+    match YAML11_BASE60_INT.captures(value) {
+        Some(caps) if sexagesimal => return base60_int(caps.at(1).unwrap(), caps.at(2).unwrap()),
+        _ => ()
+    }
+    match YAML11_FLOAT.captures(value) {
+        Some(caps) => return signed_float(caps.at(1).unwrap(), caps.at(2).unwrap()),
+        None => ()
+    }
+    match YAML11_BASE60_FLOAT.captures(value) {
+        Some(caps) if sexagesimal => return base60_float(caps.at(1).unwrap(), caps.at(2).unwrap()),
+        _ => ()
+    }
+    match YAML11_INF.captures(value) {
+        Some(caps) => return Some(YamlFloat(if caps.at(1).unwrap() == "-" { f64::NEG_INFINITY } else { f64::INFINITY })),
+        None => ()
+    }
+
+    if YAML11_NAN.is_match(value) {
+        Some(YamlFloat(f64::NAN))
+    } else if YAML11_NULL.is_match(value) {
+        Some(YamlNull)
+    } else if YAML11_TRUE.is_match(value) || (word_booleans && YAML11_WORD_TRUE.is_match(value)) {
+        Some(YamlBool(true))
+    } else if YAML11_FALSE.is_match(value) || (word_booleans && YAML11_WORD_FALSE.is_match(value)) {
+        Some(YamlBool(false))
+    } else {
+        None
+    }
+}
+
+fn resolve_core(value: &str) -> Option<YamlStandardData> {
+    match CORE_OCT_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 8),
+        None => ()
+    }
+    match CORE_HEX_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 16),
+        None => ()
+    }
+    match CORE_DEC_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 10),
+        None => ()
+    }
+    match CORE_FLOAT.captures(value) {
+        Some(caps) => return signed_float(caps.at(1).unwrap(), caps.at(2).unwrap()),
+        None => ()
+    }
+    match YAML11_INF.captures(value) {
+        Some(caps) => return Some(YamlFloat(if caps.at(1).unwrap() == "-" { f64::NEG_INFINITY } else { f64::INFINITY })),
+        None => ()
+    }
+
+    if YAML11_NAN.is_match(value) {
+        Some(YamlFloat(f64::NAN))
+    } else if CORE_NULL.is_match(value) {
+        Some(YamlNull)
+    } else if CORE_TRUE.is_match(value) {
+        Some(YamlBool(true))
+    } else if CORE_FALSE.is_match(value) {
+        Some(YamlBool(false))
+    } else {
+        None
+    }
+}
+
+fn resolve_json(value: &str) -> Option<YamlStandardData> {
+    match JSON_INT.captures(value) {
+        Some(caps) => return signed_int(caps.at(1).unwrap(), caps.at(2).unwrap(), 10),
+        None => ()
+    }
+    match JSON_FLOAT.captures(value) {
+        Some(caps) => return signed_float(caps.at(1).unwrap(), caps.at(2).unwrap()),
+        None => ()
+    }
+
+    match value {
+        "null" => Some(YamlNull),
+        "true" => Some(YamlBool(true)),
+        "false" => Some(YamlBool(false)),
+        _ => None
+    }
+}
+
+// the inverse of the float resolution above; integral values keep a
+// trailing .0 so they are not read back as integers
+pub fn float_text(value: f64) -> String {
+    if value.is_nan() {
+        ".nan".to_string()
+    } else if value == f64::INFINITY {
+        ".inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-.inf".to_string()
+    } else {
+        let text = value.to_string();
+        if text.contains_char('.') || text.contains_char('e') {
+            text
+        } else {
+            format!("{}.0", text)
+        }
+    }
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub enum YamlYaml11Construct {
+    WordBoolean,
+    Sexagesimal,
+    ValueKey
+}
+
+// plain scalars whose meaning changed or disappeared in YAML 1.2
+pub fn yaml11_construct(value: &str) -> Option<YamlYaml11Construct> {
+    if YAML11_WORD_TRUE.is_match(value) || YAML11_WORD_FALSE.is_match(value) {
+        Some(YamlYaml11Construct::WordBoolean)
+    } else if YAML11_BASE60_INT.is_match(value) || YAML11_BASE60_FLOAT.is_match(value) {
+        Some(YamlYaml11Construct::Sexagesimal)
+    } else if value == "=" {
+        Some(YamlYaml11Construct::ValueKey)
+    } else {
+        None
+    }
+}
+
+// a cheap filter over the raw bytes: word booleans and "=" are at most five
+// bytes, and anything longer must be made of sexagesimal characters
+pub fn may_be_yaml11_construct(bytes: &[u8]) -> bool {
+    bytes.len() <= 5 || bytes.iter().all(|&b| match b as char {
+        '0'...'9' | '_' | ':' | '.' | '-' | '+' => true,
+        _ => false
+    })
+}
+
+// strings that a 1.1 or core schema reader would load as anything else
+// must be written quoted
+pub fn needs_quotes(value: &str) -> bool {
+    let yaml11 = YamlResolver::new(YamlSchema::Yaml11).with_word_booleans(true).with_sexagesimal(true);
+    let core = YamlResolver::new(YamlSchema::Core);
+    let plain = YamlString(value.to_string());
+    yaml11.resolve_plain(value) != plain || core.resolve_plain(value) != plain
+}
+
+pub fn data_tag(data: &YamlStandardData) -> &'static str {
+    match *data {
+        YamlInteger(_) => "tag:yaml.org,2002:int",
+        YamlFloat(_) => "tag:yaml.org,2002:float",
+        YamlNull => "tag:yaml.org,2002:null",
+        YamlBool(_) => "tag:yaml.org,2002:bool",
+        YamlString(_) => "tag:yaml.org,2002:str",
+        YamlSequence(_) => "tag:yaml.org,2002:seq",
+        YamlMapping(_) => "tag:yaml.org,2002:map",
+        YamlSet(_) => "tag:yaml.org,2002:set",
+        YamlOrderedMap(_) => "tag:yaml.org,2002:omap",
+        YamlPairs(_) => "tag:yaml.org,2002:pairs"
+    }
+}
+
+#[derive(Show, PartialEq, Copy)]
+pub struct YamlResolver {
+    schema: YamlSchema,
+    word_booleans: bool,
+    sexagesimal: bool
+}
+
+impl YamlResolver {
+    pub fn new(schema: YamlSchema) -> YamlResolver {
+        YamlResolver {
+            schema: schema,
+            word_booleans: false,
+            sexagesimal: false
+        }
+    }
+
+    // y/n, yes/no and on/off only resolve to booleans under the 1.1 schema
+    pub fn with_word_booleans(mut self, enabled: bool) -> YamlResolver {
+        self.word_booleans = enabled;
+        self
+    }
+
+    // base-60 values like 1:30:00 are easily confused with times, so they
+    // stay strings unless asked for
+    pub fn with_sexagesimal(mut self, enabled: bool) -> YamlResolver {
+        self.sexagesimal = enabled;
+        self
+    }
+
+    pub fn schema(&self) -> YamlSchema {
+        self.schema
+    }
+
+    pub fn resolve_plain(&self, value: &str) -> YamlStandardData {
+        let resolved = match self.schema {
+            YamlSchema::Yaml11 => resolve_yaml11(value, self.word_booleans, self.sexagesimal),
+            YamlSchema::Core => resolve_core(value),
+            YamlSchema::Json => resolve_json(value),
+            YamlSchema::Failsafe => None
+        };
+        resolved.unwrap_or_else(|| YamlString(value.to_string()))
+    }
+
+    pub fn implicit_tag(&self, value: &str) -> &'static str {
+        data_tag(&self.resolve_plain(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use schema::{YamlResolver, YamlSchema, YamlYaml11Construct, float_text, needs_quotes, yaml11_construct,
+                 may_be_yaml11_construct};
+    use constructor::YamlStandardData::*;
+    use std::f64;
+    use std::int;
+    use std::num::Float;
+
+    #[test]
+    fn test_yaml11_resolver() {
+        let resolver = YamlResolver::new(YamlSchema::Yaml11).with_word_booleans(true).with_sexagesimal(true);
+        let cases = [("yes", YamlBool(true)), ("Off", YamlBool(false)), ("n", YamlBool(false)),
+                     ("~", YamlNull), ("", YamlNull), ("0b101", YamlInteger(5)), ("017", YamlInteger(15)),
+                     ("-0x1F", YamlInteger(-31)), ("1_000", YamlInteger(1000)), ("1:30:00", YamlInteger(5400)),
+                     ("-1:30", YamlInteger(-90)), ("1:30.5", YamlFloat(90.5)), ("-.5", YamlFloat(-0.5)),
+                     ("-.inf", YamlFloat(f64::NEG_INFINITY)), ("yep", YamlString("yep".to_string())),
+                     ("1:60", YamlString("1:60".to_string())), ("1.5e+3", YamlFloat(1500.0)),
+                     ("1e3", YamlString("1e3".to_string())), ("1.0e3", YamlString("1.0e3".to_string())),
+                     (".", YamlString(".".to_string())), ("-0x8000000000000000", YamlInteger(int::MIN))];
+
+        for &(value, ref expected) in cases.iter() {
+            assert_eq!(*expected, resolver.resolve_plain(value));
+        }
+        assert_eq!("tag:yaml.org,2002:bool", resolver.implicit_tag("ON"));
+        assert_eq!("tag:yaml.org,2002:str", resolver.implicit_tag("99999999999999999999999"));
+    }
+
+    #[test]
+    fn test_octal_and_hex() {
+        let yaml11 = YamlResolver::new(YamlSchema::Yaml11);
+        let core = YamlResolver::new(YamlSchema::Core);
+        let cases = [("017", YamlInteger(15), YamlInteger(17)), ("-017", YamlInteger(-15), YamlInteger(-17)),
+                     ("0o17", YamlInteger(15), YamlInteger(15)), ("08", YamlString("08".to_string()), YamlInteger(8)),
+                     ("-0o17", YamlInteger(-15), YamlString("-0o17".to_string())),
+                     ("0x1A", YamlInteger(26), YamlInteger(26)), ("0", YamlInteger(0), YamlInteger(0)),
+                     ("0.5", YamlFloat(0.5), YamlFloat(0.5))];
+
+        for &(value, ref expected_yaml11, ref expected_core) in cases.iter() {
+            assert_eq!(*expected_yaml11, yaml11.resolve_plain(value));
+            assert_eq!(*expected_core, core.resolve_plain(value));
+        }
+    }
+
+    #[test]
+    fn test_yaml11_construct() {
+        assert_eq!(Some(YamlYaml11Construct::WordBoolean), yaml11_construct("Off"));
+        assert_eq!(Some(YamlYaml11Construct::Sexagesimal), yaml11_construct("1:30"));
+        assert_eq!(Some(YamlYaml11Construct::ValueKey), yaml11_construct("="));
+        assert_eq!(None, yaml11_construct("true"));
+        assert_eq!(None, yaml11_construct("08:30"));
+
+        assert!(may_be_yaml11_construct("OFF".as_bytes()));
+        assert!(may_be_yaml11_construct("-190:20:30.15".as_bytes()));
+        assert!(!may_be_yaml11_construct("a much longer plain scalar".as_bytes()));
+    }
+
+    #[test]
+    fn test_float_text() {
+        let resolver = YamlResolver::new(YamlSchema::Core);
+        for &(value, text) in [(f64::INFINITY, ".inf"), (f64::NEG_INFINITY, "-.inf"), (1.0, "1.0"), (-2.5, "-2.5")].iter() {
+            assert_eq!(text, float_text(value).as_slice());
+            assert_eq!(YamlFloat(value), resolver.resolve_plain(text));
+        }
+        assert_eq!(".nan", float_text(f64::NAN).as_slice());
+        match resolver.resolve_plain(".NaN") {
+            YamlFloat(f) => assert!(f.is_nan()),
+            value => panic!("unexpected value: {}", value)
+        }
+    }
+
+    #[test]
+    fn test_underscores() {
+        let resolver = YamlResolver::new(YamlSchema::Yaml11);
+        let cases = [("1_000_000", YamlInteger(1000000)), ("0x_FF", YamlInteger(255)), ("-0b1_0", YamlInteger(-2)),
+                     ("0_17", YamlInteger(15)), ("1_000.5", YamlFloat(1000.5)), (".5_0", YamlFloat(0.5)),
+                     ("0x_", YamlString("0x_".to_string())), ("_1", YamlString("_1".to_string()))];
+
+        for &(value, ref expected) in cases.iter() {
+            assert_eq!(*expected, resolver.resolve_plain(value));
+        }
+        for value in ["1_000_000", "0x_FF", "1_000.5"].iter() {
+            assert_eq!(YamlString(value.to_string()), YamlResolver::new(YamlSchema::Core).resolve_plain(*value));
+        }
+    }
+
+    #[test]
+    fn test_sexagesimal() {
+        let resolver = YamlResolver::new(YamlSchema::Yaml11);
+        let sexagesimal = resolver.with_sexagesimal(true);
+        let cases = [("1:30:00", YamlInteger(5400)), ("12:30", YamlInteger(750)), ("-1:30.5", YamlFloat(-90.5)),
+                     ("08:30", YamlString("08:30".to_string())), ("12:60", YamlString("12:60".to_string())),
+                     ("12:30:45:15", YamlInteger(2702715)), ("2014-12-01 12:30", YamlString("2014-12-01 12:30".to_string()))];
+
+        for &(value, ref expected) in cases.iter() {
+            assert_eq!(*expected, sexagesimal.resolve_plain(value));
+            assert_eq!(YamlString(value.to_string()), resolver.resolve_plain(value));
+        }
+        assert_eq!(YamlString("1:30".to_string()), YamlResolver::new(YamlSchema::Core).with_sexagesimal(true).resolve_plain("1:30"));
+    }
+
+    #[test]
+    fn test_word_booleans() {
+        let resolver = YamlResolver::new(YamlSchema::Yaml11);
+        for value in ["y", "Yes", "NO", "on", "Off"].iter() {
+            assert_eq!(YamlString(value.to_string()), resolver.resolve_plain(*value));
+        }
+        assert_eq!(YamlBool(false), resolver.resolve_plain("False"));
+        assert_eq!(YamlBool(false), resolver.with_word_booleans(true).resolve_plain("NO"));
+        assert_eq!(YamlString("NO".to_string()),
+                   YamlResolver::new(YamlSchema::Core).with_word_booleans(true).resolve_plain("NO"));
+    }
+
+    #[test]
+    fn test_core_resolver() {
+        let resolver = YamlResolver::new(YamlSchema::Core);
+        let cases = [("True", YamlBool(true)), ("yes", YamlString("yes".to_string())),
+                     ("off", YamlString("off".to_string())), ("~", YamlNull), ("", YamlNull),
+                     ("017", YamlInteger(17)), ("0o17", YamlInteger(15)), ("0x1F", YamlInteger(31)),
+                     ("-12", YamlInteger(-12)), ("0b101", YamlString("0b101".to_string())),
+                     ("1_000", YamlString("1_000".to_string())), ("1:30", YamlString("1:30".to_string())),
+                     ("1e3", YamlFloat(1000.0)), ("-.5", YamlFloat(-0.5)), (".inf", YamlFloat(f64::INFINITY))];
+
+        for &(value, ref expected) in cases.iter() {
+            assert_eq!(*expected, resolver.resolve_plain(value));
+        }
+    }
+
+    #[test]
+    fn test_json_resolver() {
+        let resolver = YamlResolver::new(YamlSchema::Json);
+        let cases = [("true", YamlBool(true)), ("True", YamlString("True".to_string())),
+                     ("null", YamlNull), ("~", YamlString("~".to_string())), ("", YamlString("".to_string())),
+                     ("-12", YamlInteger(-12)), ("+12", YamlString("+12".to_string())),
+                     ("017", YamlString("017".to_string())), ("0x1F", YamlString("0x1F".to_string())),
+                     ("-2.5e3", YamlFloat(-2500.0)), ("1.", YamlString("1.".to_string())),
+                     (".inf", YamlString(".inf".to_string()))];
+
+        for &(value, ref expected) in cases.iter() {
+            assert_eq!(*expected, resolver.resolve_plain(value));
+        }
+    }
+
+    #[test]
+    fn test_failsafe_resolver() {
+        let resolver = YamlResolver::new(YamlSchema::Failsafe);
+        for value in ["true", "null", "~", "", "12", "0x1F", "1.5", ".nan"].iter() {
+            assert_eq!(YamlString(value.to_string()), resolver.resolve_plain(*value));
+            assert_eq!("tag:yaml.org,2002:str", resolver.implicit_tag(*value));
+        }
+    }
+
+    #[test]
+    fn test_needs_quotes() {
+        for value in ["no", "0o17", "1:30", "~", "", "3.5", ".NaN"].iter() {
+            assert!(needs_quotes(*value), "{} should be quoted", value);
+        }
+        for value in ["hello", "1:60", "0x", "v1.2"].iter() {
+            assert!(!needs_quotes(*value), "{} should stay plain", value);
+        }
+    }
+}
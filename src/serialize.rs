@@ -0,0 +1,584 @@
+use error::YamlError;
+use document::YamlDocument;
+use constructor::YamlStandardData;
+use constructor::YamlStandardData::*;
+use emitter::YamlEmitter;
+use typed::{decode_error, expected, document_value, first_value, first_value_with};
+use parser::YamlResourceLimits;
+use schema;
+use ffi;
+use ffi::YamlScalarStyle::YamlAnyScalarStyle;
+use ffi::YamlSequenceStyle::YamlAnySequenceStyle;
+
+use std::num;
+use std::num::NumCast;
+use std::str;
+use std::io::MemWriter;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+
+pub struct YamlDecoder {
+    stack: Vec<YamlStandardData>
+}
+
+impl YamlDecoder {
+    pub fn new(value: YamlStandardData) -> YamlDecoder {
+        YamlDecoder {
+            stack: vec![value]
+        }
+    }
+
+    fn pop(&mut self) -> Result<YamlStandardData, YamlError> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(decode_error("no value left to decode".to_string()))
+        }
+    }
+
+    fn read_number<T: NumCast>(&mut self, what: &str) -> Result<T, YamlError> {
+        match try!(self.pop()) {
+            YamlInteger(i) => match num::cast(i) {
+                Some(n) => Ok(n),
+                None => Err(decode_error(format!("{} does not fit in {}", i, what)))
+            },
+            value => Err(expected(what, &value))
+        }
+    }
+}
+
+impl Decoder for YamlDecoder {
+    type Error = YamlError;
+
+    fn read_nil(&mut self) -> Result<(), YamlError> {
+        match try!(self.pop()) {
+            YamlNull => Ok(()),
+            value => Err(expected("null", &value))
+        }
+    }
+
+    fn read_uint(&mut self) -> Result<uint, YamlError> { self.read_number("uint") }
+    fn read_u64(&mut self) -> Result<u64, YamlError> { self.read_number("u64") }
+    fn read_u32(&mut self) -> Result<u32, YamlError> { self.read_number("u32") }
+    fn read_u16(&mut self) -> Result<u16, YamlError> { self.read_number("u16") }
+    fn read_u8(&mut self) -> Result<u8, YamlError> { self.read_number("u8") }
+    fn read_int(&mut self) -> Result<int, YamlError> { self.read_number("int") }
+    fn read_i64(&mut self) -> Result<i64, YamlError> { self.read_number("i64") }
+    fn read_i32(&mut self) -> Result<i32, YamlError> { self.read_number("i32") }
+    fn read_i16(&mut self) -> Result<i16, YamlError> { self.read_number("i16") }
+    fn read_i8(&mut self) -> Result<i8, YamlError> { self.read_number("i8") }
+
+    fn read_bool(&mut self) -> Result<bool, YamlError> {
+        match try!(self.pop()) {
+            YamlBool(b) => Ok(b),
+            value => Err(expected("a boolean", &value))
+        }
+    }
+
+    fn read_f64(&mut self) -> Result<f64, YamlError> {
+        match try!(self.pop()) {
+            YamlFloat(f) => Ok(f),
+            YamlInteger(i) => Ok(i as f64),
+            value => Err(expected("a float", &value))
+        }
+    }
+
+    fn read_f32(&mut self) -> Result<f32, YamlError> {
+        self.read_f64().map(|f| f as f32)
+    }
+
+    fn read_char(&mut self) -> Result<char, YamlError> {
+        match try!(self.pop()) {
+            YamlString(ref s) if s.chars().count() == 1 => Ok(s.chars().next().unwrap()),
+            value => Err(expected("a single character", &value))
+        }
+    }
+
+    fn read_str(&mut self) -> Result<String, YamlError> {
+        match try!(self.pop()) {
+            YamlString(s) => Ok(s),
+            value => Err(expected("a string", &value))
+        }
+    }
+
+    fn read_enum<T, F>(&mut self, _name: &str, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        f(self)
+    }
+
+    // a variant is either its bare name or a single-pair mapping from the
+    // name to the sequence of its arguments, as the encoder writes it. The
+    // arguments stay on the stack as one sequence so that reading too many
+    // or too few of them is an error.
+    fn read_enum_variant<T, F>(&mut self, names: &[&str], mut f: F) -> Result<T, YamlError>
+        where F: FnMut(&mut YamlDecoder, uint) -> Result<T, YamlError>
+    {
+        let (name, args) = match try!(self.pop()) {
+            YamlString(name) => (name, Vec::new()),
+            YamlMapping(mut pairs) if pairs.len() == 1 => match pairs.pop().unwrap() {
+                (YamlString(name), YamlSequence(args)) => (name, args),
+                (YamlString(_), value) => return Err(expected("a sequence of variant arguments", &value)),
+                (key, _) => return Err(expected("a variant name", &key))
+            },
+            value => return Err(expected("an enum variant", &value))
+        };
+        let idx = match names.iter().position(|n| *n == name.as_slice()) {
+            Some(idx) => idx,
+            None => return Err(decode_error(format!("unknown variant {}", name)))
+        };
+
+        self.stack.push(YamlSequence(args.into_iter().rev().collect()));
+        let value = try!(f(self, idx));
+        match try!(self.pop()) {
+            YamlSequence(ref rest) if rest.is_empty() => Ok(value),
+            _ => Err(decode_error(format!("too many arguments for variant {}", name)))
+        }
+    }
+
+    fn read_enum_variant_arg<T, F>(&mut self, idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        let mut args = match try!(self.pop()) {
+            YamlSequence(args) => args,
+            value => return Err(expected("variant arguments", &value))
+        };
+        let arg = match args.pop() {
+            Some(arg) => arg,
+            None => return Err(decode_error(format!("missing variant argument {}", idx)))
+        };
+        self.stack.push(YamlSequence(args));
+        self.stack.push(arg);
+        f(self)
+    }
+
+    fn read_enum_struct_variant<T, F>(&mut self, names: &[&str], f: F) -> Result<T, YamlError>
+        where F: FnMut(&mut YamlDecoder, uint) -> Result<T, YamlError>
+    {
+        self.read_enum_variant(names, f)
+    }
+
+    fn read_enum_struct_variant_field<T, F>(&mut self, _name: &str, idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        self.read_enum_variant_arg(idx, f)
+    }
+
+    fn read_struct<T, F>(&mut self, _name: &str, _len: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        let value = try!(f(self));
+        try!(self.pop());
+        Ok(value)
+    }
+
+    // missing fields decode from null so that Option fields may be omitted
+    fn read_struct_field<T, F>(&mut self, name: &str, _idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        let mut pairs = match try!(self.pop()) {
+            YamlMapping(pairs) => pairs,
+            value => return Err(expected("a mapping", &value))
+        };
+        let value = match pairs.iter().position(|&(ref k, _)| *k == YamlString(name.to_string())) {
+            Some(pos) => {
+                let (_, value) = pairs.remove(pos);
+                self.stack.push(value);
+                try!(f(self))
+            },
+            None => {
+                self.stack.push(YamlNull);
+                match f(self) {
+                    Ok(value) => value,
+                    Err(_) => return Err(decode_error(format!("missing field {}", name)))
+                }
+            }
+        };
+        self.stack.push(YamlMapping(pairs));
+        Ok(value)
+    }
+
+    fn read_tuple<T, F>(&mut self, len: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        self.read_seq(move |d, found| {
+            if found == len {
+                f(d)
+            } else {
+                Err(decode_error(format!("expected a sequence of {} items, found {}", len, found)))
+            }
+        })
+    }
+
+    fn read_tuple_arg<T, F>(&mut self, idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        self.read_seq_elt(idx, f)
+    }
+
+    fn read_tuple_struct<T, F>(&mut self, _name: &str, len: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        self.read_tuple(len, f)
+    }
+
+    fn read_tuple_struct_arg<T, F>(&mut self, idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        self.read_tuple_arg(idx, f)
+    }
+
+    fn read_option<T, F>(&mut self, mut f: F) -> Result<T, YamlError>
+        where F: FnMut(&mut YamlDecoder, bool) -> Result<T, YamlError>
+    {
+        match try!(self.pop()) {
+            YamlNull => f(self, false),
+            value => {
+                self.stack.push(value);
+                f(self, true)
+            }
+        }
+    }
+
+    fn read_seq<T, F>(&mut self, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder, uint) -> Result<T, YamlError>
+    {
+        let items = match try!(self.pop()) {
+            YamlSequence(items) | YamlSet(items) => items,
+            value => return Err(expected("a sequence", &value))
+        };
+        let len = items.len();
+        self.stack.extend(items.into_iter().rev());
+        f(self, len)
+    }
+
+    fn read_seq_elt<T, F>(&mut self, _idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        f(self)
+    }
+
+    fn read_map<T, F>(&mut self, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder, uint) -> Result<T, YamlError>
+    {
+        let pairs = match try!(self.pop()) {
+            YamlMapping(pairs) | YamlOrderedMap(pairs) => pairs,
+            value => return Err(expected("a mapping", &value))
+        };
+        let len = pairs.len();
+        for (key, value) in pairs.into_iter().rev() {
+            self.stack.push(value);
+            self.stack.push(key);
+        }
+        f(self, len)
+    }
+
+    fn read_map_elt_key<T, F>(&mut self, _idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        f(self)
+    }
+
+    fn read_map_elt_val<T, F>(&mut self, _idx: uint, f: F) -> Result<T, YamlError>
+        where F: FnOnce(&mut YamlDecoder) -> Result<T, YamlError>
+    {
+        f(self)
+    }
+
+    fn error(&mut self, err: &str) -> YamlError {
+        decode_error(err.to_string())
+    }
+}
+
+pub fn decode_value<T: Decodable>(value: YamlStandardData) -> Result<T, YamlError> {
+    Decodable::decode(&mut YamlDecoder::new(value))
+}
+
+pub fn decode_document<T: Decodable>(doc: &YamlDocument) -> Result<T, YamlError> {
+    decode_value(try!(document_value(doc)))
+}
+
+pub fn decode<T: Decodable>(s: &str) -> Result<T, YamlError> {
+    decode_value(try!(first_value(s)))
+}
+
+pub fn decode_with<T: Decodable>(s: &str, limits: &YamlResourceLimits) -> Result<T, YamlError> {
+    decode_value(try!(first_value_with(s, limits)))
+}
+
+pub struct YamlEncoder<'e, 'r: 'e> {
+    emitter: &'e mut YamlEmitter<'r>
+}
+
+impl<'e, 'r> YamlEncoder<'e, 'r> {
+    pub fn new(emitter: &'e mut YamlEmitter<'r>) -> YamlEncoder<'e, 'r> {
+        YamlEncoder {
+            emitter: emitter
+        }
+    }
+
+    fn emit_plain(&mut self, value: &str) -> Result<(), YamlError> {
+        self.emitter.emit_scalar_event(None, None, value, true, true, YamlAnyScalarStyle)
+    }
+}
+
+impl<'e, 'r> Encoder for YamlEncoder<'e, 'r> {
+    type Error = YamlError;
+
+    fn emit_nil(&mut self) -> Result<(), YamlError> { self.emit_plain("null") }
+
+    fn emit_uint(&mut self, v: uint) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_u64(&mut self, v: u64) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_u32(&mut self, v: u32) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_u16(&mut self, v: u16) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_u8(&mut self, v: u8) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_int(&mut self, v: int) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_i64(&mut self, v: i64) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_i32(&mut self, v: i32) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_i16(&mut self, v: i16) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+    fn emit_i8(&mut self, v: i8) -> Result<(), YamlError> { self.emit_plain(v.to_string().as_slice()) }
+
+    fn emit_bool(&mut self, v: bool) -> Result<(), YamlError> {
+        self.emit_plain(if v { "true" } else { "false" })
+    }
+
+    fn emit_f64(&mut self, v: f64) -> Result<(), YamlError> {
+        self.emit_plain(schema::float_text(v).as_slice())
+    }
+
+    fn emit_f32(&mut self, v: f32) -> Result<(), YamlError> {
+        self.emit_f64(v as f64)
+    }
+
+    fn emit_char(&mut self, v: char) -> Result<(), YamlError> {
+        self.emit_str(v.to_string().as_slice())
+    }
+
+    fn emit_str(&mut self, v: &str) -> Result<(), YamlError> {
+        self.emitter.emit_string(None, v)
+    }
+
+    fn emit_enum<F>(&mut self, _name: &str, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        f(self)
+    }
+
+    // the inverse of read_enum_variant: a bare name, or a name mapped to
+    // the sequence of arguments
+    fn emit_enum_variant<F>(&mut self, name: &str, _id: uint, len: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        if len == 0 {
+            return self.emit_str(name);
+        }
+        try!(self.emitter.emit_mapping_start_event(None, None, true, YamlAnySequenceStyle));
+        try!(self.emit_str(name));
+        try!(self.emitter.emit_sequence_start_event(None, None, true, YamlAnySequenceStyle));
+        try!(f(self));
+        try!(self.emitter.emit_sequence_end_event());
+        self.emitter.emit_mapping_end_event()
+    }
+
+    fn emit_enum_variant_arg<F>(&mut self, _idx: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        f(self)
+    }
+
+    fn emit_enum_struct_variant<F>(&mut self, name: &str, id: uint, len: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        self.emit_enum_variant(name, id, len, f)
+    }
+
+    fn emit_enum_struct_variant_field<F>(&mut self, _name: &str, idx: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        self.emit_enum_variant_arg(idx, f)
+    }
+
+    fn emit_struct<F>(&mut self, _name: &str, _len: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        try!(self.emitter.emit_mapping_start_event(None, None, true, YamlAnySequenceStyle));
+        try!(f(self));
+        self.emitter.emit_mapping_end_event()
+    }
+
+    fn emit_struct_field<F>(&mut self, name: &str, _idx: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        try!(self.emit_str(name));
+        f(self)
+    }
+
+    fn emit_tuple<F>(&mut self, len: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        self.emit_seq(len, f)
+    }
+
+    fn emit_tuple_arg<F>(&mut self, idx: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_tuple_struct<F>(&mut self, _name: &str, len: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        self.emit_seq(len, f)
+    }
+
+    fn emit_tuple_struct_arg<F>(&mut self, idx: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        f(self)
+    }
+
+    fn emit_option_none(&mut self) -> Result<(), YamlError> {
+        self.emit_nil()
+    }
+
+    fn emit_option_some<F>(&mut self, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, _len: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        try!(self.emitter.emit_sequence_start_event(None, None, true, YamlAnySequenceStyle));
+        try!(f(self));
+        self.emitter.emit_sequence_end_event()
+    }
+
+    fn emit_seq_elt<F>(&mut self, _idx: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, _len: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        try!(self.emitter.emit_mapping_start_event(None, None, true, YamlAnySequenceStyle));
+        try!(f(self));
+        self.emitter.emit_mapping_end_event()
+    }
+
+    fn emit_map_elt_key<F>(&mut self, _idx: uint, mut f: F) -> Result<(), YamlError>
+        where F: FnMut(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        f(self)
+    }
+
+    fn emit_map_elt_val<F>(&mut self, _idx: uint, f: F) -> Result<(), YamlError>
+        where F: FnOnce(&mut YamlEncoder<'e, 'r>) -> Result<(), YamlError>
+    {
+        f(self)
+    }
+}
+
+pub fn encode<T: Encodable>(value: &T) -> Result<String, YamlError> {
+    let mut writer = MemWriter::new();
+    {
+        let mut emitter = YamlEmitter::init(&mut writer);
+        try!(emitter.emit_stream(ffi::YamlEncoding::YamlUtf8Encoding, |e| {
+            e.emit_document(None, &[], true, |e| value.encode(&mut YamlEncoder::new(e)))
+        }));
+    }
+    Ok(str::from_utf8(writer.get_ref()).unwrap().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use serialize::{decode, encode};
+    use error::YamlErrorCode;
+    use std::collections::HashMap;
+
+    #[derive(RustcDecodable, RustcEncodable, Show, PartialEq)]
+    enum Mode {
+        Fast,
+        Limited(uint),
+    }
+
+    #[derive(RustcDecodable, RustcEncodable, Show, PartialEq)]
+    struct Server {
+        name: String,
+        port: u16,
+        ratio: f64,
+        tags: Vec<String>,
+        backup: Option<String>,
+        mode: Mode,
+        env: HashMap<String, int>,
+        pair: (bool, char)
+    }
+
+    #[test]
+    fn test_decode_struct() {
+        let data = "name: web\nport: 8080\nratio: 1\ntags: [a, b]\nmode: {Limited: [3]}\nenv: {x: 1}\npair: [true, z]\n";
+        let server: Server = decode(data).unwrap();
+
+        let mut env = HashMap::new();
+        env.insert("x".to_string(), 1);
+        assert_eq!(Server {
+            name: "web".to_string(),
+            port: 8080,
+            ratio: 1.0,
+            tags: vec!["a".to_string(), "b".to_string()],
+            backup: None,
+            mode: Mode::Limited(3),
+            env: env,
+            pair: (true, 'z')
+        }, server);
+        assert_eq!(Ok(vec![Mode::Fast, Mode::Limited(1)]), decode("[Fast, {Limited: [1]}]"));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        for data in ["70000", "-1", "abc"].iter() {
+            match decode::<u16>(*data) {
+                Err(e) => assert_eq!(YamlErrorCode::DecodeError, e.code),
+                Ok(n) => panic!("unexpected value: {}", n)
+            }
+        }
+        match decode::<Server>("name: web\n") {
+            Err(e) => assert_eq!(Some("missing field port".to_string()), e.problem),
+            Ok(server) => panic!("unexpected value: {}", server)
+        }
+        assert!(decode::<Mode>("Slow").is_err());
+        for data in ["{Limited: []}", "{Limited: [1, 2]}", "{Limited: 1}", "{Fast: [3]}", "Limited"].iter() {
+            match decode::<Mode>(*data) {
+                Err(e) => assert_eq!(YamlErrorCode::DecodeError, e.code),
+                Ok(mode) => panic!("unexpected value: {}", mode)
+            }
+        }
+        assert!(decode::<Server>("name: web\nport: 1\nratio: 1\ntags: []\nmode: {Limited: []}\nenv: {}\npair: [true, z]\n").is_err());
+        assert!(decode::<(int, int)>("[1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let server = Server {
+            name: "true".to_string(),
+            port: 22,
+            ratio: 0.5,
+            tags: vec!["a".to_string()],
+            backup: None,
+            mode: Mode::Limited(2),
+            env: HashMap::new(),
+            pair: (false, 'x')
+        };
+        let text = encode(&server).unwrap();
+        assert_eq!("name: 'true'\nport: 22\nratio: 0.5\ntags:\n- a\nbackup: null\nmode:\n  Limited:\n  - 2\nenv: {}\npair:\n- false\n- x\n", text.as_slice());
+        assert_eq!(Ok(server), decode(text.as_slice()));
+        assert_eq!(Ok("- Fast\n- 'null'\n".to_string()), encode(&vec![Some("Fast"), Some("null")]));
+    }
+}
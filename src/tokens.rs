@@ -0,0 +1,246 @@
+use ffi;
+use ffi::{YamlEncoding, YamlScalarStyle};
+use ffi::yaml_token_type_t::*;
+use codecs;
+use error::{YamlError, YamlMark};
+use event::{YamlVersionDirective, YamlTagDirective};
+use parser::YamlParser;
+
+use std::mem;
+
+#[derive(Show, PartialEq)]
+pub enum YamlToken {
+    YamlNoToken,
+    YamlStreamStartToken(YamlEncoding),
+    YamlStreamEndToken,
+    YamlVersionDirectiveToken(YamlVersionDirective),
+    YamlTagDirectiveToken(YamlTagDirective),
+    YamlDocumentStartToken,
+    YamlDocumentEndToken,
+    YamlBlockSequenceStartToken,
+    YamlBlockMappingStartToken,
+    YamlBlockEndToken,
+    YamlFlowSequenceStartToken,
+    YamlFlowSequenceEndToken,
+    YamlFlowMappingStartToken,
+    YamlFlowMappingEndToken,
+    YamlBlockEntryToken,
+    YamlFlowEntryToken,
+    YamlKeyToken,
+    YamlValueToken,
+    YamlAliasToken(String),
+    YamlAnchorToken(String),
+    YamlTagToken(String, String),
+    YamlScalarToken(String, YamlScalarStyle),
+}
+
+impl YamlToken {
+    pub unsafe fn load(token: &ffi::yaml_token_t) -> YamlToken {
+        match token.token_type {
+            YAML_NO_TOKEN => YamlToken::YamlNoToken,
+            YAML_STREAM_START_TOKEN => {
+                let data: &ffi::yaml_stream_start_token_t = mem::transmute(&token.data);
+                YamlToken::YamlStreamStartToken(data.encoding)
+            },
+            YAML_STREAM_END_TOKEN => YamlToken::YamlStreamEndToken,
+            YAML_VERSION_DIRECTIVE_TOKEN => {
+                let data: &ffi::yaml_version_directive_token_t = mem::transmute(&token.data);
+                YamlToken::YamlVersionDirectiveToken(YamlVersionDirective {
+                    major: data.major as int,
+                    minor: data.minor as int
+                })
+            },
+            YAML_TAG_DIRECTIVE_TOKEN => {
+                let data: &ffi::yaml_tag_directive_token_t = mem::transmute(&token.data);
+                YamlToken::YamlTagDirectiveToken(YamlTagDirective {
+                    handle: codecs::decode_c_str(data.handle).unwrap(),
+                    prefix: codecs::decode_c_str(data.prefix).unwrap()
+                })
+            },
+            YAML_DOCUMENT_START_TOKEN => YamlToken::YamlDocumentStartToken,
+            YAML_DOCUMENT_END_TOKEN => YamlToken::YamlDocumentEndToken,
+            YAML_BLOCK_SEQUENCE_START_TOKEN => YamlToken::YamlBlockSequenceStartToken,
+            YAML_BLOCK_MAPPING_START_TOKEN => YamlToken::YamlBlockMappingStartToken,
+            YAML_BLOCK_END_TOKEN => YamlToken::YamlBlockEndToken,
+            YAML_FLOW_SEQUENCE_START_TOKEN => YamlToken::YamlFlowSequenceStartToken,
+            YAML_FLOW_SEQUENCE_END_TOKEN => YamlToken::YamlFlowSequenceEndToken,
+            YAML_FLOW_MAPPING_START_TOKEN => YamlToken::YamlFlowMappingStartToken,
+            YAML_FLOW_MAPPING_END_TOKEN => YamlToken::YamlFlowMappingEndToken,
+            YAML_BLOCK_ENTRY_TOKEN => YamlToken::YamlBlockEntryToken,
+            YAML_FLOW_ENTRY_TOKEN => YamlToken::YamlFlowEntryToken,
+            YAML_KEY_TOKEN => YamlToken::YamlKeyToken,
+            YAML_VALUE_TOKEN => YamlToken::YamlValueToken,
+            YAML_ALIAS_TOKEN => {
+                let data: &ffi::yaml_alias_token_t = mem::transmute(&token.data);
+                YamlToken::YamlAliasToken(codecs::decode_c_str(data.value).unwrap())
+            },
+            YAML_ANCHOR_TOKEN => {
+                let data: &ffi::yaml_alias_token_t = mem::transmute(&token.data);
+                YamlToken::YamlAnchorToken(codecs::decode_c_str(data.value).unwrap())
+            },
+            YAML_TAG_TOKEN => {
+                let data: &ffi::yaml_tag_token_t = mem::transmute(&token.data);
+                YamlToken::YamlTagToken(codecs::decode_c_str(data.handle).unwrap_or(String::new()),
+                                        codecs::decode_c_str(data.suffix).unwrap())
+            },
+            YAML_SCALAR_TOKEN => {
+                let data: &ffi::yaml_scalar_token_t = mem::transmute(&token.data);
+                YamlToken::YamlScalarToken(codecs::decode_buf(data.value, data.length).unwrap(), data.style)
+            }
+        }
+    }
+}
+
+#[derive(Show, PartialEq)]
+pub struct YamlMarkedToken {
+    pub token: YamlToken,
+    pub start_mark: YamlMark,
+    pub end_mark: YamlMark
+}
+
+impl YamlMarkedToken {
+    pub unsafe fn load(token: &ffi::yaml_token_t) -> YamlMarkedToken {
+        YamlMarkedToken {
+            token: YamlToken::load(token),
+            start_mark: YamlMark::conv(&token.start_mark),
+            end_mark: YamlMark::conv(&token.end_mark)
+        }
+    }
+}
+
+struct InternalToken {
+    token_mem: ffi::yaml_token_t
+}
+
+impl Drop for InternalToken {
+    fn drop(&mut self) {
+        unsafe {
+            self.token_mem.delete()
+        }
+    }
+}
+
+// libyaml can't interleave scanning with parsing, so the stream owns its parser
+pub struct YamlTokenStream<P> {
+    parser: Box<P>,
+    finished: bool
+}
+
+impl<P: YamlParser> YamlTokenStream<P> {
+    pub fn new(parser: Box<P>) -> YamlTokenStream<P> {
+        YamlTokenStream {
+            parser: parser,
+            finished: false
+        }
+    }
+}
+
+impl<P: YamlParser> Iterator for YamlTokenStream<P> {
+    type Item = Result<YamlMarkedToken, YamlError>;
+
+    fn next(&mut self) -> Option<Result<YamlMarkedToken, YamlError>> {
+        if self.finished {
+            return None;
+        }
+
+        let res = unsafe {
+            let mut token = InternalToken {
+                token_mem: mem::zeroed()
+            };
+
+            if self.parser.base_parser_ref().scan(&mut token.token_mem) {
+                match YamlMarkedToken::load(&token.token_mem) {
+                    YamlMarkedToken { token: YamlToken::YamlNoToken, .. } => None,
+                    marked => Some(Ok(marked))
+                }
+            } else {
+                Some(Err(self.parser.get_error()))
+            }
+        };
+
+        match res {
+            Some(Ok(YamlMarkedToken { token: YamlToken::YamlStreamEndToken, .. })) => self.finished = true,
+            Some(Ok(_)) => (),
+            _ => self.finished = true
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokens::YamlToken;
+    use tokens::YamlToken::*;
+    use parser::{YamlParser, YamlByteParser};
+    use event::{YamlVersionDirective, YamlTagDirective};
+    use error::{YamlError, YamlErrorCode, YamlMark};
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+    use ffi::YamlScalarStyle::*;
+
+    #[test]
+    fn test_tokens() {
+        let data = "- a\n- &x 'b'\n- *x\n- !t c\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let tokens: Vec<YamlToken> = parser.tokens().map(|tok| tok.unwrap().token).collect();
+
+        assert_eq!(vec![
+            YamlStreamStartToken(YamlUtf8Encoding),
+            YamlBlockSequenceStartToken,
+            YamlBlockEntryToken,
+            YamlScalarToken("a".to_string(), YamlPlainScalarStyle),
+            YamlBlockEntryToken,
+            YamlAnchorToken("x".to_string()),
+            YamlScalarToken("b".to_string(), YamlSingleQuotedScalarStyle),
+            YamlBlockEntryToken,
+            YamlAliasToken("x".to_string()),
+            YamlBlockEntryToken,
+            YamlTagToken("!".to_string(), "t".to_string()),
+            YamlScalarToken("c".to_string(), YamlPlainScalarStyle),
+            YamlBlockEndToken,
+            YamlStreamEndToken
+        ], tokens);
+    }
+
+    #[test]
+    fn test_directive_tokens() {
+        let data = "%YAML 1.1\n%TAG !e! tag:example.com,2000:\n--- {k: v}\n";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let tokens: Vec<YamlToken> = parser.tokens().map(|tok| tok.unwrap().token).collect();
+
+        assert_eq!(vec![
+            YamlStreamStartToken(YamlUtf8Encoding),
+            YamlVersionDirectiveToken(YamlVersionDirective { major: 1, minor: 1 }),
+            YamlTagDirectiveToken(YamlTagDirective { handle: "!e!".to_string(), prefix: "tag:example.com,2000:".to_string() }),
+            YamlDocumentStartToken,
+            YamlFlowMappingStartToken,
+            YamlKeyToken,
+            YamlScalarToken("k".to_string(), YamlPlainScalarStyle),
+            YamlValueToken,
+            YamlScalarToken("v".to_string(), YamlPlainScalarStyle),
+            YamlFlowMappingEndToken,
+            YamlStreamEndToken
+        ], tokens);
+    }
+
+    #[test]
+    fn test_token_marks() {
+        let data = "key: 'value'";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let tokens: Vec<_> = parser.tokens().map(|tok| tok.unwrap()).collect();
+
+        assert_eq!(YamlScalarToken("value".to_string(), YamlSingleQuotedScalarStyle), tokens[5].token);
+        assert_eq!(YamlMark { index: 5, line: 0, column: 5 }, tokens[5].start_mark);
+        assert_eq!(YamlMark { index: 12, line: 0, column: 12 }, tokens[5].end_mark);
+    }
+
+    #[test]
+    fn test_token_error() {
+        let parser = YamlByteParser::init("'abc".as_bytes(), YamlUtf8Encoding).unwrap();
+        let tokens: Result<Vec<_>, YamlError> = parser.tokens().collect();
+
+        match tokens {
+            Err(e) => assert_eq!(YamlErrorCode::ScannerError, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use ffi;
+use ffi::YamlErrorType;
+use error::{YamlError, YamlErrorCode};
+use document::YamlDocument;
+use constructor::{YamlConstructor, YamlStandardConstructor, YamlStandardData};
+use constructor::YamlStandardData::*;
+use parser::{YamlParser, YamlByteParser, YamlResourceLimits};
+use emitter::YamlEmitter;
+use diff::render_value;
+
+use std::num;
+use std::str;
+use std::hash::Hash;
+use std::io::MemWriter;
+use std::collections::HashMap;
+
+pub fn decode_error(message: String) -> YamlError {
+    YamlError {
+        kind: YamlErrorType::YAML_COMPOSER_ERROR,
+        code: YamlErrorCode::DecodeError,
+        problem: Some(message),
+        io_error: None,
+        context: None,
+        filename: None
+    }
+}
+
+pub fn expected(what: &str, found: &YamlStandardData) -> YamlError {
+    decode_error(format!("expected {}, found {}", what, render_value(found)))
+}
+
+pub trait FromYaml {
+    fn from_yaml(value: &YamlStandardData) -> Result<Self, YamlError>;
+}
+
+pub trait ToYaml {
+    fn to_yaml(&self) -> YamlStandardData;
+}
+
+impl FromYaml for YamlStandardData {
+    fn from_yaml(value: &YamlStandardData) -> Result<YamlStandardData, YamlError> {
+        Ok(value.clone())
+    }
+}
+
+impl ToYaml for YamlStandardData {
+    fn to_yaml(&self) -> YamlStandardData {
+        self.clone()
+    }
+}
+
+macro_rules! int_impls {
+    ($($t:ident),*) => ($(
+        impl FromYaml for $t {
+            fn from_yaml(value: &YamlStandardData) -> Result<$t, YamlError> {
+                match *value {
+                    YamlInteger(i) => match num::cast(i) {
+                        Some(n) => Ok(n),
+                        None => Err(decode_error(format!("{} does not fit in {}", i, stringify!($t))))
+                    },
+                    // integers past the range of int load as strings, and are written as such
+                    YamlString(ref s) => match s.parse::<$t>() {
+                        Some(n) if num::cast::<$t, int>(n).is_none() => Ok(n),
+                        _ => Err(expected(stringify!($t), value))
+                    },
+                    _ => Err(expected(stringify!($t), value))
+                }
+            }
+        }
+
+        impl ToYaml for $t {
+            fn to_yaml(&self) -> YamlStandardData {
+                match num::cast(*self) {
+                    Some(i) => YamlInteger(i),
+                    None => YamlString(self.to_string())
+                }
+            }
+        }
+    )*)
+}
+
+int_impls!(int, i8, i16, i32, i64, uint, u8, u16, u32, u64);
+
+impl FromYaml for f64 {
+    fn from_yaml(value: &YamlStandardData) -> Result<f64, YamlError> {
+        match *value {
+            YamlFloat(f) => Ok(f),
+            YamlInteger(i) => Ok(i as f64),
+            _ => Err(expected("a float", value))
+        }
+    }
+}
+
+impl ToYaml for f64 {
+    fn to_yaml(&self) -> YamlStandardData {
+        YamlFloat(*self)
+    }
+}
+
+impl FromYaml for f32 {
+    fn from_yaml(value: &YamlStandardData) -> Result<f32, YamlError> {
+        FromYaml::from_yaml(value).map(|f: f64| f as f32)
+    }
+}
+
+impl ToYaml for f32 {
+    fn to_yaml(&self) -> YamlStandardData {
+        YamlFloat(*self as f64)
+    }
+}
+
+impl FromYaml for bool {
+    fn from_yaml(value: &YamlStandardData) -> Result<bool, YamlError> {
+        match *value {
+            YamlBool(b) => Ok(b),
+            _ => Err(expected("a boolean", value))
+        }
+    }
+}
+
+impl ToYaml for bool {
+    fn to_yaml(&self) -> YamlStandardData {
+        YamlBool(*self)
+    }
+}
+
+impl FromYaml for String {
+    fn from_yaml(value: &YamlStandardData) -> Result<String, YamlError> {
+        match *value {
+            YamlString(ref s) => Ok(s.clone()),
+            _ => Err(expected("a string", value))
+        }
+    }
+}
+
+impl ToYaml for String {
+    fn to_yaml(&self) -> YamlStandardData {
+        YamlString(self.clone())
+    }
+}
+
+impl<'a> ToYaml for &'a str {
+    fn to_yaml(&self) -> YamlStandardData {
+        YamlString(self.to_string())
+    }
+}
+
+impl<T: FromYaml> FromYaml for Option<T> {
+    fn from_yaml(value: &YamlStandardData) -> Result<Option<T>, YamlError> {
+        match *value {
+            YamlNull => Ok(None),
+            _ => FromYaml::from_yaml(value).map(|v| Some(v))
+        }
+    }
+}
+
+impl<T: ToYaml> ToYaml for Option<T> {
+    fn to_yaml(&self) -> YamlStandardData {
+        match *self {
+            Some(ref v) => v.to_yaml(),
+            None => YamlNull
+        }
+    }
+}
+
+impl<T: FromYaml> FromYaml for Vec<T> {
+    fn from_yaml(value: &YamlStandardData) -> Result<Vec<T>, YamlError> {
+        match *value {
+            YamlSequence(ref items) | YamlSet(ref items) => items.iter().map(|item| FromYaml::from_yaml(item)).collect(),
+            _ => Err(expected("a sequence", value))
+        }
+    }
+}
+
+impl<T: ToYaml> ToYaml for Vec<T> {
+    fn to_yaml(&self) -> YamlStandardData {
+        YamlSequence(self.iter().map(|item| item.to_yaml()).collect())
+    }
+}
+
+impl<K: FromYaml + Eq + Hash, V: FromYaml> FromYaml for HashMap<K, V> {
+    fn from_yaml(value: &YamlStandardData) -> Result<HashMap<K, V>, YamlError> {
+        let pairs = match *value {
+            YamlMapping(ref pairs) | YamlOrderedMap(ref pairs) => pairs,
+            _ => return Err(expected("a mapping", value))
+        };
+        let mut map = HashMap::new();
+        for &(ref k, ref v) in pairs.iter() {
+            map.insert(try!(FromYaml::from_yaml(k)), try!(FromYaml::from_yaml(v)));
+        }
+        Ok(map)
+    }
+}
+
+impl<K: ToYaml + Eq + Hash, V: ToYaml> ToYaml for HashMap<K, V> {
+    fn to_yaml(&self) -> YamlStandardData {
+        YamlMapping(self.iter().map(|(k, v)| (k.to_yaml(), v.to_yaml())).collect())
+    }
+}
+
+macro_rules! tuple_impls {
+    ($($len:expr => ($($name:ident),+))+) => ($(
+        impl<$($name: FromYaml),+> FromYaml for ($($name,)+) {
+            fn from_yaml(value: &YamlStandardData) -> Result<($($name,)+), YamlError> {
+                match *value {
+                    YamlSequence(ref items) if items.len() == $len => {
+                        let mut iter = items.iter();
+                        Ok(($({
+                            let item: $name = try!(FromYaml::from_yaml(iter.next().unwrap()));
+                            item
+                        },)+))
+                    },
+                    _ => Err(expected(format!("a sequence of {} items", $len).as_slice(), value))
+                }
+            }
+        }
+
+        impl<$($name: ToYaml),+> ToYaml for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn to_yaml(&self) -> YamlStandardData {
+                let ($(ref $name,)+) = *self;
+                YamlSequence(vec![$($name.to_yaml()),+])
+            }
+        }
+    )+)
+}
+
+tuple_impls! {
+    1 => (A)
+    2 => (A, B)
+    3 => (A, B, C)
+    4 => (A, B, C, D)
+}
+
+// used by the code #[deriving_yaml] generates for structs
+pub fn mapping<'a>(value: &'a YamlStandardData) -> Result<&'a [(YamlStandardData, YamlStandardData)], YamlError> {
+    match *value {
+        YamlMapping(ref pairs) | YamlOrderedMap(ref pairs) => Ok(pairs.as_slice()),
+        _ => Err(expected("a mapping", value))
+    }
+}
+
+// a missing key converts from null, so Option fields may be left out
+pub fn field<T: FromYaml>(pairs: &[(YamlStandardData, YamlStandardData)], key: &str) -> Result<T, YamlError> {
+    match pairs.iter().find(|&&(ref k, _)| *k == YamlString(key.to_string())) {
+        Some(&(_, ref value)) => FromYaml::from_yaml(value),
+        None => FromYaml::from_yaml(&YamlNull).map_err(|_| decode_error(format!("missing field {}", key)))
+    }
+}
+
+pub fn document_value(doc: &YamlDocument) -> Result<YamlStandardData, YamlError> {
+    document_value_with(doc, &YamlResourceLimits::new())
+}
+
+pub fn document_value_with(doc: &YamlDocument, limits: &YamlResourceLimits) -> Result<YamlStandardData, YamlError> {
+    if doc.is_empty() {
+        return Ok(YamlNull);
+    }
+    try!(doc.check_acyclic());
+    doc.root().and_then(|root| YamlStandardConstructor::with_limits(limits).construct(root))
+}
+
+// the first document of the stream; an empty stream is null
+pub fn first_value(s: &str) -> Result<YamlStandardData, YamlError> {
+    first_value_with(s, &YamlResourceLimits::new())
+}
+
+pub fn first_value_with(s: &str, limits: &YamlResourceLimits) -> Result<YamlStandardData, YamlError> {
+    let mut parser = try!(YamlByteParser::new(s.as_bytes(), ffi::YamlEncoding::YamlUtf8Encoding));
+    parser.set_limits(*limits);
+    match parser.documents().next() {
+        Some(doc) => document_value_with(&*try!(doc), limits),
+        None => Ok(YamlNull)
+    }
+}
+
+pub fn from_document<T: FromYaml>(doc: &YamlDocument) -> Result<T, YamlError> {
+    FromYaml::from_yaml(&try!(document_value(doc)))
+}
+
+pub fn from_str<T: FromYaml>(s: &str) -> Result<T, YamlError> {
+    FromYaml::from_yaml(&try!(first_value(s)))
+}
+
+pub fn from_str_with<T: FromYaml>(s: &str, limits: &YamlResourceLimits) -> Result<T, YamlError> {
+    FromYaml::from_yaml(&try!(first_value_with(s, limits)))
+}
+
+pub fn to_string<T: ToYaml>(value: &T) -> Result<String, YamlError> {
+    let data = value.to_yaml();
+    let mut writer = MemWriter::new();
+    {
+        let mut emitter = YamlEmitter::init(&mut writer);
+        try!(emitter.emit_stream(ffi::YamlEncoding::YamlUtf8Encoding, |e| {
+            e.emit_document(None, &[], true, |e| e.emit_value(&data))
+        }));
+    }
+    Ok(str::from_utf8(writer.get_ref()).unwrap().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use typed::{ToYaml, field, from_str, from_str_with, to_string};
+    use constructor::YamlStandardData;
+    use constructor::YamlStandardData::*;
+    use parser::YamlResourceLimits;
+    use error::YamlErrorCode;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_from_yaml() {
+        assert_eq!(Ok(vec![Some(1u8), None]), from_str("[1, ~]"));
+        assert_eq!(Ok((true, 2.5f64, "x".to_string())), from_str("[true, 2.5, x]"));
+        assert_eq!(Ok(3.0f32), from_str("3"));
+
+        let map: HashMap<String, Vec<int>> = from_str("a: [1, 2]\nb: []\n").unwrap();
+        assert_eq!(Some(&vec![1, 2]), map.get("a"));
+        assert_eq!(Some(&vec![]), map.get("b"));
+
+        match from_str::<u8>("256") {
+            Err(e) => assert_eq!(Some("256 does not fit in u8".to_string()), e.problem),
+            Ok(n) => panic!("unexpected value: {}", n)
+        }
+        match from_str::<(int, int)>("[1]") {
+            Err(e) => assert_eq!(YamlErrorCode::DecodeError, e.code),
+            Ok(t) => panic!("unexpected value: {}", t)
+        }
+        assert!(from_str::<String>("12").is_err());
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        assert_eq!(YamlSequence(vec![YamlInteger(1), YamlNull]), vec![Some(1i8), None].to_yaml());
+
+        let mut map = HashMap::new();
+        map.insert("ports", vec![80u16, 443]);
+        assert_eq!(Ok("ports:\n- 80\n- 443\n".to_string()), to_string(&map));
+        assert_eq!(Ok("- 'yes'\n- 1.0\n- null\n".to_string()), to_string(&("yes", 1.0f64, None::<int>)));
+
+        let value = (-5i64, "on".to_string(), vec![true]);
+        let text = to_string(&value).unwrap();
+        assert_eq!(Ok(value), from_str(text.as_slice()));
+        let big = vec![18446744073709551615u64, 5];
+        assert_eq!(YamlSequence(vec![YamlString("18446744073709551615".to_string()), YamlInteger(5)]), big.to_yaml());
+        let text = to_string(&big).unwrap();
+        assert_eq!(Ok(big), from_str(text.as_slice()));
+        assert!(from_str::<u64>("'12'").is_err());
+    }
+
+    #[test]
+    fn test_from_str_with_limits() {
+        let data = "- &a [x, x, x]\n- &b [*a, *a, *a]\n- [*b, *b, *b]\n";
+        let mut limits = YamlResourceLimits::new();
+        limits.alias_expansions = Some(20);
+
+        assert!(from_str::<YamlStandardData>(data).is_ok());
+        match from_str_with::<YamlStandardData>(data, &limits) {
+            Err(e) => assert_eq!(YamlErrorCode::NodeLimitExceeded, e.code),
+            Ok(_) => panic!("should return an error")
+        }
+    }
+
+    #[test]
+    fn test_field() {
+        let pairs = vec![(YamlString("a".to_string()), YamlInteger(1))];
+        assert_eq!(Ok(1i), field(pairs.as_slice(), "a"));
+        assert_eq!(Ok(None::<int>), field(pairs.as_slice(), "b"));
+        match field::<int>(pairs.as_slice(), "b") {
+            Err(e) => assert_eq!(Some("missing field b".to_string()), e.problem),
+            Ok(n) => panic!("unexpected value: {}", n)
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use error::YamlError;
+use document::{YamlDocument, YamlNode, YamlNodeData, YamlScalarData, YamlSequenceData, YamlMappingData};
+
+pub trait YamlVisitor {
+    fn visit_scalar(&mut self, _scalar: &YamlScalarData) {}
+    fn visit_sequence(&mut self, _sequence: &YamlSequenceData) {}
+    fn visit_mapping(&mut self, _mapping: &YamlMappingData) {}
+}
+
+fn walk_inner<V: YamlVisitor>(node: &YamlNode, visitor: &mut V, stack: &mut Vec<int>) -> Result<(), YamlError> {
+    match *node {
+        YamlNode::YamlScalarNode(ref scalar) => visitor.visit_scalar(scalar),
+        YamlNode::YamlSequenceNode(ref sequence) => {
+            visitor.visit_sequence(sequence);
+            if stack.contains(&sequence.index()) {
+                return Ok(());
+            }
+            stack.push(sequence.index());
+            for item in sequence.values() {
+                try!(walk_inner(&try!(item), visitor, stack));
+            }
+            stack.pop();
+        },
+        YamlNode::YamlMappingNode(ref mapping) => {
+            visitor.visit_mapping(mapping);
+            if stack.contains(&mapping.index()) {
+                return Ok(());
+            }
+            stack.push(mapping.index());
+            for pair in mapping.pairs() {
+                let (key, value) = try!(pair);
+                try!(walk_inner(&key, visitor, stack));
+                try!(walk_inner(&value, visitor, stack));
+            }
+            stack.pop();
+        }
+    }
+    Ok(())
+}
+
+pub fn walk_node<V: YamlVisitor>(node: &YamlNode, visitor: &mut V) -> Result<(), YamlError> {
+    walk_inner(node, visitor, &mut Vec::new())
+}
+
+pub fn walk<V: YamlVisitor>(doc: &YamlDocument, visitor: &mut V) -> Result<(), YamlError> {
+    if doc.is_empty() {
+        Ok(())
+    } else {
+        walk_node(&try!(doc.root()), visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use visitor::{walk, YamlVisitor};
+    use document::{YamlNodeData, YamlScalarData, YamlSequenceData, YamlMappingData};
+    use parser::{YamlParser, YamlByteParser};
+    use ffi::YamlEncoding::YamlUtf8Encoding;
+
+    struct Counter {
+        scalars: Vec<String>,
+        sequences: uint,
+        mappings: uint,
+        tags: Vec<String>
+    }
+
+    impl YamlVisitor for Counter {
+        fn visit_scalar(&mut self, scalar: &YamlScalarData) {
+            self.scalars.push(scalar.get_value());
+        }
+
+        fn visit_sequence(&mut self, _sequence: &YamlSequenceData) {
+            self.sequences += 1;
+        }
+
+        fn visit_mapping(&mut self, mapping: &YamlMappingData) {
+            self.mappings += 1;
+            match mapping.tag() {
+                Some(tag) => self.tags.push(tag),
+                None => ()
+            }
+        }
+    }
+
+    fn counter() -> Counter {
+        Counter { scalars: Vec::new(), sequences: 0, mappings: 0, tags: Vec::new() }
+    }
+
+    #[test]
+    fn test_walk() {
+        let data = "!cfg {a: [1, 2], b: {c: d}}";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let mut visitor = counter();
+
+        assert!(walk(&*doc, &mut visitor).is_ok());
+        assert_eq!(vec!["a".to_string(), "1".to_string(), "2".to_string(),
+                        "b".to_string(), "c".to_string(), "d".to_string()], visitor.scalars);
+        assert_eq!(1, visitor.sequences);
+        assert_eq!(2, visitor.mappings);
+        assert_eq!("!cfg".to_string(), visitor.tags[0]);
+    }
+
+    #[test]
+    fn test_walk_recursive_alias() {
+        let data = "&a [x, *a]";
+        let parser = YamlByteParser::init(data.as_bytes(), YamlUtf8Encoding).unwrap();
+        let doc = parser.load().next().unwrap().unwrap();
+        let mut visitor = counter();
+
+        assert!(walk(&*doc, &mut visitor).is_ok());
+        assert_eq!(vec!["x".to_string()], visitor.scalars);
+        assert_eq!(2, visitor.sequences);
+    }
+}
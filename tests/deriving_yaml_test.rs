@@ -0,0 +1,51 @@
+#![feature(phase)]
+
+#[phase(plugin)]
+extern crate yaml_macros;
+extern crate yaml;
+
+use yaml::typed::{FromYaml, ToYaml, from_str, to_string};
+use yaml::constructor::YamlStandardData::*;
+
+#[deriving_yaml]
+#[derive(Show, PartialEq)]
+struct Limits {
+    #[yaml(rename = "max-size")]
+    max_size: uint,
+    timeout: Option<f64>
+}
+
+#[deriving_yaml]
+#[derive(Show, PartialEq)]
+struct Service {
+    name: String,
+    ports: Vec<u16>,
+    limits: Limits
+}
+
+#[test]
+fn test_deriving_from_yaml() {
+    let service: Service = from_str("name: web\nports: [80, 443]\nlimits: {max-size: 10}\n").unwrap();
+    assert_eq!(Service {
+        name: "web".to_string(),
+        ports: vec![80, 443],
+        limits: Limits { max_size: 10, timeout: None }
+    }, service);
+
+    match from_str::<Service>("name: web\nports: []\n") {
+        Err(e) => assert_eq!(Some("missing field limits".to_string()), e.problem),
+        Ok(s) => panic!("unexpected value: {}", s)
+    }
+    assert!(from_str::<Limits>("[1, 2]").is_err());
+}
+
+#[test]
+fn test_deriving_to_yaml() {
+    let limits = Limits { max_size: 5, timeout: Some(1.5) };
+    assert_eq!(YamlMapping(vec![
+        (YamlString("max-size".to_string()), YamlInteger(5)),
+        (YamlString("timeout".to_string()), YamlFloat(1.5))
+    ]), limits.to_yaml());
+    assert_eq!(Ok("max-size: 5\ntimeout: 1.5\n".to_string()), to_string(&limits));
+    assert_eq!(Ok(limits), FromYaml::from_yaml(&Limits { max_size: 5, timeout: Some(1.5) }.to_yaml()));
+}
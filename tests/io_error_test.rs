@@ -65,3 +65,27 @@ fn error_cause_test_write() {
         Err(e) => assert_eq!(e.cause().and_then(|ioe| ioe.detail()), Some("mock writer".to_string()))
     }
 }
+
+struct DeniedReader;
+
+impl Reader for DeniedReader {
+    fn read(&mut self, _buf: &mut [u8]) -> IoResult<uint> {
+        Err(IoError {
+            kind: IoErrorKind::PermissionDenied,
+            desc: "permission denied",
+            detail: None
+        })
+    }
+}
+
+#[test]
+fn error_io_cause_kind() {
+    let mut reader = DeniedReader;
+    match yaml::parse_io_utf8(&mut reader) {
+        Ok(_) => panic!("Should return an error"),
+        Err(e) => {
+            assert_eq!(Some(IoErrorKind::PermissionDenied), e.io_error_kind());
+            assert_eq!(Some("permission denied"), e.io_cause().map(|ioe| ioe.desc));
+        }
+    }
+}
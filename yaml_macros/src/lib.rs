@@ -0,0 +1,107 @@
+#![crate_name = "yaml_macros"]
+
+#![crate_type = "dylib"]
+
+#![feature(plugin_registrar)]
+#![feature(quote)]
+
+extern crate syntax;
+extern crate rustc;
+
+use syntax::ast;
+use syntax::attr;
+use syntax::codemap::Span;
+use syntax::ext::base::{Decorator, ExtCtxt};
+use syntax::ext::build::AstBuilder;
+use syntax::parse::token;
+use syntax::ptr::P;
+use rustc::plugin::Registry;
+
+#[plugin_registrar]
+pub fn plugin_registrar(reg: &mut Registry) {
+    reg.register_syntax_extension(token::intern("deriving_yaml"), Decorator(box expand_deriving_yaml));
+}
+
+// the mapping key of a field: its name, unless #[yaml(rename = "...")] says otherwise
+fn field_key(cx: &mut ExtCtxt, field: &ast::StructField, name: ast::Ident) -> String {
+    let mut key = token::get_ident(name).get().to_string();
+    for attribute in field.node.attrs.iter() {
+        if !attribute.check_name("yaml") {
+            continue;
+        }
+        attr::mark_used(attribute);
+        let items = match attribute.node.value.node {
+            ast::MetaList(_, ref items) => items,
+            _ => {
+                cx.span_err(attribute.span, "expected #[yaml(rename = \"...\")]");
+                continue;
+            }
+        };
+        for item in items.iter() {
+            match item.node {
+                ast::MetaNameValue(ref attr_name, ref lit) if attr_name.get() == "rename" => match lit.node {
+                    ast::LitStr(ref s, _) => key = s.get().to_string(),
+                    _ => cx.span_err(lit.span, "rename expects a string literal")
+                },
+                _ => cx.span_err(item.span, "unknown yaml attribute")
+            }
+        }
+    }
+    key
+}
+
+fn expand_deriving_yaml(cx: &mut ExtCtxt, span: Span, _meta: &ast::MetaItem, item: &ast::Item,
+                        push: |P<ast::Item>|) {
+    let def = match item.node {
+        ast::ItemStruct(ref def, ref generics) if def.ctor_id.is_none() && !generics.is_parameterized() => def,
+        _ => {
+            cx.span_err(span, "#[deriving_yaml] only supports non-generic structs with named fields");
+            return;
+        }
+    };
+
+    let ty = item.ident;
+    let mut fields = Vec::new();
+    let mut pushes = Vec::new();
+    for field in def.fields.iter() {
+        let name = match field.node.kind {
+            ast::NamedField(name, _) => name,
+            ast::UnnamedField(_) => continue
+        };
+        let key_str = field_key(cx, field, name);
+        let key = cx.expr_str(field.span, token::intern_and_get_ident(key_str.as_slice()));
+
+        fields.push(cx.field_imm(field.span, name,
+                                 quote_expr!(cx, try!(::yaml::typed::field(pairs, $key)))));
+        pushes.push(quote_stmt!(cx,
+            pairs.push((::yaml::constructor::YamlStandardData::YamlString($key.to_string()),
+                        ::yaml::typed::ToYaml::to_yaml(&self.$name)));
+        ));
+    }
+
+    let construct = cx.expr_struct_ident(span, ty, fields);
+    let from_yaml = quote_item!(cx,
+        impl ::yaml::typed::FromYaml for $ty {
+            fn from_yaml(value: &::yaml::constructor::YamlStandardData)
+                -> Result<$ty, ::yaml::error::YamlError>
+            {
+                let pairs = try!(::yaml::typed::mapping(value));
+                Ok($construct)
+            }
+        }
+    );
+
+    let result = quote_expr!(cx, ::yaml::constructor::YamlStandardData::YamlMapping(pairs));
+    let body = cx.expr_block(cx.block(span, pushes, Some(result)));
+    let to_yaml = quote_item!(cx,
+        impl ::yaml::typed::ToYaml for $ty {
+            fn to_yaml(&self) -> ::yaml::constructor::YamlStandardData {
+                let mut pairs = Vec::new();
+                $body
+            }
+        }
+    );
+
+    push(from_yaml.unwrap());
+    push(to_yaml.unwrap());
+}